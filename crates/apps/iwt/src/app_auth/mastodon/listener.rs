@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::commons::auth::token_db::{SqliteTokenDB, TokenDB};
+use crate::social::Account;
+use axum::{
+    extract::Query,
+    response::{Html, IntoResponse},
+    routing::get,
+    Extension, Router,
+};
+use oauth2::AccessToken;
+use rusqlite::Connection;
+use serde_derive::Deserialize;
+use tokio::sync::mpsc::Sender;
+
+use super::{App, Error, REDIRECT_URI, SCOPES};
+use crate::config::{self, Config};
+
+struct State {
+    base_uri: String,
+    oauth_state: String,
+    client_id: String,
+    client_secret: String,
+    account: Account,
+    shutdown_signal: Sender<()>,
+    db_path: String,
+}
+
+pub async fn start(
+    config: &Config,
+    mastodon: &config::Mastodon,
+    account: &Account,
+    app: &App,
+    csrf_state: &str,
+) -> Result<(), Error> {
+    // Create a channel to be able to shut down the webserver from the
+    // Request handler after receiving the auth code
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(10);
+
+    // Initialise the shared state
+    let state = Arc::new(State {
+        base_uri: mastodon.base_uri.clone(),
+        oauth_state: csrf_state.to_string(),
+        client_id: app.client_id.clone(),
+        client_secret: app.client_secret.clone(),
+        account: account.clone(),
+        shutdown_signal: tx,
+        db_path: config.db.path.clone(),
+    });
+
+    let sock_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 6009);
+    let app = Router::new()
+        .route("/", get(receive_token))
+        // share the state with the request handler
+        .layer(Extension(state));
+
+    axum::Server::bind(&sock_addr)
+        .serve(app.into_make_service())
+        // gracefuly shut down the server when we receive a message on the
+        // previously created channel
+        .with_graceful_shutdown(async {
+            rx.recv().await;
+        })
+        .await
+        .map_err(|_| Error::ListenerError())
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token_type: String,
+    access_token: String,
+    scope: String,
+}
+
+async fn receive_token(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(state): Extension<Arc<State>>,
+) -> impl IntoResponse {
+    if params.get("state") != Some(&state.oauth_state) {
+        log::error!("Invalid state param, expected: {}", state.oauth_state);
+        return Html("<h1>Invalid state param</h1><p>Please restart the authentication flow.</p>");
+    }
+
+    let auth_code = match params.get("code") {
+        Some(auth_code) => auth_code,
+        None => {
+            log::error!("Auth code param not found");
+            return Html(
+                "<h1>Auth code param not found</h1><p>Please restart the authentication flow.</p>",
+            );
+        }
+    };
+    log::debug!("Got auth code, exchanging for access token");
+    log::debug!("auth_code is {}", auth_code);
+
+    let tokens = match exchange_auth_code(auth_code, &state).await {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            log::error!("Couldn't exchange the auth code: {}", err);
+            return Html("<h1>Couldn't get the access token</h1><p>See the logs and restart the authentication flow.</p>");
+        }
+    };
+
+    println!(
+        "
+token_type: {}
+access_token: {}
+scope: {}
+",
+        tokens.token_type, tokens.access_token, tokens.scope
+    );
+
+    if let Err(err) = persist_token(&tokens, &state.account, &state.db_path) {
+        log::error!("Couldn't persist the access token: {}", err);
+        return Html("<h1>Couldn't store the access token</h1><p>See the logs and restart the authentication flow.</p>");
+    }
+
+    // Send the shut down signal
+    if state.shutdown_signal.send(()).await.is_err() {
+        log::warn!("The listener is already shutting down");
+    }
+
+    Html("<h1>Hello from mastodon-auth</h1><p>Your token is displayed on the standard output.</p>")
+}
+
+/// Exchanges the auth code to an access token
+async fn exchange_auth_code(
+    auth_code: &str,
+    state: &State,
+) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+    let params = [
+        ("code", auth_code),
+        ("grant_type", "authorization_code"),
+        ("client_id", state.client_id.as_str()),
+        ("client_secret", state.client_secret.as_str()),
+        ("redirect_uri", REDIRECT_URI),
+        ("scope", SCOPES),
+    ];
+
+    let client = reqwest::Client::new();
+    let json = client
+        .post(format!("{}/oauth/token", state.base_uri))
+        .form(&params)
+        .send()
+        .await?
+        .text()
+        .await?;
+    log::debug!("json: {}", json);
+
+    Ok(serde_json::from_str::<TokenResponse>(&json)?)
+}
+
+fn persist_token(
+    tokens: &TokenResponse,
+    account: &Account,
+    db_path: &String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let token_db = SqliteTokenDB::new(Rc::new(Connection::open(db_path)?));
+    token_db.init_table()?;
+
+    token_db.store_access_token(account, &AccessToken::new(tokens.access_token.clone()))
+}
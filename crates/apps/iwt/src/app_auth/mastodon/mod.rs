@@ -0,0 +1,172 @@
+
+use std::fmt::Display;
+
+use crate::config::{self, Config};
+use crate::social::Account;
+
+use rand::{rngs::OsRng, RngCore};
+use serde_derive::Deserialize;
+
+mod listener;
+
+const REDIRECT_URI: &str = "http://127.0.0.1:6009";
+const SCOPES: &str = "read write";
+
+#[derive(Debug)]
+pub enum Error {
+    ListenerError(),
+    RegistrationError(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ListenerError() => write!(f, "ListenerError"),
+            Error::RegistrationError(message) => write!(f, "RegistrationError: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Client credentials of the application registered on the Mastodon instance
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct App {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+pub async fn start_flow(
+    config: &Config,
+    mastodon: &config::Mastodon,
+    account: &Account,
+) -> Result<(), Error> {
+    let base_uri = &mastodon.base_uri;
+    let app = register_app(base_uri).await?;
+
+    // Create CSRF state
+    let mut csrf_state = [0u8; 64];
+    OsRng.fill_bytes(&mut csrf_state);
+    let csrf_state = base64::encode(csrf_state);
+
+    let oauth_uri = construct_uri(base_uri, &app.client_id, &csrf_state);
+    println!(
+        "Open the following link in your browser:
+
+{}
+",
+        oauth_uri
+    );
+
+    listener::start(config, mastodon, account, &app, &csrf_state).await
+}
+
+/// Registers iwt as an application on the Mastodon instance
+async fn register_app(base_uri: &str) -> Result<App, Error> {
+    let params = [
+        ("client_name", "indieweb-tools"),
+        ("redirect_uris", REDIRECT_URI),
+        ("scopes", SCOPES),
+    ];
+
+    let response = reqwest::Client::new()
+        .post(format!("{base_uri}/api/v1/apps"))
+        .form(&params)
+        .send()
+        .await
+        .map_err(|err| Error::RegistrationError(err.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(Error::RegistrationError(format!(
+            "Unexpected status: {}",
+            response.status()
+        )));
+    }
+
+    response
+        .json::<App>()
+        .await
+        .map_err(|err| Error::RegistrationError(err.to_string()))
+}
+
+fn construct_uri(base_uri: &str, client_id: &str, csrf_state: &str) -> String {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("response_type", "code")
+        .append_pair("client_id", client_id)
+        .append_pair("redirect_uri", REDIRECT_URI)
+        .append_pair("scope", SCOPES)
+        .append_pair("state", csrf_state)
+        .finish();
+
+    // Construct URI that starts the Oauth flow
+    format!("{base_uri}/oauth/authorize?{query}")
+}
+
+#[cfg(test)]
+mod test {
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{construct_uri, register_app, App};
+
+    #[test]
+    fn test_construct_uri() {
+        assert_eq!(
+            construct_uri("https://mastodon.example", "some-client-id", "some-state"),
+            "https://mastodon.example/oauth/authorize?response_type=code&client_id=some-client-id&redirect_uri=http%3A%2F%2F127.0.0.1%3A6009&scope=read+write&state=some-state"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_app_returns_client_credentials() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/apps"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{
+                    "id": "563419",
+                    "name": "indieweb-tools",
+                    "redirect_uri": "http://127.0.0.1:6009",
+                    "client_id": "some-client-id",
+                    "client_secret": "some-client-secret"
+                }"#,
+                "application/json",
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let app = register_app(&mock_server.uri()).await.unwrap();
+
+        assert_eq!(
+            app,
+            App {
+                client_id: String::from("some-client-id"),
+                client_secret: String::from("some-client-secret"),
+            }
+        );
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let form: Vec<(String, String)> = url::form_urlencoded::parse(&requests[0].body)
+            .into_owned()
+            .collect();
+        assert!(form
+            .iter()
+            .any(|(k, v)| k == "redirect_uris" && v == "http://127.0.0.1:6009"));
+    }
+
+    #[tokio::test]
+    async fn test_register_app_fails_on_unexpected_status() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/apps"))
+            .respond_with(ResponseTemplate::new(422))
+            .mount(&mock_server)
+            .await;
+
+        assert!(register_app(&mock_server.uri()).await.is_err());
+    }
+}
@@ -1,26 +1,51 @@
 
-use crate::config::Config;
+use crate::config::{Config, TargetKind};
+use crate::social::Network;
+use crate::IwtError;
 
 use clap::Subcommand;
 
+mod mastodon;
 mod twitter;
 
 #[derive(Subcommand)]
 pub enum AuthSubcommand {
     /// Twitter Oauth flow
-    Twitter,
+    Twitter {
+        /// Name of the target to authenticate, defaults to the first Twitter target
+        #[clap(long, value_parser)]
+        name: Option<String>,
+    },
     /// Mastodon Oauth flow
-    Mastodon,
+    Mastodon {
+        /// Name of the target to authenticate, defaults to the first Mastodon target
+        #[clap(long, value_parser)]
+        name: Option<String>,
+    },
 }
 
 pub async fn execute(
     command: AuthSubcommand,
     config: &Config,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    match command {
-        AuthSubcommand::Twitter => twitter::start_flow(config)
+    let (network, name) = match &command {
+        AuthSubcommand::Twitter { name } => (Network::Twitter, name),
+        AuthSubcommand::Mastodon { name } => (Network::Mastodon, name),
+    };
+
+    let target = config
+        .find_target(&network, name.as_deref())
+        .ok_or_else(|| IwtError::new(&format!("No {network} target is configured")))?;
+
+    match &target.kind {
+        TargetKind::Twitter(twitter) => twitter::start_flow(config, twitter, &target.account())
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
+        TargetKind::Mastodon(mastodon) => mastodon::start_flow(config, mastodon, &target.account())
             .await
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
-        AuthSubcommand::Mastodon => todo!(),
+        TargetKind::Bluesky(_) => Err(Box::new(IwtError::new(
+            "Bluesky uses app passwords, there is no app-auth flow",
+        ))),
     }
 }
\ No newline at end of file
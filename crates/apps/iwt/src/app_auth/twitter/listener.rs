@@ -1,32 +1,42 @@
 use std::{
     collections::HashMap,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    rc::Rc,
     sync::Arc,
 };
 
-use crate::social::Network::Twitter;
+use crate::commons::auth::token_db::{SqliteTokenDB, TokenDB};
+use crate::social::Account;
 use axum::{
     extract::Query,
     response::{Html, IntoResponse},
     routing::get,
     Extension, Router,
 };
+use oauth2::{AccessToken, RefreshToken};
 use rusqlite::Connection;
 use serde_derive::Deserialize;
 use tokio::sync::mpsc::Sender;
 
 use super::Error;
-use crate::config::Config;
+use crate::config::{self, Config};
 
 struct State {
     challenge: String,
     oauth_state: String,
     client_id: String,
+    account: Account,
     shutdown_signal: Sender<()>,
     db_path: String,
 }
 
-pub async fn start(config: &Config, challenge: &str, csrf_state: &str) -> Result<(), Error> {
+pub async fn start(
+    config: &Config,
+    twitter: &config::Twitter,
+    account: &Account,
+    challenge: &str,
+    csrf_state: &str,
+) -> Result<(), Error> {
     // Create a channel to be able to shut down the webserver from the
     // Request handler after receiving the auth code
     let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(10);
@@ -35,7 +45,8 @@ pub async fn start(config: &Config, challenge: &str, csrf_state: &str) -> Result
     let state = Arc::new(State {
         challenge: challenge.to_string(),
         oauth_state: csrf_state.to_string(),
-        client_id: config.twitter.client_id.to_string(),
+        client_id: twitter.client_id.to_string(),
+        account: account.clone(),
         shutdown_signal: tx,
         db_path: config.db.path.clone(),
     });
@@ -50,7 +61,9 @@ pub async fn start(config: &Config, challenge: &str, csrf_state: &str) -> Result
         .serve(app.into_make_service())
         // gracefuly shut down the server when we receive a message on the
         // previously created channel
-        .with_graceful_shutdown(async { rx.recv().await.unwrap() })
+        .with_graceful_shutdown(async {
+            rx.recv().await;
+        })
         .await
         .map_err(|_| Error::ListenerError())
 }
@@ -66,43 +79,30 @@ async fn receive_token(
     Query(params): Query<HashMap<String, String>>,
     Extension(state): Extension<Arc<State>>,
 ) -> impl IntoResponse {
-    let state_param = params.get("state").expect("state param not found");
-
-    assert!(
-        state_param != &state.oauth_state,
-        "Invalid state param,
-expected: {}
-got     : {}",
-        state.oauth_state,
-        state_param
-    );
-
-    let auth_code = params.get("code").expect("auth code param not found");
+    if params.get("state") != Some(&state.oauth_state) {
+        log::error!("Invalid state param, expected: {}", state.oauth_state);
+        return Html("<h1>Invalid state param</h1><p>Please restart the authentication flow.</p>");
+    }
+
+    let auth_code = match params.get("code") {
+        Some(auth_code) => auth_code,
+        None => {
+            log::error!("Auth code param not found");
+            return Html(
+                "<h1>Auth code param not found</h1><p>Please restart the authentication flow.</p>",
+            );
+        }
+    };
     log::debug!("Got auth code, exchanging for access token");
     log::debug!("auth_code is {}", auth_code);
 
-    let challenge = state.challenge.to_string();
-    let params = [
-        ("code", auth_code.as_str()),
-        ("grant_type", "authorization_code"),
-        ("client_id", state.client_id.as_str()),
-        ("code_verifier", challenge.as_str()),
-        ("redirect_uri", "http://127.0.0.1:6009"),
-    ];
-
-    // Exchange the auth code to an access_token and a refresh_token
-    let client = reqwest::Client::new();
-    let result = client
-        .post("https://api.twitter.com/2/oauth2/token")
-        .form(&params)
-        .send()
-        .await
-        .expect("Oauth request failed");
-
-    let json = result.text().await.expect("Couldn't get response body");
-    log::debug!("json: {}", json);
-    let tokens =
-        serde_json::from_str::<TokenResponse>(&json).expect("Coulnd't decode json response");
+    let tokens = match exchange_auth_code(auth_code, &state).await {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            log::error!("Couldn't exchange the auth code: {}", err);
+            return Html("<h1>Couldn't get the tokens</h1><p>See the logs and restart the authentication flow.</p>");
+        }
+    };
 
     println!(
         "
@@ -115,36 +115,59 @@ refresh_token: {}
 
     // TODO: add argument to be able to disable updating the db
     // if let Some(db_path) = state.db_path.clone() {
-    persist_tokens(&tokens, &state.db_path).expect("couldn't persist tokens");
+    if let Err(err) = persist_tokens(&tokens, &state.account, &state.db_path) {
+        log::error!("Couldn't persist the tokens: {}", err);
+        return Html("<h1>Couldn't store the tokens</h1><p>See the logs and restart the authentication flow.</p>");
+    }
     // }
 
     // Send the shut down signal
-    state.shutdown_signal.send(()).await.unwrap();
+    if state.shutdown_signal.send(()).await.is_err() {
+        log::warn!("The listener is already shutting down");
+    }
 
     Html("<h1>Hello from twitter-auth</h1><p>Your tokens are displayed on the standard output.</p>")
 }
 
-fn persist_tokens(tokens: &TokenResponse, db_path: &String) -> rusqlite::Result<()> {
+/// Exchanges the auth code to an access token and a refresh token
+async fn exchange_auth_code(
+    auth_code: &str,
+    state: &State,
+) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+    let challenge = state.challenge.to_string();
+    let params = [
+        ("code", auth_code),
+        ("grant_type", "authorization_code"),
+        ("client_id", state.client_id.as_str()),
+        ("code_verifier", challenge.as_str()),
+        ("redirect_uri", "http://127.0.0.1:6009"),
+    ];
+
+    let client = reqwest::Client::new();
+    let json = client
+        .post("https://api.twitter.com/2/oauth2/token")
+        .form(&params)
+        .send()
+        .await?
+        .text()
+        .await?;
+    log::debug!("json: {}", json);
+
+    Ok(serde_json::from_str::<TokenResponse>(&json)?)
+}
+
+fn persist_tokens(
+    tokens: &TokenResponse,
+    account: &Account,
+    db_path: &String,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Initialize db to store tokens
-    let conn = Connection::open(db_path)?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS auth_token (
-            social_network VARCHAR(20) PRIMARY KEY,
-            access_token   TEXT,
-            refresh_token  TEXT
-        )
-        ",
-        (),
-    )?;
-
-    conn.execute(
-        "INSERT INTO auth_token (social_network, access_token, refresh_token)
-         VALUES (?1, ?2, ?3)
-         ON CONFLICT (social_network) 
-            DO UPDATE SET access_token = excluded.access_token, refresh_token = excluded.refresh_token",
-        (Twitter.to_string().as_str(), tokens.access_token.clone(), tokens.refresh_token.clone())
-    )?;
-
-    Ok(())
+    let token_db = SqliteTokenDB::new(Rc::new(Connection::open(db_path)?));
+    token_db.init_table()?;
+
+    token_db.store(
+        account,
+        &AccessToken::new(tokens.access_token.clone()),
+        &RefreshToken::new(tokens.refresh_token.clone()),
+    )
 }
@@ -1,7 +1,8 @@
 
 use std::fmt::Display;
 
-use crate::config::Config;
+use crate::config::{self, Config};
+use crate::social::Account;
 
 use rand::{rngs::OsRng, RngCore};
 
@@ -20,7 +21,11 @@ impl Display for Error {
 
 impl std::error::Error for Error {}
 
-pub async fn start_flow(config: &Config) -> Result<(), Error> {
+pub async fn start_flow(
+    config: &Config,
+    twitter: &config::Twitter,
+    account: &Account,
+) -> Result<(), Error> {
     // Create CSRF state and secret challenge
     let mut challenge = [0u8; 64];
     let mut csrf_state = [0u8; 64];
@@ -31,7 +36,7 @@ pub async fn start_flow(config: &Config) -> Result<(), Error> {
     let challenge = base64::encode(challenge);
     let csrf_state = base64::encode(csrf_state);
 
-    let oauth_uri = construct_uri(&config.twitter.client_id, &csrf_state, &challenge);
+    let oauth_uri = construct_uri(&twitter.client_id, &csrf_state, &challenge);
     println!(
         "Open the following link in your browser:
 
@@ -40,7 +45,7 @@ pub async fn start_flow(config: &Config) -> Result<(), Error> {
         oauth_uri
     );
 
-    listener::start(config, &challenge, &csrf_state).await
+    listener::start(config, twitter, account, &challenge, &csrf_state).await
 }
 
 fn construct_uri(client_id: &str, csrf_state: &str, challenge: &str) -> String {
@@ -48,7 +53,10 @@ fn construct_uri(client_id: &str, csrf_state: &str, challenge: &str) -> String {
         .append_pair("response_type", "code")
         .append_pair("client_id", client_id)
         .append_pair("redirect_uri", "http://127.0.0.1:6009")
-        .append_pair("scope", "tweet.read tweet.write users.read offline.access")
+        .append_pair(
+            "scope",
+            "tweet.read tweet.write users.read like.read media.write offline.access",
+        )
         .append_pair("state", csrf_state)
         .append_pair("code_challenge", challenge)
         .append_pair("code_challenge_method", "plain")
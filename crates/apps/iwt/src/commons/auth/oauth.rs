@@ -3,7 +3,7 @@ use std::rc::Rc;
 
 use async_mutex::Mutex;
 
-use crate::social::Network;
+use crate::social::Account;
 
 use super::token_db::TokenDB;
 use oauth2::{
@@ -20,30 +20,31 @@ struct TokenCredentials {
 pub struct AuthedClient<DB: TokenDB> {
     oauth_client: BasicClient,
     db: Rc<DB>,
-    social_network: Network,
+    account: Account,
     http_client: Client,
     // TODO: do we need this async mutex here? Couldn't we use TokenDB / sled directly?
     tokens: Mutex<TokenCredentials>,
 }
 
 impl<DB: TokenDB> AuthedClient<DB> {
-    pub fn new(social_network: Network, oauth_client: BasicClient, db: Rc<DB>) -> Self {
-        let access_token = db
-            .get_access_token(&social_network)
-            .expect("Couldn't load access token");
-        let refresh_token = db
-            .get_refresh_token(&social_network)
-            .expect("Couldn't load refresh token");
-        Self {
+    /// Loads the tokens of the account, they are obtained by the `app-auth` flow
+    pub fn new(
+        account: Account,
+        oauth_client: BasicClient,
+        db: Rc<DB>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let access_token = db.get_access_token(&account)?;
+        let refresh_token = db.get_refresh_token(&account)?;
+        Ok(Self {
             oauth_client,
             db,
-            social_network,
+            account,
             http_client: reqwest::Client::new(),
             tokens: Mutex::new(TokenCredentials {
                 access_token,
                 refresh_token,
             }),
-        }
+        })
     }
 
     pub async fn authed_request(
@@ -117,11 +118,7 @@ impl<DB: TokenDB> AuthedClient<DB> {
                 );
 
                 self.db
-                    .store(
-                        &self.social_network,
-                        &tokens.access_token,
-                        &tokens.refresh_token,
-                    )
+                    .store(&self.account, &tokens.access_token, &tokens.refresh_token)
                     .map(|_| tokens)
             }
         }
@@ -141,7 +138,7 @@ mod test {
     use std::rc::Rc;
 
     use crate::commons::auth::token_db::TokenDB;
-    use crate::social::Network;
+    use crate::social::{Account, Network};
     use oauth2::{basic::BasicClient, AuthUrl, ClientId, TokenUrl};
     use reqwest::{Method, Request, StatusCode, Url};
     use wiremock::{
@@ -168,7 +165,12 @@ mod test {
         let shared_db = Rc::new(db);
         (
             Rc::clone(&shared_db),
-            AuthedClient::new(Network::Twitter, basic_client(base_url), shared_db),
+            AuthedClient::new(
+                Account::from(Network::Twitter),
+                basic_client(base_url),
+                shared_db,
+            )
+            .unwrap(),
         )
     }
 
@@ -320,12 +322,16 @@ mod test {
         // The tokens are updated in the db
         assert_eq!(
             "new-access-token",
-            db.get_access_token(&Network::Twitter).unwrap().secret(),
+            db.get_access_token(&Account::from(Network::Twitter))
+                .unwrap()
+                .secret(),
         );
 
         assert_eq!(
             "new-refresh-token",
-            db.get_refresh_token(&Network::Twitter).unwrap().secret(),
+            db.get_refresh_token(&Account::from(Network::Twitter))
+                .unwrap()
+                .secret(),
         );
     }
 }
\ No newline at end of file
@@ -4,23 +4,30 @@ use std::rc::Rc;
 use oauth2::{AccessToken, RefreshToken};
 use rusqlite::Connection;
 
-use crate::social::Network;
+use crate::commons::db;
+use crate::social::Account;
 
 pub trait TokenDB {
     fn get_access_token(
         &self,
-        social_network: &Network,
+        account: &Account,
     ) -> Result<AccessToken, Box<dyn std::error::Error>>;
     fn get_refresh_token(
         &self,
-        social_network: &Network,
+        account: &Account,
     ) -> Result<RefreshToken, Box<dyn std::error::Error>>;
     fn store(
         &self,
-        social_network: &Network,
+        account: &Account,
         access_token: &AccessToken,
         refresh_token: &RefreshToken,
     ) -> Result<(), Box<dyn std::error::Error>>;
+    /// Stores an access token for networks that don't issue refresh tokens (i.e. Mastodon)
+    fn store_access_token(
+        &self,
+        account: &Account,
+        access_token: &AccessToken,
+    ) -> Result<(), Box<dyn std::error::Error>>;
 }
 
 pub struct SqliteTokenDB {
@@ -31,17 +38,39 @@ impl SqliteTokenDB {
     pub fn new(conn: Rc<Connection>) -> Self {
         Self { conn }
     }
+
+    pub fn init_table(&self) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS auth_token (
+                account       VARCHAR(64) PRIMARY KEY,
+                access_token  TEXT,
+                refresh_token TEXT
+            )",
+            (),
+        )?;
+
+        // Tokens used to be stored per social network, these rows belong to the default accounts
+        // which are named after the network
+        if db::has_column(&self.conn, "auth_token", "social_network")? {
+            self.conn.execute(
+                "ALTER TABLE auth_token RENAME COLUMN social_network TO account",
+                (),
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
 impl TokenDB for SqliteTokenDB {
     fn get_access_token(
         &self,
-        social_network: &Network,
+        account: &Account,
     ) -> Result<AccessToken, Box<dyn std::error::Error>> {
         self.conn
             .query_row(
-                "SELECT access_token FROM auth_token WHERE social_network = :social_network",
-                &[(":social_network", social_network.to_string().as_str())],
+                "SELECT access_token FROM auth_token WHERE account = :account",
+                &[(":account", account.name.as_str())],
                 |row| row.get("access_token").map(AccessToken::new),
             )
             .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
@@ -49,12 +78,12 @@ impl TokenDB for SqliteTokenDB {
 
     fn get_refresh_token(
         &self,
-        social_network: &Network,
+        account: &Account,
     ) -> Result<RefreshToken, Box<dyn std::error::Error>> {
         self.conn
             .query_row(
-                "SELECT refresh_token FROM auth_token WHERE social_network = :social_network",
-                &[(":social_network", social_network.to_string().as_str())],
+                "SELECT refresh_token FROM auth_token WHERE account = :account",
+                &[(":account", account.name.as_str())],
                 |row| row.get("refresh_token").map(RefreshToken::new),
             )
             .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
@@ -62,18 +91,96 @@ impl TokenDB for SqliteTokenDB {
 
     fn store(
         &self,
-        social_network: &Network,
+        account: &Account,
         access_token: &AccessToken,
         refresh_token: &RefreshToken,
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.conn.execute(
-            "INSERT INTO auth_token (social_network, access_token, refresh_token)
+            "INSERT INTO auth_token (account, access_token, refresh_token)
              VALUES (?1, ?2, ?3)
-             ON CONFLICT (social_network) 
+             ON CONFLICT (account)
                 DO UPDATE SET access_token = excluded.access_token, refresh_token = excluded.refresh_token",
-            (social_network.to_string().as_str(), access_token.secret(), refresh_token.secret())
+            (account.name.as_str(), access_token.secret(), refresh_token.secret())
         )
             .map(|_| ())
             .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
     }
-}
\ No newline at end of file
+
+    fn store_access_token(
+        &self,
+        account: &Account,
+        access_token: &AccessToken,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn
+            .execute(
+                "INSERT INTO auth_token (account, access_token)
+                 VALUES (?1, ?2)
+                 ON CONFLICT (account)
+                    DO UPDATE SET access_token = excluded.access_token",
+                (account.name.as_str(), access_token.secret()),
+            )
+            .map(|_| ())
+            .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use oauth2::AccessToken;
+    use rusqlite::Connection;
+
+    use super::{SqliteTokenDB, TokenDB};
+    use crate::social::{Account, Network};
+
+    #[test]
+    fn test_tokens_are_stored_per_account() {
+        let token_db = SqliteTokenDB::new(Rc::new(Connection::open_in_memory().unwrap()));
+        token_db.init_table().unwrap();
+
+        let personal = Account::new(Network::Mastodon, "personal");
+        let project = Account::new(Network::Mastodon, "project");
+
+        token_db
+            .store_access_token(&personal, &AccessToken::new(String::from("personal-token")))
+            .unwrap();
+        token_db
+            .store_access_token(&project, &AccessToken::new(String::from("project-token")))
+            .unwrap();
+
+        assert_eq!(
+            token_db.get_access_token(&personal).unwrap().secret(),
+            "personal-token"
+        );
+        assert_eq!(
+            token_db.get_access_token(&project).unwrap().secret(),
+            "project-token"
+        );
+    }
+
+    #[test]
+    fn test_tokens_stored_per_network_belong_to_the_default_account() {
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        conn.execute_batch(
+            "CREATE TABLE auth_token (
+                social_network VARCHAR(20) PRIMARY KEY,
+                access_token   TEXT,
+                refresh_token  TEXT
+            );
+            INSERT INTO auth_token VALUES ('twitter', 'some-access-token', 'some-refresh-token');",
+        )
+        .unwrap();
+
+        let token_db = SqliteTokenDB::new(conn);
+        token_db.init_table().unwrap();
+
+        assert_eq!(
+            token_db
+                .get_access_token(&Account::from(Network::Twitter))
+                .unwrap()
+                .secret(),
+            "some-access-token"
+        );
+    }
+}
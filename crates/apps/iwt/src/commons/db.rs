@@ -0,0 +1,26 @@
+use rusqlite::Connection;
+
+/// Checks whether the table has the given column, used by the schema migrations
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut statement = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let mut columns = statement.query_map((), |row| row.get::<_, String>("name"))?;
+
+    columns.try_fold(false, |found, name| Ok(found || name? == column))
+}
+
+#[cfg(test)]
+mod test {
+    use rusqlite::Connection;
+
+    use super::has_column;
+
+    #[test]
+    fn test_has_column() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE some_table (some_column TEXT)", ())
+            .unwrap();
+
+        assert!(has_column(&conn, "some_table", "some_column").unwrap());
+        assert!(!has_column(&conn, "some_table", "other_column").unwrap());
+    }
+}
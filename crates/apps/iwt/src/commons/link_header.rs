@@ -0,0 +1,99 @@
+/// The URI of the first link of the `Link` header with the relation type, i.e. `next` in
+/// `<https://example.com/?page=2>; rel="next"`. The relation types of a link are space separated
+/// and compared case-insensitively, see https://www.rfc-editor.org/rfc/rfc8288
+#[must_use]
+pub fn find_rel(header: &str, rel: &str) -> Option<String> {
+    let mut rest = header;
+    loop {
+        let start = rest.find('<')?;
+        // The URI ends at the `>`, it may contain commas
+        let (uri, after) = rest[start + 1..].split_once('>')?;
+        let (params, next) = split_params(after);
+
+        let has_rel = params
+            .split(';')
+            .filter_map(|param| param.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .any(|(_, value)| {
+                value
+                    .trim()
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .any(|value| value.eq_ignore_ascii_case(rel))
+            });
+        if has_rel {
+            return Some(uri.to_string());
+        }
+
+        rest = next;
+    }
+}
+
+/// Splits the parameters of a link from the next links, at the first comma which isn't quoted
+fn split_params(input: &str) -> (&str, &str) {
+    let mut quoted = false;
+    for (i, c) in input.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => return (&input[..i], &input[i + 1..]),
+            _ => {}
+        }
+    }
+
+    (input, "")
+}
+
+#[cfg(test)]
+mod test {
+    use super::find_rel;
+
+    #[test]
+    fn test_find_rel_returns_the_uri_of_the_first_link_with_the_rel() {
+        let header =
+            r#"<https://hub.example.com/>; rel="hub", <http://example.com/rss.xml>; rel="self""#;
+
+        assert_eq!(
+            find_rel(header, "self"),
+            Some(String::from("http://example.com/rss.xml"))
+        );
+        assert_eq!(
+            find_rel(header, "hub"),
+            Some(String::from("https://hub.example.com/"))
+        );
+        assert_eq!(find_rel(header, "next"), None);
+        assert_eq!(
+            find_rel("<https://hub.example.com/>; rel=hub", "self"),
+            None
+        );
+        assert_eq!(find_rel("", "self"), None);
+    }
+
+    #[test]
+    fn test_find_rel_matches_one_of_the_space_separated_rels_case_insensitively() {
+        assert_eq!(
+            find_rel(
+                r#"<https://example.com/feed>; rel="alternate", </webmention>; REL="other WebMention""#,
+                "webmention"
+            ),
+            Some(String::from("/webmention"))
+        );
+        assert_eq!(
+            find_rel("<https://example.com/?page=2>; rel=next", "next"),
+            Some(String::from("https://example.com/?page=2"))
+        );
+    }
+
+    #[test]
+    fn test_find_rel_handles_commas_in_the_uris_and_in_quoted_params() {
+        let header = r#"<https://example.com/?ids=1,2>; title="a, b"; rel="prev", <https://example.com/?ids=3,4>; rel="next""#;
+
+        assert_eq!(
+            find_rel(header, "prev"),
+            Some(String::from("https://example.com/?ids=1,2"))
+        );
+        assert_eq!(
+            find_rel(header, "next"),
+            Some(String::from("https://example.com/?ids=3,4"))
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use std::{error::Error, fmt::Display};
 
 pub mod auth;
+pub mod db;
+pub mod link_header;
 pub mod permashort_link;
 pub mod text;
 pub mod url_shortener;
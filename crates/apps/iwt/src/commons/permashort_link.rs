@@ -1,4 +1,6 @@
 
+use std::fmt::Display;
+
 pub struct PermashortCitation {
     protocol: String,
     domain: String,
@@ -21,10 +23,9 @@ impl PermashortCitation {
     }
 }
 
-impl ToString for PermashortCitation {
-    #[must_use]
-    fn to_string(&self) -> String {
-        format!("{} {}", self.domain, self.short_url)
+impl Display for PermashortCitation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.domain, self.short_url)
     }
 }
 
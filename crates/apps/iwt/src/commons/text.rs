@@ -4,6 +4,9 @@ use convert_case::{Case, Casing};
 use regex::Regex;
 use scraper::{Html, Selector};
 
+/// Twitter and Mastodon count every link as 23 characters, the other networks count the link
+const COUNTED_LINK_LENGTH: usize = 23;
+
 #[must_use]
 pub fn shorten(text: &str, limit: usize) -> &str {
     let words = words(text);
@@ -24,38 +27,177 @@ pub fn shorten_with_permashort_citation(
     permashort_citation: &PermashortCitation,
     tags: &[String],
 ) -> String {
-    let hash_tags = tags
-        .iter()
-        .map(|tag| String::from("#") + &tag.to_case(Case::Pascal))
-        .collect::<Vec<_>>()
-        .join(" ");
+    let hash_tags = hash_tags(tags);
 
     let (cleaned, short) = clean_description(text);
 
-    let suffix = if short {
-        format!("\n{hash_tags} {}", permashort_citation.to_uri())
-    } else {
-        format!("\n{hash_tags} ({})", permashort_citation.to_string())
-    };
+    let suffix = citation_suffix(&hash_tags, permashort_citation, short);
 
-    let shortened = shorten(&cleaned, limit - suffix.len());
+    let shortened = shorten(&cleaned, limit.saturating_sub(suffix.len()));
 
     if shortened == cleaned {
         let mut appended = cleaned;
         appended.push_str(&suffix);
         appended
     } else {
+        let uri = permashort_citation.to_uri();
         let shortened = shorten(
             &cleaned,
-            limit - 23 - 4 - hash_tags.len() - 2, /* Link + space + ellipsis + quuotes + hastags + space around hash_tags*/
+            limit.saturating_sub(uri.len().max(COUNTED_LINK_LENGTH) + 4 + hash_tags.len() + 2), /* Link + space + ellipsis + quuotes + hastags + space around hash_tags*/
         );
 
-        format!(
-            "\"{}…\"\n{} {}",
-            shortened,
-            hash_tags,
-            permashort_citation.to_uri()
-        )
+        format!("\"{}…\"\n{} {}", shortened, hash_tags, uri)
+    }
+}
+
+/// Splits the text into parts which can be published as a thread, each part is numbered
+/// (e.g. `1/4`) and the last one carries the permashort citation. If the text fits into a single
+/// post it is returned as is, with the citation.
+#[must_use]
+pub fn thread_with_permashort_citation(
+    text: &str,
+    limit: usize,
+    permashort_citation: &PermashortCitation,
+    tags: &[String],
+) -> Vec<String> {
+    thread_rest_with_permashort_citation(text, None, 0, limit, permashort_citation, tags)
+        .into_iter()
+        .map(|(_, part)| part)
+        .collect()
+}
+
+/// Splits the `rest` of the text into the parts of a thread whose first `published` parts have
+/// already been published, they are numbered after them. The whole text is split if there is no
+/// rest. Each part is returned along with the text it carries, so that the text left after a part
+/// which couldn't be published can be split again.
+#[must_use]
+pub fn thread_rest_with_permashort_citation(
+    text: &str,
+    rest: Option<&str>,
+    published: usize,
+    limit: usize,
+    permashort_citation: &PermashortCitation,
+    tags: &[String],
+) -> Vec<(String, String)> {
+    let (cleaned, short) = clean_description(text);
+    let suffix = citation_suffix(&hash_tags(tags), permashort_citation, short);
+    let rest = rest.map_or(cleaned, String::from);
+
+    if published == 0 && rest.len() + suffix.len() <= limit {
+        return vec![(rest.clone(), rest + &suffix)];
+    }
+
+    // The length of the numbering depends on the number of the parts, start with a single digit
+    // and split again if there are more parts than that
+    let mut digits = 1;
+    loop {
+        let numbering_len = " /".len() + 2 * digits;
+        let parts = split(&rest, limit.saturating_sub(numbering_len), suffix.len());
+        if parts.is_empty() {
+            return vec![(rest.clone(), rest + &suffix)];
+        }
+        let count = published + parts.len();
+
+        if count.to_string().len() <= digits {
+            return parts
+                .into_iter()
+                .enumerate()
+                .map(|(i, part)| {
+                    let number = published + i + 1;
+                    let text = if number == count {
+                        format!("{part} {number}/{count}{suffix}")
+                    } else {
+                        format!("{part} {number}/{count}")
+                    };
+                    (part, text)
+                })
+                .collect();
+        }
+
+        digits = count.to_string().len();
+    }
+}
+
+/// Splits the text on word boundaries into parts not longer than the limit, leaving room for
+/// `reserved` characters in the last part. Words longer than the limit are split, and the parts
+/// are never empty.
+fn split(text: &str, limit: usize, reserved: usize) -> Vec<String> {
+    let limit = limit.max(1);
+    let mut parts = vec![];
+    let mut current = String::new();
+
+    for word in words(text)
+        .into_iter()
+        .flat_map(|word| hard_split(word, limit))
+    {
+        if !current.is_empty() && current.len() + 1 + word.len() > limit {
+            parts.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if current.len() + reserved > limit {
+        // Move the leading words of the last part into a new one to make room for the reserved
+        let words = words(&current);
+        let mut i = 0;
+        while i < words.len() && words[i..].join(" ").len() + reserved > limit {
+            i += 1;
+        }
+        if i == words.len() && reserved < limit {
+            // Not even the last word fits beside the reserved, its tail is moved instead
+            let last = words[i - 1];
+            let mut at = last.len() - (limit - reserved);
+            while !last.is_char_boundary(at) {
+                at += 1;
+            }
+            parts.push([&words[..i - 1], &[&last[..at]]].concat().join(" "));
+            current = last[at..].to_string();
+        } else {
+            parts.push(words[..i].join(" "));
+            current = words[i..].join(" ");
+        }
+    }
+    parts.push(current);
+
+    parts.retain(|part| !part.trim().is_empty());
+    parts
+}
+
+/// Splits the word into pieces not longer than the limit on char boundaries, i.e. a long URL
+fn hard_split(word: &str, limit: usize) -> Vec<&str> {
+    let mut pieces = vec![];
+    let mut start = 0;
+
+    for (i, c) in word.char_indices() {
+        if i > start && i + c.len_utf8() - start > limit {
+            pieces.push(&word[start..i]);
+            start = i;
+        }
+    }
+    pieces.push(&word[start..]);
+
+    pieces
+}
+
+fn hash_tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| String::from("#") + &tag.to_case(Case::Pascal))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn citation_suffix(
+    hash_tags: &str,
+    permashort_citation: &PermashortCitation,
+    short: bool,
+) -> String {
+    if short {
+        format!("\n{hash_tags} {}", permashort_citation.to_uri())
+    } else {
+        format!("\n{hash_tags} ({permashort_citation})")
     }
 }
 
@@ -98,7 +240,10 @@ pub fn clean_description(description: &str) -> (String, bool) {
 mod test {
     use crate::commons::permashort_link::PermashortCitation;
 
-    use super::{shorten, shorten_with_permashort_citation};
+    use super::{
+        shorten, shorten_with_permashort_citation, thread_rest_with_permashort_citation,
+        thread_with_permashort_citation,
+    };
 
     #[test]
     fn test_short_returns_same_if_short() {
@@ -167,4 +312,175 @@ mod test {
             "\"Lorem ipsum dolor sit…\"\n#Tag http://localhost/asdf"
         );
     }
+
+    #[test]
+    fn test_shorten_with_permashort_citation_leaves_room_for_a_long_link() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.";
+        let permashort_citation = PermashortCitation::new(
+            "https".to_string(),
+            "short.example.com".to_string(),
+            "s/some-long-slug".to_string(),
+        );
+
+        let shortened =
+            shorten_with_permashort_citation(text, 80, &permashort_citation, &["tag".to_string()]);
+
+        assert_eq!(
+            shortened,
+            "\"Lorem ipsum dolor sit amet,…\"\n#Tag https://short.example.com/s/some-long-slug"
+        );
+        assert!(shortened.chars().count() <= 80);
+    }
+
+    #[test]
+    fn test_thread_with_permashort_citation_returns_a_single_part_if_short() {
+        let permashort_citation = PermashortCitation::new(
+            "http".to_string(),
+            "localhost".to_string(),
+            "asdf".to_string(),
+        );
+        assert_eq!(
+            thread_with_permashort_citation(
+                "This is some text.",
+                100,
+                &permashort_citation,
+                &["tag".to_string()]
+            ),
+            vec!["This is some text.\n#Tag (localhost asdf)"]
+        );
+    }
+
+    #[test]
+    fn test_thread_with_permashort_citation_numbers_the_parts() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.";
+        let permashort_citation = PermashortCitation::new(
+            "http".to_string(),
+            "localhost".to_string(),
+            "asdf".to_string(),
+        );
+
+        let parts =
+            thread_with_permashort_citation(text, 60, &permashort_citation, &["tag".to_string()]);
+
+        assert_eq!(
+            parts,
+            vec![
+                "Lorem ipsum dolor sit amet, consectetur adipiscing elit, 1/3",
+                "sed do eiusmod tempor incididunt ut labore et dolore 2/3",
+                "magna aliqua. 3/3\n#Tag (localhost asdf)",
+            ]
+        );
+        assert!(parts.iter().all(|part| part.len() <= 60));
+    }
+
+    #[test]
+    fn test_thread_rest_with_permashort_citation_numbers_the_parts_after_the_published_ones() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.";
+        let permashort_citation = PermashortCitation::new(
+            "http".to_string(),
+            "localhost".to_string(),
+            "asdf".to_string(),
+        );
+
+        let parts = thread_rest_with_permashort_citation(
+            text,
+            Some("sed do eiusmod tempor incididunt ut labore et dolore magna aliqua."),
+            1,
+            60,
+            &permashort_citation,
+            &["tag".to_string()],
+        );
+
+        assert_eq!(
+            parts,
+            vec![
+                (
+                    String::from("sed do eiusmod tempor incididunt ut labore et dolore"),
+                    String::from("sed do eiusmod tempor incididunt ut labore et dolore 2/3")
+                ),
+                (
+                    String::from("magna aliqua."),
+                    String::from("magna aliqua. 3/3\n#Tag (localhost asdf)")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thread_with_permashort_citation_widens_the_numbering() {
+        let text = "word ".repeat(100);
+        let permashort_citation = PermashortCitation::new(
+            "http".to_string(),
+            "localhost".to_string(),
+            "asdf".to_string(),
+        );
+
+        let parts = thread_with_permashort_citation(text.trim(), 30, &permashort_citation, &[]);
+
+        assert!(parts.len() >= 10);
+        assert!(parts[0].ends_with(&format!(" 1/{}", parts.len())));
+        assert!(parts.iter().all(|part| part.len() <= 30));
+    }
+    #[test]
+    fn test_thread_with_permashort_citation_splits_the_words_longer_than_the_limit() {
+        let text = format!("Some link: https://example.com/{}", "a".repeat(40));
+        let permashort_citation = PermashortCitation::new(
+            "http".to_string(),
+            "localhost".to_string(),
+            "asdf".to_string(),
+        );
+
+        let parts = thread_with_permashort_citation(&text, 40, &permashort_citation, &[]);
+
+        assert_eq!(
+            parts,
+            vec![
+                String::from("Some link: 1/4"),
+                format!("https://example.com/{} 2/4", "a".repeat(16)),
+                format!("{} 3/4", "a".repeat(6)),
+                format!("{} 4/4\n (localhost asdf)", "a".repeat(18)),
+            ]
+        );
+        assert!(parts.iter().all(|part| part.len() <= 40));
+    }
+
+    #[test]
+    fn test_thread_with_permashort_citation_has_no_empty_parts() {
+        let permashort_citation = PermashortCitation::new(
+            "http".to_string(),
+            "localhost".to_string(),
+            "asdf".to_string(),
+        );
+
+        // The citation alone is longer than a part
+        let parts = thread_with_permashort_citation(
+            "Lorem ipsum dolor sit amet, consectetur adipiscing elit",
+            30,
+            &permashort_citation,
+            &["some-long-tag".to_string()],
+        );
+
+        assert_eq!(
+            parts,
+            vec![
+                "Lorem ipsum dolor sit 1/3",
+                "amet, consectetur 2/3",
+                "adipiscing elit 3/3\n#SomeLongTag (localhost asdf)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thread_with_permashort_citation_handles_limits_below_the_numbering() {
+        let permashort_citation = PermashortCitation::new(
+            "http".to_string(),
+            "localhost".to_string(),
+            "asdf".to_string(),
+        );
+
+        let parts = thread_with_permashort_citation("Some text", 3, &permashort_citation, &[]);
+
+        assert_eq!(parts.len(), 8);
+        assert!(parts.iter().all(|part| !part.starts_with(' ')));
+    }
 }
\ No newline at end of file
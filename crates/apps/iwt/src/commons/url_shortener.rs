@@ -76,3 +76,24 @@ impl Client for ReqwestClient {
         }
     }
 }
+
+#[cfg(test)]
+pub mod stubs {
+    use async_trait::async_trait;
+
+    use super::{Client, ClientError};
+    use crate::commons::permashort_link::PermashortCitation;
+
+    pub struct StubUrlShortenerClient;
+
+    #[async_trait(?Send)]
+    impl Client for StubUrlShortenerClient {
+        async fn put_uri(&self, _uri: &str) -> Result<PermashortCitation, ClientError> {
+            Ok(PermashortCitation::new(
+                String::from("http"),
+                String::from("localhost"),
+                String::from("s/asdf"),
+            ))
+        }
+    }
+}
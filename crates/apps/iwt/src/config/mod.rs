@@ -1,16 +1,29 @@
 
+use std::collections::HashMap;
 use std::fs;
+use std::str::FromStr;
 
-use oauth2::{AccessToken, ClientId};
-use serde_derive::Deserialize;
+use oauth2::ClientId;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::social::{Account, Network};
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct Config {
     pub rss: Rss,
+    pub h_feed: Option<HFeed>,
     pub db: DB,
-    pub twitter: Twitter,
-    pub mastodon: Mastodon,
+    /// Syndication targets, only the configured ones are used
+    #[serde(default)]
+    pub targets: Vec<Target>,
     pub url_shortener: UrlShortener,
+    #[serde(default)]
+    pub daemon: Daemon,
+    #[serde(default)]
+    pub outbox: Outbox,
+    pub websub: Option<WebSub>,
+    pub webmention: Option<Webmention>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -18,20 +31,104 @@ pub struct Rss {
     pub urls: Vec<String>,
 }
 
+/// Pages whose `h-feed` is syndicated
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct HFeed {
+    pub urls: Vec<String>,
+    /// The `network` or `network:account` targets of the entries, every configured target by
+    /// default
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct DB {
     pub path: String,
 }
 
+/// A `[[targets]]` entry
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Target {
+    /// Name of the target, defaults to its kind
+    pub name: Option<String>,
+    /// Propagate the edits of the original posts, only Mastodon can edit the syndicated copies
+    #[serde(default)]
+    pub edits: bool,
+    #[serde(flatten)]
+    pub kind: TargetKind,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TargetKind {
+    Twitter(Twitter),
+    Mastodon(Mastodon),
+    Bluesky(Bluesky),
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct Twitter {
     pub client_id: ClientId,
+    /// Publish long posts as a thread instead of shortening them
+    #[serde(default)]
+    pub thread: bool,
 }
 
-#[derive(Debug, Deserialize)]
+/// The access token is obtained by the `app-auth mastodon` flow and is stored in the db
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct Mastodon {
     pub base_uri: String,
-    pub access_token: AccessToken,
+    /// Publish long posts as a thread instead of shortening them
+    #[serde(default)]
+    pub thread: bool,
+    /// Visibility of the statuses, defaults to the one of the account
+    pub visibility: Option<Visibility>,
+    /// ISO 639-1 language code of the statuses, detected by the instance by default
+    pub language: Option<String>,
+    /// Mark the attachments of the statuses as sensitive
+    #[serde(default)]
+    pub sensitive: bool,
+    /// The statuses are replies to this status
+    pub in_reply_to_id: Option<String>,
+}
+
+/// Visibility of a Mastodon status
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    Unlisted,
+    Private,
+    Direct,
+}
+
+impl FromStr for Visibility {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "public" => Ok(Self::Public),
+            "unlisted" => Ok(Self::Unlisted),
+            "private" => Ok(Self::Private),
+            "direct" => Ok(Self::Direct),
+            _ => Err(Error::config(&format!("Unknown visibility: {value}"))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Bluesky {
+    /// PDS of the account, defaults to https://bsky.social
+    #[serde(default = "default_bluesky_base_uri")]
+    pub base_uri: String,
+    /// Handle or DID of the account
+    pub identifier: String,
+    /// App password, see https://bsky.app/settings/app-passwords
+    pub app_password: String,
+}
+
+fn default_bluesky_base_uri() -> String {
+    String::from("https://bsky.social")
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -41,32 +138,193 @@ pub struct UrlShortener {
     pub put_base_uri: Option<String>,
 }
 
-impl PartialEq for Mastodon {
-    fn eq(&self, other: &Self) -> bool {
-        self.base_uri == other.base_uri && self.access_token.secret() == other.access_token.secret()
+/// Scheduling of the `daemon` command, durations are in seconds
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Daemon {
+    /// Polling interval of the feeds
+    pub interval: u64,
+    /// Polling intervals of specific feeds, by URL
+    pub intervals: HashMap<String, u64>,
+    /// Maximum random delay added to the intervals, so that feeds of the same site aren't
+    /// requested at once
+    pub jitter: u64,
+    /// The interval of failing feeds is doubled on every failure up to this limit
+    pub max_backoff: u64,
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self {
+            interval: 900,
+            intervals: HashMap::new(),
+            jitter: 60,
+            max_backoff: 6 * 60 * 60,
+        }
+    }
+}
+
+/// Retries of the failed publishes, durations are in seconds
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Outbox {
+    /// Delay of the first retry, it's doubled on every further failure
+    pub backoff: u64,
+    /// The delay between the retries is limited to this
+    pub max_backoff: u64,
+    /// Publishes failing this many times are dead-lettered, they aren't retried anymore
+    pub max_attempts: u32,
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        Self {
+            backoff: 5 * 60,
+            max_backoff: 24 * 60 * 60,
+            max_attempts: 8,
+        }
     }
 }
 
+/// WebSub subscriptions of the `daemon` command, the feeds naming a hub are pushed by the hub
+/// instead of being polled
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct WebSub {
+    /// Public URI of the callback endpoint, i.e. a reverse proxy in front of `listen`
+    pub callback_uri: String,
+    /// Address of the callback endpoint, defaults to 127.0.0.1:6010
+    #[serde(default = "default_websub_listen")]
+    pub listen: String,
+    /// Secret of the signatures of the pushed content
+    pub secret: String,
+    /// Requested lease of the subscriptions in seconds, they are renewed at its half
+    #[serde(default = "default_websub_lease_seconds")]
+    pub lease_seconds: u64,
+}
+
+fn default_websub_listen() -> String {
+    String::from("127.0.0.1:6010")
+}
+
+fn default_websub_lease_seconds() -> u64 {
+    10 * 24 * 60 * 60
+}
+
+/// Webmentions are sent to the links of the syndicated posts, only with this section
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Webmention {
+    /// Timeout of the endpoint discovery and of the sending in seconds, defaults to 10
+    #[serde(default = "default_webmention_timeout")]
+    pub timeout: u64,
+}
+
+fn default_webmention_timeout() -> u64 {
+    10
+}
+
 impl Config {
-    pub fn from_file(file_name: &str) -> Result<Config, toml::de::Error> {
-        let config_str = fs::read_to_string(file_name)
-            .unwrap_or_else(|_| panic!("Cannot found file: {file_name}"));
+    pub fn from_file(file_name: &str) -> Result<Config, Error> {
+        let config_str = fs::read_to_string(file_name).map_err(|err| Error::Config {
+            message: format!("Cannot read {file_name}"),
+            source: Some(Box::new(err)),
+        })?;
 
-        toml::from_str(&config_str)
+        let config: Config = toml::from_str(&config_str).map_err(|err| Error::Config {
+            message: format!("Cannot parse {file_name}"),
+            source: Some(Box::new(err)),
+        })?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let mut names = self.targets.iter().map(Target::name).collect::<Vec<_>>();
+        names.sort();
+        if let Some(duplicate) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(Error::config(&format!(
+                "Target names must be unique, \"{}\" is used more than once",
+                duplicate[0]
+            )));
+        }
+
+        for target in &self.targets {
+            if let TargetKind::Mastodon(Mastodon {
+                language: Some(language),
+                ..
+            }) = &target.kind
+            {
+                if !is_language_code(language) {
+                    return Err(Error::config(&format!(
+                        "The language of {} isn't an ISO 639-1 code: {language}",
+                        target.name()
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the target of the network, by its name if there are more of them
+    #[must_use]
+    pub fn find_target(&self, network: &Network, name: Option<&str>) -> Option<&Target> {
+        self.targets.iter().find(|target| {
+            target.kind.network() == *network && name.is_none_or(|name| target.name() == name)
+        })
+    }
+}
+
+/// Two lowercase letters, as the ISO 639-1 codes accepted by Mastodon
+#[must_use]
+pub fn is_language_code(language: &str) -> bool {
+    language.len() == 2 && language.bytes().all(|letter| letter.is_ascii_lowercase())
+}
+
+impl Target {
+    /// Name of the account, unique across the targets
+    #[must_use]
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.kind.network().to_string())
+    }
+
+    #[must_use]
+    pub fn account(&self) -> Account {
+        Account::new(self.kind.network(), &self.name())
+    }
+}
+
+impl TargetKind {
+    #[must_use]
+    pub fn network(&self) -> Network {
+        match self {
+            TargetKind::Twitter(_) => Network::Twitter,
+            TargetKind::Mastodon(_) => Network::Mastodon,
+            TargetKind::Bluesky(_) => Network::Bluesky,
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use oauth2::AccessToken;
     use oauth2::ClientId;
 
+    use super::Bluesky;
     use super::Config;
+    use super::Daemon;
+    use super::HFeed;
     use super::Mastodon;
+    use super::Outbox;
     use super::Rss;
+    use super::Target;
+    use super::TargetKind;
     use super::Twitter;
     use super::UrlShortener;
+    use super::Visibility;
     use super::DB;
+    use crate::social::{Account, Network};
 
     #[test]
     fn config_model_should_be_deserializable() {
@@ -76,13 +334,25 @@ mod test {
           "http://exmample.com/rss.xml",
           "http://exmample.com/some-site/rss.xml"
         ]
+        [h_feed]
+        urls = ["http://exmample.com/notes"]
         [db]
         path = "some/path"
-        [twitter]
+        [[targets]]
+        kind = "twitter"
         client_id = "some_client_id"
-        [mastodon]
-        base_uri = "https://mastodon.social"
-        access_token = "some-access-token"
+        [[targets]]
+        kind = "mastodon"
+        name = "fosstodon"
+        base_uri = "https://fosstodon.org"
+        thread = true
+        visibility = "unlisted"
+        language = "en"
+        edits = true
+        [[targets]]
+        kind = "bluesky"
+        identifier = "someone.bsky.social"
+        app_password = "some-app-password"
         [url_shortener]
         protocol = "http"
         domain = "localhost:9000"
@@ -97,22 +367,224 @@ mod test {
                         "http://exmample.com/some-site/rss.xml".to_string()
                     ]
                 },
+                h_feed: Some(HFeed {
+                    urls: vec!["http://exmample.com/notes".to_string()],
+                    targets: vec![],
+                }),
                 db: DB {
                     path: String::from("some/path")
                 },
-                twitter: Twitter {
-                    client_id: ClientId::new(String::from("some_client_id"))
-                },
-                mastodon: Mastodon {
-                    base_uri: String::from("https://mastodon.social"),
-                    access_token: AccessToken::new(String::from("some-access-token"))
-                },
+                targets: vec![
+                    Target {
+                        name: None,
+                        edits: false,
+                        kind: TargetKind::Twitter(Twitter {
+                            client_id: ClientId::new(String::from("some_client_id")),
+                            thread: false,
+                        }),
+                    },
+                    Target {
+                        name: Some(String::from("fosstodon")),
+                        edits: true,
+                        kind: TargetKind::Mastodon(Mastodon {
+                            base_uri: String::from("https://fosstodon.org"),
+                            thread: true,
+                            visibility: Some(Visibility::Unlisted),
+                            language: Some(String::from("en")),
+                            sensitive: false,
+                            in_reply_to_id: None,
+                        }),
+                    },
+                    Target {
+                        name: None,
+                        edits: false,
+                        kind: TargetKind::Bluesky(Bluesky {
+                            base_uri: String::from("https://bsky.social"),
+                            identifier: String::from("someone.bsky.social"),
+                            app_password: String::from("some-app-password"),
+                        }),
+                    },
+                ],
                 url_shortener: UrlShortener {
                     protocol: String::from("http"),
                     domain: String::from("localhost:9000"),
                     put_base_uri: None,
-                }
+                },
+                daemon: Daemon::default(),
+                outbox: Outbox::default(),
+                websub: None,
+                webmention: None,
             })
         );
     }
+
+    #[test]
+    fn config_model_should_be_deserializable_without_targets() {
+        let config = r#"
+        [rss]
+        urls = []
+        [db]
+        path = "some/path"
+        [url_shortener]
+        protocol = "http"
+        domain = "localhost:9000"
+        "#;
+
+        assert_eq!(toml::from_str::<Config>(config).unwrap().targets, vec![]);
+    }
+
+    #[test]
+    fn daemon_config_should_fall_back_to_the_defaults() {
+        let config = r#"
+        [rss]
+        urls = []
+        [db]
+        path = "some/path"
+        [url_shortener]
+        protocol = "http"
+        domain = "localhost:9000"
+        [daemon]
+        interval = 300
+        [daemon.intervals]
+        "http://example.com/rss.xml" = 60
+        "#;
+
+        let daemon = toml::from_str::<Config>(config).unwrap().daemon;
+
+        assert_eq!(daemon.interval, 300);
+        assert_eq!(
+            daemon.intervals.get("http://example.com/rss.xml"),
+            Some(&60)
+        );
+        assert_eq!(daemon.jitter, Daemon::default().jitter);
+    }
+
+    #[test]
+    fn target_name_should_default_to_its_kind() {
+        let target = Target {
+            name: None,
+            edits: false,
+            kind: TargetKind::Mastodon(Mastodon {
+                base_uri: String::from("https://mastodon.social"),
+                thread: false,
+                visibility: None,
+                language: None,
+                sensitive: false,
+                in_reply_to_id: None,
+            }),
+        };
+
+        assert_eq!(target.name(), "mastodon");
+    }
+
+    #[test]
+    fn find_target_should_find_the_target_by_name() {
+        let config = toml::from_str::<Config>(
+            r#"
+        [rss]
+        urls = []
+        [db]
+        path = "some/path"
+        [[targets]]
+        kind = "mastodon"
+        name = "personal"
+        base_uri = "https://mastodon.social"
+        [[targets]]
+        kind = "mastodon"
+        name = "project"
+        base_uri = "https://fosstodon.org"
+        [url_shortener]
+        protocol = "http"
+        domain = "localhost:9000"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config
+                .find_target(&Network::Mastodon, Some("project"))
+                .map(Target::account),
+            Some(Account::new(Network::Mastodon, "project"))
+        );
+        assert_eq!(
+            config
+                .find_target(&Network::Mastodon, None)
+                .map(Target::account),
+            Some(Account::new(Network::Mastodon, "personal"))
+        );
+        assert_eq!(config.find_target(&Network::Twitter, None), None);
+    }
+
+    #[test]
+    fn test_missing_config_file_is_a_config_error() {
+        let err = Config::from_file("does/not/exist.toml").unwrap_err();
+
+        assert_eq!(err.kind(), "config");
+        assert!(err
+            .to_string()
+            .starts_with("Config error: Cannot read does/not/exist.toml"));
+    }
+
+    #[test]
+    fn test_duplicate_target_names_are_a_config_error() {
+        let config = toml::from_str::<Config>(
+            r#"
+        [rss]
+        urls = []
+
+        [db]
+        path = "indieweb.db"
+
+        [url_shortener]
+        protocol = "http"
+        domain = "localhost:9000"
+
+        [[targets]]
+        kind = "mastodon"
+        base_uri = "https://mastodon.social"
+
+        [[targets]]
+        kind = "mastodon"
+        base_uri = "https://fosstodon.org"
+        "#,
+        )
+        .unwrap();
+
+        let err = config.validate().unwrap_err();
+
+        assert_eq!(err.kind(), "config");
+        assert!(err
+            .to_string()
+            .ends_with("Target names must be unique, \"mastodon\" is used more than once"));
+    }
+
+    #[test]
+    fn test_language_which_isnt_an_iso_639_1_code_is_a_config_error() {
+        let config = toml::from_str::<Config>(
+            r#"
+        [rss]
+        urls = []
+
+        [db]
+        path = "indieweb.db"
+
+        [url_shortener]
+        protocol = "http"
+        domain = "localhost:9000"
+
+        [[targets]]
+        kind = "mastodon"
+        base_uri = "https://mastodon.social"
+        language = "english"
+        "#,
+        )
+        .unwrap();
+
+        let err = config.validate().unwrap_err();
+
+        assert_eq!(err.kind(), "config");
+        assert!(err
+            .to_string()
+            .ends_with("The language of mastodon isn't an ISO 639-1 code: english"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+
+use atom_syndication::{extension, Entry, Feed, Link, Text};
+use rss::{
+    extension::atom::AtomExtension, extension::Extension, extension::ExtensionMap, Category,
+    Channel, Enclosure, Guid, Item,
+};
+
+/// Maps the Atom feed to the RSS item model. Foreign markup, i.e. the `iwt` extension, is kept as
+/// RSS extensions so that it can be read by `RssItemExt`.
+#[must_use]
+pub fn to_channel(feed: &Feed) -> Channel {
+    Channel {
+        title: feed.title().as_str().to_string(),
+        link: alternate_link(feed.links()).unwrap_or_default(),
+        description: feed
+            .subtitle()
+            .map(|subtitle| subtitle.as_str().to_string())
+            .unwrap_or_default(),
+        items: feed.entries().iter().map(to_item).collect(),
+        // The WebSub hub and the topic, as in RSS
+        atom_ext: Some(AtomExtension {
+            links: feed
+                .links()
+                .iter()
+                .filter(|link| link.rel() == "hub" || link.rel() == "self")
+                .cloned()
+                .collect(),
+        }),
+        ..Default::default()
+    }
+}
+
+fn to_item(entry: &Entry) -> Item {
+    Item {
+        title: Some(entry.title().as_str().to_string()),
+        link: Some(alternate_link(entry.links()).unwrap_or_else(|| entry.id().to_string())),
+        guid: Some(Guid {
+            value: entry.id().to_string(),
+            permalink: false,
+        }),
+        description: entry
+            .content()
+            .and_then(|content| content.value())
+            .or_else(|| entry.summary().map(Text::as_str))
+            .map(String::from),
+        pub_date: Some(entry.published().unwrap_or(entry.updated()).to_rfc2822()),
+        categories: entry
+            .categories()
+            .iter()
+            .map(|category| Category {
+                name: category.term().to_string(),
+                domain: category.scheme().map(String::from),
+            })
+            .collect(),
+        enclosure: entry
+            .links()
+            .iter()
+            .find(|link| link.rel() == "enclosure")
+            .map(|link| Enclosure {
+                url: link.href().to_string(),
+                length: link.length().unwrap_or_default().to_string(),
+                mime_type: link.mime_type().unwrap_or_default().to_string(),
+            }),
+        extensions: to_extension_map(entry.extensions()),
+        ..Default::default()
+    }
+}
+
+fn alternate_link(links: &[Link]) -> Option<String> {
+    links
+        .iter()
+        .find(|link| link.rel() == "alternate")
+        .map(|link| link.href().to_string())
+}
+
+fn to_extension_map(extensions: &extension::ExtensionMap) -> ExtensionMap {
+    extensions
+        .iter()
+        .map(|(prefix, elements)| (prefix.clone(), to_children(elements)))
+        .collect()
+}
+
+fn to_children(
+    children: &BTreeMap<String, Vec<extension::Extension>>,
+) -> BTreeMap<String, Vec<Extension>> {
+    children
+        .iter()
+        .map(|(name, extensions)| {
+            (
+                name.clone(),
+                extensions.iter().map(to_extension).collect::<Vec<_>>(),
+            )
+        })
+        .collect()
+}
+
+fn to_extension(extension: &extension::Extension) -> Extension {
+    Extension {
+        name: extension.name().to_string(),
+        value: extension.value().map(String::from),
+        attrs: extension.attrs().clone(),
+        children: to_children(extension.children()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use atom_syndication::Feed;
+
+    use super::to_channel;
+    use crate::cross_publisher::rss_item_ext::{IwtRssExtension, IwtRssTargetNetwork, RssItemExt};
+    use crate::social::Network;
+
+    #[test]
+    fn test_to_channel_maps_the_entries_with_the_iwt_extension() {
+        let feed = r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom" xmlns:iwt="https://iwt.example.com/ns">
+              <title>Some blog</title>
+              <id>http://example.com/</id>
+              <updated>2023-01-02T10:00:00Z</updated>
+              <link rel="alternate" href="http://example.com/"/>
+              <link rel="hub" href="https://hub.example.com/"/>
+              <entry>
+                <title>Post 1</title>
+                <id>tag:example.com,2023:post-1</id>
+                <updated>2023-01-02T10:00:00Z</updated>
+                <link rel="alternate" href="http://example.com/post-1"/>
+                <link rel="enclosure" type="image/png" length="42" href="http://example.com/cat.png"/>
+                <content type="html">&lt;p&gt;Some post&lt;/p&gt;</content>
+                <iwt:extension>
+                  <iwt:targetNetworks>
+                    <iwt:targetNetwork>mastodon</iwt:targetNetwork>
+                  </iwt:targetNetworks>
+                  <iwt:contentWarning>Cats</iwt:contentWarning>
+                  <iwt:tags>
+                    <iwt:tag>cat</iwt:tag>
+                  </iwt:tags>
+                </iwt:extension>
+              </entry>
+            </feed>"#;
+
+        let channel = to_channel(&Feed::read_from(feed.as_bytes()).unwrap());
+        let item = &channel.items()[0];
+
+        assert_eq!(channel.link(), "http://example.com/");
+        assert_eq!(
+            channel.atom_ext().unwrap().links()[0].href(),
+            "https://hub.example.com/"
+        );
+        assert_eq!(item.link(), Some("http://example.com/post-1"));
+        assert_eq!(item.guid().unwrap().value(), "tag:example.com,2023:post-1");
+        assert_eq!(item.description(), Some("<p>Some post</p>"));
+        assert_eq!(item.enclosure().unwrap().mime_type(), "image/png");
+        assert_eq!(
+            item.get_iwt_extension(),
+            Some(IwtRssExtension {
+                target_networks: vec![IwtRssTargetNetwork {
+                    network: Network::Mastodon,
+                    account: None,
+                }],
+                content_warning: Some(String::from("Cats")),
+                tags: vec![String::from("cat")],
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use reqwest::header::LINK;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+
+use super::{Author, Interaction, InteractionKind, Source};
+use crate::commons::auth::token_db::TokenDB;
+use crate::commons::link_header;
+use crate::cross_publisher::syndicated_post::SyndicatedPost;
+use crate::social::Account;
+
+/// The maximum of the accounts which favourited or reblogged a status
+const PAGE_SIZE: usize = 80;
+
+/// Replies, favourites and reblogs of the statuses
+pub struct Mastodon<DB: TokenDB> {
+    account: Account,
+    base_uri: String,
+    token_db: Rc<DB>,
+    http_client: Client,
+}
+
+impl<DB: TokenDB> Mastodon<DB> {
+    pub fn new(account: Account, base_uri: String, token_db: Rc<DB>) -> Self {
+        Self {
+            account,
+            base_uri,
+            token_db,
+            http_client: Client::new(),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Box<dyn std::error::Error>> {
+        let access_token = self.token_db.get_access_token(&self.account)?;
+
+        Ok(self
+            .http_client
+            .get(format!("{}{}", self.base_uri, path))
+            .bearer_auth(access_token.secret())
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<T>()
+            .await?)
+    }
+
+    /// Every page of the list, the next page is linked by the `Link` header
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+        let access_token = self.token_db.get_access_token(&self.account)?;
+
+        let mut items = vec![];
+        let mut next = Some(format!("{}{}?limit={}", self.base_uri, path, PAGE_SIZE));
+        while let Some(uri) = next {
+            let response = self
+                .http_client
+                .get(uri)
+                .bearer_auth(access_token.secret())
+                .send()
+                .await?
+                .error_for_status()?;
+            next = response
+                .headers()
+                .get_all(LINK)
+                .iter()
+                .filter_map(|link| link.to_str().ok())
+                .find_map(|link| link_header::find_rel(link, "next"));
+            items.extend(response.json::<Vec<T>>().await?);
+        }
+
+        Ok(items)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Status {
+    id: String,
+    url: Option<String>,
+    uri: String,
+    in_reply_to_id: Option<String>,
+    content: String,
+    created_at: String,
+    account: MastodonAccount,
+}
+
+#[derive(serde::Deserialize)]
+struct Context {
+    descendants: Vec<Status>,
+}
+
+#[derive(serde::Deserialize)]
+struct MastodonAccount {
+    id: String,
+    acct: String,
+    display_name: String,
+    url: String,
+    avatar: Option<String>,
+}
+
+impl From<MastodonAccount> for Author {
+    fn from(account: MastodonAccount) -> Self {
+        Self {
+            name: if account.display_name.is_empty() {
+                account.acct
+            } else {
+                account.display_name
+            },
+            url: account.url,
+            photo: account.avatar,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<DB: TokenDB> Source for Mastodon<DB> {
+    /// The direct replies to any part of the thread, the favourites and the reblogs of the status
+    async fn interactions(
+        &self,
+        post: &SyndicatedPost,
+    ) -> Result<Vec<Interaction>, Box<dyn std::error::Error>> {
+        let status = self
+            .get::<Status>(&format!("/api/v1/statuses/{}", post.id))
+            .await?;
+        let status_url = status.url.unwrap_or(status.uri);
+
+        let replies = self
+            .get::<Context>(&format!("/api/v1/statuses/{}/context", post.id))
+            .await?
+            .descendants
+            .into_iter()
+            .filter(|reply| !post.thread.contains(&reply.id))
+            .filter(|reply| {
+                reply
+                    .in_reply_to_id
+                    .as_ref()
+                    .is_some_and(|id| id == &post.id || post.thread.contains(id))
+            })
+            .map(|reply| Interaction {
+                kind: InteractionKind::Reply,
+                id: reply.id,
+                url: reply.url.unwrap_or(reply.uri),
+                author: reply.account.into(),
+                content: Some(reply.content),
+                published: Some(reply.created_at),
+            });
+
+        let likes = self
+            .get_all::<MastodonAccount>(&format!("/api/v1/statuses/{}/favourited_by", post.id))
+            .await?
+            .into_iter()
+            .map(|account| Interaction {
+                kind: InteractionKind::Like,
+                id: format!("like:{}:{}", post.id, account.id),
+                url: format!("{}#favorited-by-{}", status_url, account.id),
+                author: account.into(),
+                content: None,
+                published: None,
+            });
+
+        let reposts = self
+            .get_all::<MastodonAccount>(&format!("/api/v1/statuses/{}/reblogged_by", post.id))
+            .await?
+            .into_iter()
+            .map(|account| Interaction {
+                kind: InteractionKind::Repost,
+                id: format!("repost:{}:{}", post.id, account.id),
+                url: format!("{}#reblogged-by-{}", status_url, account.id),
+                author: account.into(),
+                content: None,
+                published: None,
+            });
+
+        Ok(replies.chain(likes).chain(reposts).collect())
+    }
+
+    fn account(&self) -> &Account {
+        &self.account
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::Mastodon;
+    use crate::cross_publisher::backfeed::{InteractionKind, Source};
+    use crate::cross_publisher::syndicated_post::SyndicatedPost;
+    use crate::social::{Account, Network};
+    use crate::stubs::auth::token_db::stubs::StubTokenDB;
+
+    fn account(id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "acct": format!("user{id}@mastodon.example"),
+            "display_name": "",
+            "url": format!("https://mastodon.example/@user{id}"),
+            "avatar": null,
+        })
+    }
+
+    fn status(id: &str, in_reply_to_id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "url": format!("https://mastodon.example/@me/{id}"),
+            "uri": format!("https://mastodon.example/users/me/statuses/{id}"),
+            "in_reply_to_id": in_reply_to_id,
+            "content": "<p>Nice post</p>",
+            "created_at": "2023-01-02T10:00:00.000Z",
+            "account": account("7"),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_interactions_are_the_replies_and_every_page_of_favourites_and_reblogs() {
+        let server = MockServer::start().await;
+        let responses = [
+            ("/api/v1/statuses/1", status("1", "0")),
+            (
+                "/api/v1/statuses/1/context",
+                // 2 is the second part of the thread, 4 is a reply to a reply
+                json!({ "ancestors": [], "descendants": [
+                    status("2", "1"), status("3", "2"), status("4", "3")
+                ] }),
+            ),
+            ("/api/v1/statuses/1/reblogged_by", json!([account("9")])),
+        ];
+        for (status_path, response) in responses {
+            Mock::given(method("GET"))
+                .and(path(status_path))
+                .respond_with(ResponseTemplate::new(200).set_body_json(response))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/api/v1/statuses/1/favourited_by"))
+            .and(query_param("limit", "80"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "Link",
+                        format!(
+                            "<{}/api/v1/statuses/1/favourited_by?max_id=8>; rel=\"next\"",
+                            server.uri()
+                        )
+                        .as_str(),
+                    )
+                    .set_body_json(json!([account("8")])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/statuses/1/favourited_by"))
+            .and(query_param("max_id", "8"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([account("10")])))
+            .mount(&server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            server.uri(),
+            Rc::new(StubTokenDB::new()),
+        );
+        let post = SyndicatedPost {
+            social_network: Network::Mastodon,
+            account: String::from("mastodon"),
+            id: String::from("1"),
+            original_guid: String::from("post-1"),
+            original_uri: String::from("http://example.com/post-1"),
+            thread: vec![String::from("2")],
+            content_hash: None,
+            retracted: false,
+        };
+
+        let interactions = mastodon.interactions(&post).await.unwrap();
+
+        assert_eq!(
+            interactions
+                .iter()
+                .map(|interaction| (interaction.kind.clone(), interaction.url.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (InteractionKind::Reply, "https://mastodon.example/@me/3"),
+                (
+                    InteractionKind::Like,
+                    "https://mastodon.example/@me/1#favorited-by-8"
+                ),
+                (
+                    InteractionKind::Like,
+                    "https://mastodon.example/@me/1#favorited-by-10"
+                ),
+                (
+                    InteractionKind::Repost,
+                    "https://mastodon.example/@me/1#reblogged-by-9"
+                ),
+            ]
+        );
+        assert_eq!(interactions[1].author.name, "user8@mastodon.example");
+    }
+}
@@ -0,0 +1,373 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::json;
+
+use super::syndicated_post::{SqliteSyndycatedPostStorage, SyndicatedPost};
+use crate::commons::auth::token_db::SqliteTokenDB;
+use crate::config::{Config, TargetKind};
+use crate::error::Error;
+use crate::social::Account;
+
+mod mastodon;
+mod twitter;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum InteractionKind {
+    Reply,
+    Like,
+    Repost,
+}
+
+impl InteractionKind {
+    /// The microformats2 property linking the interaction to the original post
+    fn property(&self) -> &'static str {
+        match self {
+            InteractionKind::Reply => "in-reply-to",
+            InteractionKind::Like => "like-of",
+            InteractionKind::Repost => "repost-of",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Author {
+    pub name: String,
+    pub url: String,
+    pub photo: Option<String>,
+}
+
+/// A reply, like or repost of a syndicated copy
+#[derive(Debug, PartialEq, Clone)]
+pub struct Interaction {
+    pub kind: InteractionKind,
+    /// Unique per account, likes and reposts are identified by the post and their author
+    pub id: String,
+    /// Likes and reposts don't have their own page on the networks, so they have the URL of the
+    /// syndicated copy with a fragment, like Bridgy
+    pub url: String,
+    pub author: Author,
+    /// HTML content of replies
+    pub content: Option<String>,
+    /// RFC 3339 date of replies
+    pub published: Option<String>,
+}
+
+/// Fetches the interactions of the syndicated copies published with an account
+#[async_trait(?Send)]
+pub trait Source {
+    async fn interactions(
+        &self,
+        post: &SyndicatedPost,
+    ) -> Result<Vec<Interaction>, Box<dyn std::error::Error>>;
+
+    fn account(&self) -> &Account;
+}
+
+/// Writes the interaction into the directory as a JF2 file, i.e. for a static site generator
+fn write_jf2(
+    dir: &Path,
+    account: &Account,
+    interaction: &Interaction,
+    original_uri: &str,
+) -> Result<(), Error> {
+    let file_name = format!("{}-{}.json", account.name, interaction.id)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let path = dir.join(file_name);
+    let cannot_write = format!("Cannot write {}", path.display());
+    let jf2 = serde_json::to_string_pretty(&to_jf2(interaction, original_uri))
+        .map_err(|err| Error::io(&cannot_write, err))?;
+    std::fs::write(&path, jf2).map_err(|err| Error::io(&cannot_write, err))
+}
+
+fn to_jf2(interaction: &Interaction, original_uri: &str) -> serde_json::Value {
+    let mut jf2 = json!({
+        "type": "entry",
+        "author": {
+            "type": "card",
+            "name": interaction.author.name,
+            "url": interaction.author.url,
+            "photo": interaction.author.photo,
+        },
+        "url": interaction.url,
+        "published": interaction.published,
+        "wm-property": interaction.kind.property(),
+    });
+    jf2[interaction.kind.property()] = json!(original_uri);
+    if let Some(content) = &interaction.content {
+        jf2["content"] = json!({ "html": content });
+    }
+    jf2
+}
+
+/// The interactions which have been forwarded, so that nothing is delivered twice
+pub struct SqliteBackfedInteractionStorage {
+    conn: Rc<Connection>,
+}
+
+impl SqliteBackfedInteractionStorage {
+    pub fn new(conn: Rc<Connection>) -> Self {
+        Self { conn }
+    }
+
+    pub fn init_table(&self) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS backfed_interaction (
+                    account        TEXT NOT NULL,
+                    interaction_id TEXT NOT NULL,
+                    original_uri   TEXT NOT NULL,
+                    backfed_at     TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (account, interaction_id)
+                )",
+                (),
+            )
+            .map(|_| ())
+    }
+
+    pub fn contains(&self, account: &Account, interaction_id: &str) -> rusqlite::Result<bool> {
+        self.conn
+            .query_row(
+                "SELECT 1 FROM backfed_interaction WHERE account = ?1 AND interaction_id = ?2",
+                (&account.name, interaction_id),
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|found| found.is_some())
+    }
+
+    pub fn store(
+        &self,
+        account: &Account,
+        interaction_id: &str,
+        original_uri: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO backfed_interaction (account, interaction_id, original_uri)
+                 VALUES (?1, ?2, ?3)",
+                (&account.name, interaction_id, original_uri),
+            )
+            .map(|_| ())
+    }
+}
+
+/// Forwards the interactions of every syndicated post to the original post
+pub async fn execute(config: &Config, output: &str) -> Result<(), Error> {
+    let conn = Rc::new(Connection::open(&config.db.path)?);
+
+    let token_db = Rc::new(SqliteTokenDB::new(Rc::clone(&conn)));
+    token_db.init_table()?;
+
+    let posts = SqliteSyndycatedPostStorage::new(Rc::clone(&conn));
+    posts.init_table()?;
+
+    let storage = SqliteBackfedInteractionStorage::new(conn);
+    storage.init_table()?;
+
+    let sources = config
+        .targets
+        .iter()
+        .filter_map(|target| -> Option<Result<Box<dyn Source>, Error>> {
+            match &target.kind {
+                TargetKind::Twitter(twitter) => Some(
+                    twitter::Twitter::new(
+                        target.account(),
+                        twitter.client_id.clone(),
+                        Rc::clone(&token_db),
+                    )
+                    .map(|twitter| Box::new(twitter) as Box<dyn Source>),
+                ),
+                TargetKind::Mastodon(mastodon) => Some(Ok(Box::new(mastodon::Mastodon::new(
+                    target.account(),
+                    mastodon.base_uri.clone(),
+                    Rc::clone(&token_db),
+                )))),
+                TargetKind::Bluesky(_) => {
+                    log::warn!("{} |> Backfeed isn't supported, skipping", target.account());
+                    None
+                }
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    std::fs::create_dir_all(output)
+        .map_err(|err| Error::io(&format!("Cannot create {output}"), err))?;
+
+    // The retracted copies have been deleted from the networks
+    let posts = posts
+        .all()?
+        .into_iter()
+        .filter(|post| !post.retracted)
+        .collect::<Vec<_>>();
+
+    backfeed(&posts, &sources, &storage, Path::new(output)).await
+}
+
+/// Delivers the interactions which haven't been forwarded yet, a failing post doesn't stop the
+/// others
+async fn backfeed(
+    posts: &[SyndicatedPost],
+    sources: &[Box<dyn Source>],
+    storage: &SqliteBackfedInteractionStorage,
+    dir: &Path,
+) -> Result<(), Error> {
+    let mut failures = 0;
+
+    for source in sources {
+        let account = source.account();
+        for post in posts.iter().filter(|post| post.account == account.name) {
+            let interactions = match source.interactions(post).await {
+                Ok(interactions) => interactions,
+                Err(err) => {
+                    failures += 1;
+                    log::error!(
+                        "{} |> Couldn't fetch the interactions on {}: {}",
+                        post.original_uri,
+                        account,
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            for interaction in interactions {
+                if storage.contains(account, &interaction.id)? {
+                    continue;
+                }
+
+                match write_jf2(dir, account, &interaction, &post.original_uri) {
+                    Ok(()) => {
+                        log::info!(
+                            "{} |> {:?} from {} forwarded",
+                            post.original_uri,
+                            interaction.kind,
+                            interaction.url
+                        );
+                        storage.store(account, &interaction.id, &post.original_uri)?;
+                    }
+                    Err(err) => {
+                        failures += 1;
+                        log::error!(
+                            "{} |> Couldn't forward {}: {}",
+                            post.original_uri,
+                            interaction.url,
+                            err
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(Error::Backfeed { failures });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use async_trait::async_trait;
+    use rusqlite::Connection;
+
+    use super::{
+        backfeed, Author, Interaction, InteractionKind, Source, SqliteBackfedInteractionStorage,
+    };
+    use crate::cross_publisher::syndicated_post::SyndicatedPost;
+    use crate::social::{Account, Network};
+
+    struct StubSource {
+        account: Account,
+    }
+
+    #[async_trait(?Send)]
+    impl Source for StubSource {
+        async fn interactions(
+            &self,
+            post: &SyndicatedPost,
+        ) -> Result<Vec<Interaction>, Box<dyn std::error::Error>> {
+            Ok(vec![Interaction {
+                kind: InteractionKind::Like,
+                id: format!("like:{}:42", post.id),
+                url: format!("https://mastodon.example/@me/{}#favorited-by-42", post.id),
+                author: Author {
+                    name: String::from("Someone"),
+                    url: String::from("https://mastodon.example/@someone"),
+                    photo: None,
+                },
+                content: None,
+                published: None,
+            }])
+        }
+
+        fn account(&self) -> &Account {
+            &self.account
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interactions_are_forwarded_once() {
+        let dir = std::env::temp_dir().join(format!("iwt-backfeed-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let storage =
+            SqliteBackfedInteractionStorage::new(Rc::new(Connection::open_in_memory().unwrap()));
+        storage.init_table().unwrap();
+
+        let account = Account::from(Network::Mastodon);
+        let posts = vec![
+            SyndicatedPost {
+                social_network: Network::Mastodon,
+                account: account.name.clone(),
+                id: String::from("1"),
+                original_guid: String::from("post-1"),
+                original_uri: String::from("http://example.com/post-1"),
+                thread: vec![],
+                content_hash: None,
+                retracted: false,
+            },
+            SyndicatedPost {
+                social_network: Network::Twitter,
+                account: String::from("twitter"),
+                id: String::from("2"),
+                original_guid: String::from("post-1"),
+                original_uri: String::from("http://example.com/post-1"),
+                thread: vec![],
+                content_hash: None,
+                retracted: false,
+            },
+        ];
+        let sources: Vec<Box<dyn Source>> = vec![Box::new(StubSource {
+            account: account.clone(),
+        })];
+
+        backfeed(&posts, &sources, &storage, &dir).await.unwrap();
+        let file = dir.join("mastodon-like_1_42.json");
+        let jf2 = std::fs::read_to_string(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        backfeed(&posts, &sources, &storage, &dir).await.unwrap();
+
+        assert!(storage.contains(&account, "like:1:42").unwrap());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&jf2).unwrap()["like-of"],
+            "http://example.com/post-1"
+        );
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
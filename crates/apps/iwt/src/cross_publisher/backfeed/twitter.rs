@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use oauth2::ClientId;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+
+use super::{Author, Interaction, InteractionKind, Source};
+use crate::commons::auth::oauth::AuthedClient;
+use crate::commons::auth::token_db::TokenDB;
+use crate::cross_publisher::syndicated_post::SyndicatedPost;
+use crate::cross_publisher::twitter::oauth_client;
+use crate::error::Error;
+use crate::social::Account;
+use crate::IwtError;
+
+const API_URI: &str = "https://api.twitter.com";
+const USER_FIELDS: &str = "user.fields=name,username,profile_image_url";
+/// The maximum of the search and of the liking users
+const PAGE_SIZE: usize = 100;
+
+/// Replies and likes of the tweets. The replies are searched in the conversation of the tweet,
+/// the search API only returns the tweets of the last 7 days.
+pub struct Twitter<DB: TokenDB> {
+    account: Account,
+    authed_client: AuthedClient<DB>,
+    http_client: Client,
+    base_uri: String,
+}
+
+impl<DB: TokenDB> Twitter<DB> {
+    pub fn new(account: Account, client_id: ClientId, db: Rc<DB>) -> Result<Self, Error> {
+        let authed_client = AuthedClient::new(account.clone(), oauth_client(client_id), db)
+            .map_err(|source| Error::Auth {
+                account: account.clone(),
+                source,
+            })?;
+
+        Ok(Self {
+            account,
+            authed_client,
+            http_client: Client::new(),
+            base_uri: String::from(API_URI),
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, uri: &str) -> Result<T, Box<dyn std::error::Error>> {
+        let response = self
+            .authed_client
+            .authed_request(self.http_client.get(uri).build()?)
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(Box::new(IwtError::new(&format!(
+                "Twitter request failed, status: {status}, body: {body}"
+            ))));
+        }
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Every page of the response, the next page is requested with the `next_token` of the
+    /// previous one in the `token_param`
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        uri: &str,
+        token_param: &str,
+    ) -> Result<Vec<Page<T>>, Box<dyn std::error::Error>> {
+        let mut pages = vec![];
+        let mut next_token: Option<String> = None;
+        loop {
+            let page_uri = match &next_token {
+                Some(token) => format!("{uri}&max_results={PAGE_SIZE}&{token_param}={token}"),
+                None => format!("{uri}&max_results={PAGE_SIZE}"),
+            };
+            let page = self.get::<Page<T>>(&page_uri).await?;
+            next_token = page.meta.next_token.clone();
+            pages.push(page);
+            if next_token.is_none() {
+                return Ok(pages);
+            }
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Page<T> {
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+    #[serde(default)]
+    includes: Includes,
+    #[serde(default)]
+    meta: Meta,
+}
+
+#[derive(serde::Deserialize, Default)]
+struct Meta {
+    next_token: Option<String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+struct Includes {
+    #[serde(default)]
+    users: Vec<User>,
+}
+
+#[derive(serde::Deserialize)]
+struct Tweet {
+    id: String,
+    text: String,
+    author_id: String,
+    created_at: Option<String>,
+    #[serde(default)]
+    referenced_tweets: Vec<ReferencedTweet>,
+}
+
+#[derive(serde::Deserialize)]
+struct ReferencedTweet {
+    #[serde(rename = "type")]
+    reference_type: String,
+    id: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+struct User {
+    id: String,
+    name: String,
+    username: String,
+    profile_image_url: Option<String>,
+}
+
+impl From<User> for Author {
+    fn from(user: User) -> Self {
+        Self {
+            name: user.name,
+            url: format!("https://twitter.com/{}", user.username),
+            photo: user.profile_image_url,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<DB: TokenDB> Source for Twitter<DB> {
+    /// The direct replies to any part of the thread and the likes of the tweet
+    async fn interactions(
+        &self,
+        post: &SyndicatedPost,
+    ) -> Result<Vec<Interaction>, Box<dyn std::error::Error>> {
+        let search = self
+            .get_all::<Tweet>(
+                &format!(
+                    "{}/2/tweets/search/recent?query=conversation_id:{}\
+                     &tweet.fields=author_id,created_at,referenced_tweets&expansions=author_id&{}",
+                    self.base_uri, post.id, USER_FIELDS
+                ),
+                "next_token",
+            )
+            .await?;
+        let users = search
+            .iter()
+            .flat_map(|page| page.includes.users.iter())
+            .map(|user| (user.id.clone(), user.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let replies = search
+            .into_iter()
+            .flat_map(|page| page.data)
+            .filter(|reply| !post.thread.contains(&reply.id))
+            .filter(|reply| {
+                reply.referenced_tweets.iter().any(|referenced| {
+                    referenced.reference_type == "replied_to"
+                        && (referenced.id == post.id || post.thread.contains(&referenced.id))
+                })
+            })
+            .filter_map(|reply| {
+                let author = users.get(&reply.author_id)?.clone();
+                Some(Interaction {
+                    kind: InteractionKind::Reply,
+                    url: format!(
+                        "https://twitter.com/{}/status/{}",
+                        author.username, reply.id
+                    ),
+                    id: reply.id,
+                    author: author.into(),
+                    content: Some(reply.text),
+                    published: reply.created_at,
+                })
+            });
+
+        let likes = self
+            .get_all::<User>(
+                &format!(
+                    "{}/2/tweets/{}/liking_users?{}",
+                    self.base_uri, post.id, USER_FIELDS
+                ),
+                "pagination_token",
+            )
+            .await?
+            .into_iter()
+            .flat_map(|page| page.data)
+            .map(|user| Interaction {
+                kind: InteractionKind::Like,
+                id: format!("like:{}:{}", post.id, user.id),
+                url: format!(
+                    "https://twitter.com/i/web/status/{}#liked-by-{}",
+                    post.id, user.id
+                ),
+                author: user.into(),
+                content: None,
+                published: None,
+            });
+
+        Ok(replies.chain(likes).collect())
+    }
+
+    fn account(&self) -> &Account {
+        &self.account
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use oauth2::ClientId;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::Twitter;
+    use crate::cross_publisher::backfeed::{InteractionKind, Source};
+    use crate::cross_publisher::syndicated_post::SyndicatedPost;
+    use crate::social::{Account, Network};
+    use crate::stubs::auth::token_db::stubs::StubTokenDB;
+
+    fn user(id: &str) -> serde_json::Value {
+        json!({ "id": id, "name": format!("User {id}"), "username": format!("user{id}") })
+    }
+
+    fn tweet(id: &str, replied_to: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "text": "Nice post",
+            "author_id": "7",
+            "created_at": "2023-01-02T10:00:00.000Z",
+            "referenced_tweets": [{ "type": "replied_to", "id": replied_to }],
+        })
+    }
+
+    async fn mount_pages(
+        server: &MockServer,
+        endpoint: &str,
+        token_param: &str,
+        pages: [serde_json::Value; 2],
+    ) {
+        let [first, second] = pages;
+        Mock::given(method("GET"))
+            .and(path(endpoint))
+            .and(query_param("max_results", "100"))
+            .and(query_param_is_missing(token_param))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first))
+            .expect(1)
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(endpoint))
+            .and(query_param(token_param, "next"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(second))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_interactions_are_the_replies_and_likes_of_every_page() {
+        let server = MockServer::start().await;
+        mount_pages(
+            &server,
+            "/2/tweets/search/recent",
+            "next_token",
+            [
+                // 2 is the second part of the thread
+                json!({
+                    "data": [tweet("2", "1"), tweet("3", "2")],
+                    "includes": { "users": [user("7")] },
+                    "meta": { "next_token": "next" },
+                }),
+                // 5 is a reply to a reply
+                json!({
+                    "data": [tweet("4", "1"), tweet("5", "4")],
+                    "includes": { "users": [user("7")] },
+                    "meta": {},
+                }),
+            ],
+        )
+        .await;
+        mount_pages(
+            &server,
+            "/2/tweets/1/liking_users",
+            "pagination_token",
+            [
+                json!({ "data": [user("8")], "meta": { "next_token": "next" } }),
+                json!({ "data": [user("9")], "meta": {} }),
+            ],
+        )
+        .await;
+
+        let mut twitter = Twitter::new(
+            Account::from(Network::Twitter),
+            ClientId::new(String::from("client_id")),
+            Rc::new(StubTokenDB::new()),
+        )
+        .unwrap();
+        twitter.base_uri = server.uri();
+        let post = SyndicatedPost {
+            social_network: Network::Twitter,
+            account: String::from("twitter"),
+            id: String::from("1"),
+            original_guid: String::from("post-1"),
+            original_uri: String::from("http://example.com/post-1"),
+            thread: vec![String::from("2")],
+            content_hash: None,
+            retracted: false,
+        };
+
+        let interactions = twitter.interactions(&post).await.unwrap();
+
+        assert_eq!(
+            interactions
+                .iter()
+                .map(|interaction| (interaction.kind.clone(), interaction.url.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (InteractionKind::Reply, "https://twitter.com/user7/status/3"),
+                (InteractionKind::Reply, "https://twitter.com/user7/status/4"),
+                (
+                    InteractionKind::Like,
+                    "https://twitter.com/i/web/status/1#liked-by-8"
+                ),
+                (
+                    InteractionKind::Like,
+                    "https://twitter.com/i/web/status/1#liked-by-9"
+                ),
+            ]
+        );
+        assert_eq!(interactions[0].author.name, "User 7");
+    }
+
+    #[tokio::test]
+    async fn test_failing_request_fails_the_interactions() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/2/tweets/search/recent"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+
+        let mut twitter = Twitter::new(
+            Account::from(Network::Twitter),
+            ClientId::new(String::from("client_id")),
+            Rc::new(StubTokenDB::new()),
+        )
+        .unwrap();
+        twitter.base_uri = server.uri();
+        let post = SyndicatedPost {
+            social_network: Network::Twitter,
+            account: String::from("twitter"),
+            id: String::from("1"),
+            original_guid: String::from("post-1"),
+            original_uri: String::from("http://example.com/post-1"),
+            thread: vec![],
+            content_hash: None,
+            retracted: false,
+        };
+
+        assert!(twitter.interactions(&post).await.is_err());
+    }
+}
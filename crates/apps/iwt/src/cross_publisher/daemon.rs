@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use rand::Rng;
+use rss::Channel;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+
+use super::websub::{listener, Hub, HubReader, Subscriber};
+use super::{h_feed, syndicate, Pipeline};
+use crate::config::Config;
+use crate::error::Error;
+
+enum FeedKind {
+    Rss,
+    HFeed,
+}
+
+/// A feed polled by the daemon
+struct ScheduledFeed {
+    url: String,
+    kind: FeedKind,
+    interval: Duration,
+    /// The WebSub hub named by the feed, `None` until the feed has been loaded
+    hub: Option<Option<Hub>>,
+    /// Failed runs in a row
+    failures: u32,
+    next_run: Instant,
+}
+
+/// Polls every feed on its own interval until SIGTERM (or Ctrl-C) is received. A syndication in
+/// progress is finished before exiting, so that the published posts are stored.
+///
+/// With WebSub configured, the daemon subscribes to the hubs named by the feeds and syndicates
+/// the pushed items as they arrive, the subscribed feeds are only polled to renew the lease.
+pub async fn execute(config: &Config) -> Result<(), Error> {
+    let pipeline = Pipeline::new(config, false)?;
+
+    let mut feeds = scheduled_feeds(config, Instant::now());
+    if feeds.is_empty() {
+        return Err(Error::config("No feeds are configured"));
+    }
+
+    let max_backoff = Duration::from_secs(config.daemon.max_backoff);
+    let h_feed_client = h_feed::ReqwestClient::new(config);
+    let mut sigterm = signal(SignalKind::terminate())
+        .map_err(|err| Error::io("Cannot listen for SIGTERM", err))?;
+    let mut sigint = signal(SignalKind::interrupt())
+        .map_err(|err| Error::io("Cannot listen for SIGINT", err))?;
+
+    // The sender is kept alive, so that receiving doesn't end without WebSub
+    let (pushed, mut pushed_channels) = mpsc::channel::<(String, Channel)>(10);
+    let subscriber = match &config.websub {
+        Some(websub) => {
+            let subscriber = Subscriber::new(websub);
+            let listen = websub.listen.parse().map_err(|err| Error::Config {
+                message: format!("Invalid WebSub listen address {}", websub.listen),
+                source: Some(Box::new(err)),
+            })?;
+            listener::start(
+                &listen,
+                listener::State::new(
+                    websub.secret.clone(),
+                    subscriber.subscriptions(),
+                    pushed.clone(),
+                ),
+            )
+            .map_err(|err| Error::io(&format!("Cannot listen on {}", websub.listen), err))?;
+            Some(subscriber)
+        }
+        None => None,
+    };
+
+    loop {
+        let feed = feeds.iter_mut().min_by_key(|feed| feed.next_run).unwrap();
+
+        tokio::select! {
+            biased;
+            _ = sigterm.recv() => {
+                log::info!("SIGTERM received, exiting");
+                return Ok(());
+            }
+            _ = sigint.recv() => {
+                log::info!("Interrupted, exiting");
+                return Ok(());
+            }
+            Some((topic, channel)) = pushed_channels.recv() => {
+                // The failures are logged by the syndication
+                syndicate::syndycate_channel(
+                    &topic,
+                    channel,
+                    &pipeline.targets,
+                    &pipeline.storage,
+                    Some(&pipeline.outbox),
+                    pipeline.webmention.as_ref(),
+                    false,
+                )
+                .await;
+                continue;
+            }
+            () = sleep_until(feed.next_run) => {}
+        }
+
+        let urls = [feed.url.clone()];
+        let report = match feed.kind {
+            FeedKind::Rss => {
+                // The hub is read from the loaded feed, an unchanged feed keeps the known hub
+                let client = HubReader::new(&pipeline.rss_client);
+                let report = syndicate::syndicate_feeds(
+                    &urls,
+                    &client,
+                    &pipeline.targets,
+                    &pipeline.storage,
+                    Some(&pipeline.outbox),
+                    pipeline.webmention.as_ref(),
+                    false,
+                )
+                .await;
+                if let Some(hub) = client.hub(&feed.url) {
+                    feed.hub = Some(hub);
+                }
+                report
+            }
+            FeedKind::HFeed => {
+                syndicate::syndicate_feeds(
+                    &urls,
+                    &h_feed_client,
+                    &pipeline.targets,
+                    &pipeline.storage,
+                    Some(&pipeline.outbox),
+                    pipeline.webmention.as_ref(),
+                    false,
+                )
+                .await
+            }
+        };
+
+        if report.is_success() {
+            feed.failures = 0;
+        } else {
+            feed.failures += 1;
+            log::error!(
+                "{} |> Syndication failed ({} in a row), {} failures",
+                feed.url,
+                feed.failures,
+                report.failures().count()
+            );
+        }
+
+        // The retries don't wait for the feeds of the posts, they may not contain them anymore
+        syndicate::retry_failed(
+            &pipeline.targets,
+            &pipeline.storage,
+            &pipeline.outbox,
+            false,
+        )
+        .await;
+
+        if let (Some(subscriber), FeedKind::Rss) = (&subscriber, &feed.kind) {
+            // Unchanged since the last run of a previous daemon
+            if feed.hub.is_none() {
+                match subscriber.discover(&feed.url).await {
+                    Ok(hub) => feed.hub = Some(hub),
+                    Err(err) => log::error!("{} |> WebSub discovery failed: {}", feed.url, err),
+                }
+            }
+            if let Some(Some(hub)) = &feed.hub {
+                if let Err(err) = subscriber.subscribe(hub).await {
+                    log::error!("{} |> WebSub subscription failed: {}", feed.url, err);
+                }
+            }
+        }
+
+        // The verified subscriptions are renewed before their lease expires, the others are polled
+        let interval = match (&subscriber, &feed.hub) {
+            (Some(subscriber), Some(Some(hub))) => subscriber
+                .lease(&hub.topic)
+                .map_or(feed.interval, |lease| lease / 2),
+            _ => feed.interval,
+        };
+        let delay = next_delay(interval, feed.failures, max_backoff) + jitter(config.daemon.jitter);
+        feed.next_run = Instant::now() + delay;
+
+        log::info!("{} |> Next run in {}s", feed.url, delay.as_secs());
+    }
+}
+
+/// The RSS and h-feed feeds of the config, all of them are due at `now`
+fn scheduled_feeds(config: &Config, now: Instant) -> Vec<ScheduledFeed> {
+    let rss = config.rss.urls.iter().map(|url| (url, FeedKind::Rss));
+    let h_feed = config
+        .h_feed
+        .iter()
+        .flat_map(|h_feed| h_feed.urls.iter())
+        .map(|url| (url, FeedKind::HFeed));
+
+    rss.chain(h_feed)
+        .map(|(url, kind)| ScheduledFeed {
+            url: url.clone(),
+            kind,
+            interval: Duration::from_secs(
+                *config
+                    .daemon
+                    .intervals
+                    .get(url)
+                    .unwrap_or(&config.daemon.interval),
+            ),
+            hub: None,
+            failures: 0,
+            next_run: now,
+        })
+        .collect()
+}
+
+/// The interval is doubled on every failure in a row, up to the maximum backoff
+fn next_delay(interval: Duration, failures: u32, max_backoff: Duration) -> Duration {
+    if failures == 0 {
+        return interval;
+    }
+
+    interval
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(max_backoff.max(interval))
+}
+
+fn jitter(max: u64) -> Duration {
+    Duration::from_secs(rand::thread_rng().gen_range(0..=max))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{next_delay, scheduled_feeds};
+    use crate::config::{Config, Daemon, HFeed, Outbox, Rss, UrlShortener, DB};
+
+    #[test]
+    fn test_next_delay_backs_off_failing_feeds() {
+        let interval = Duration::from_secs(60);
+        let max_backoff = Duration::from_secs(600);
+
+        assert_eq!(next_delay(interval, 0, max_backoff), interval);
+        assert_eq!(
+            next_delay(interval, 1, max_backoff),
+            Duration::from_secs(120)
+        );
+        assert_eq!(
+            next_delay(interval, 3, max_backoff),
+            Duration::from_secs(480)
+        );
+        assert_eq!(next_delay(interval, 4, max_backoff), max_backoff);
+        assert_eq!(next_delay(interval, 100, max_backoff), max_backoff);
+    }
+
+    #[test]
+    fn test_scheduled_feeds_use_their_own_intervals() {
+        let config = Config {
+            rss: Rss {
+                urls: vec![
+                    String::from("http://example.com/rss.xml"),
+                    String::from("http://example.com/other.xml"),
+                ],
+            },
+            h_feed: Some(HFeed {
+                urls: vec![String::from("http://example.com/notes")],
+                targets: vec![],
+            }),
+            db: DB {
+                path: String::from("some/path"),
+            },
+            targets: vec![],
+            url_shortener: UrlShortener {
+                protocol: String::from("http"),
+                domain: String::from("localhost:9000"),
+                put_base_uri: None,
+            },
+            daemon: Daemon {
+                interval: 900,
+                intervals: HashMap::from([(String::from("http://example.com/notes"), 60)]),
+                ..Daemon::default()
+            },
+            outbox: Outbox::default(),
+            websub: None,
+            webmention: None,
+        };
+
+        let feeds = scheduled_feeds(&config, Instant::now());
+
+        assert_eq!(
+            feeds
+                .iter()
+                .map(|feed| (feed.url.as_str(), feed.interval.as_secs()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("http://example.com/rss.xml", 900),
+                ("http://example.com/other.xml", 900),
+                ("http://example.com/notes", 60),
+            ]
+        );
+    }
+}
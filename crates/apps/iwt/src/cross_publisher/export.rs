@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use clap::ValueEnum;
+use rusqlite::Connection;
+use serde_derive::Serialize;
+
+use super::syndicated_post::{SqliteSyndycatedPostStorage, SyndicatedPost};
+use crate::commons::auth::token_db::{SqliteTokenDB, TokenDB};
+use crate::config::{Config, TargetKind};
+use crate::error::Error;
+use crate::social::{Account, Network};
+
+/// The field of the original post the links are keyed by
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Key {
+    Guid,
+    Uri,
+}
+
+/// A syndicated copy of a post, i.e. for the `u-syndication` links of the site
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SyndicationLink {
+    pub network: String,
+    pub account: String,
+    pub url: String,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct CredentialAccount {
+    url: String,
+}
+
+/// Writes the permalinks of the syndicated copies as JSON, into the file or to the standard output
+pub async fn execute(config: &Config, key: Key, output: Option<&str>) -> Result<(), Error> {
+    let conn = Rc::new(Connection::open(&config.db.path)?);
+
+    let token_db = SqliteTokenDB::new(Rc::clone(&conn));
+    token_db.init_table()?;
+
+    let storage = SqliteSyndycatedPostStorage::new(conn);
+    storage.init_table()?;
+
+    // Mastodon permalinks contain the username, it's looked up with the access token. The copies
+    // of an account which can't be looked up are skipped, the others are exported all the same.
+    let mut mastodon_profiles = HashMap::new();
+    for target in &config.targets {
+        if let TargetKind::Mastodon(mastodon) = &target.kind {
+            match mastodon_profile(&token_db, &target.account(), &mastodon.base_uri).await {
+                Ok(profile) => {
+                    mastodon_profiles.insert(target.name(), profile);
+                }
+                Err(err) => log::warn!(
+                    "{} |> Couldn't look up the profile, skipping its posts: {}",
+                    target.account(),
+                    err
+                ),
+            }
+        }
+    }
+
+    let json =
+        serde_json::to_string_pretty(&syndication_links(&storage.all()?, key, &mastodon_profiles))
+            .map_err(|err| Error::io("Cannot serialize the syndication links", err))?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, json)
+                .map_err(|err| Error::io(&format!("Cannot write {path}"), err))?;
+        }
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
+
+/// The URL of the profile of the Mastodon account
+async fn mastodon_profile(
+    token_db: &SqliteTokenDB,
+    account: &Account,
+    base_uri: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let access_token = token_db.get_access_token(account)?;
+    let profile = reqwest::Client::new()
+        .get(format!("{base_uri}/api/v1/accounts/verify_credentials"))
+        .bearer_auth(access_token.secret())
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<CredentialAccount>()
+        .await?;
+
+    Ok(profile.url)
+}
+
+/// The permalinks of the syndicated copies per original post, retracted copies and copies whose
+/// permalink can't be built (i.e. of a Mastodon account which isn't configured anymore) are
+/// skipped
+pub fn syndication_links(
+    posts: &[SyndicatedPost],
+    key: Key,
+    mastodon_profiles: &HashMap<String, String>,
+) -> BTreeMap<String, Vec<SyndicationLink>> {
+    let mut links = BTreeMap::<String, Vec<SyndicationLink>>::new();
+
+    for post in posts.iter().filter(|post| !post.retracted) {
+        let url = match permalink(post, mastodon_profiles) {
+            Some(url) => url,
+            None => {
+                log::warn!(
+                    "{} |> No permalink for {} on {}, skipping",
+                    post.original_uri,
+                    post.id,
+                    post.account
+                );
+                continue;
+            }
+        };
+
+        let original = match key {
+            Key::Guid => &post.original_guid,
+            Key::Uri => &post.original_uri,
+        };
+
+        links
+            .entry(original.clone())
+            .or_default()
+            .push(SyndicationLink {
+                network: post.social_network.to_string(),
+                account: post.account.clone(),
+                url,
+            });
+    }
+
+    links
+}
+
+fn permalink(post: &SyndicatedPost, mastodon_profiles: &HashMap<String, String>) -> Option<String> {
+    match post.social_network {
+        Network::Twitter => Some(format!("https://twitter.com/i/web/status/{}", post.id)),
+        Network::Mastodon => mastodon_profiles
+            .get(&post.account)
+            .map(|profile| format!("{}/{}", profile.trim_end_matches('/'), post.id)),
+        // i.e. at://did:plc:someone/app.bsky.feed.post/3k4duaz5vfs2b
+        Network::Bluesky => {
+            let (did, rkey) = post
+                .id
+                .strip_prefix("at://")?
+                .split_once("/app.bsky.feed.post/")?;
+            Some(format!("https://bsky.app/profile/{did}/post/{rkey}"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{syndication_links, Key, SyndicationLink};
+    use crate::cross_publisher::syndicated_post::SyndicatedPost;
+    use crate::social::Network;
+
+    fn post(network: Network, account: &str, id: &str, guid: &str) -> SyndicatedPost {
+        SyndicatedPost {
+            social_network: network,
+            account: String::from(account),
+            id: String::from(id),
+            original_guid: String::from(guid),
+            original_uri: format!("http://example.com/{guid}"),
+            thread: vec![],
+            content_hash: None,
+            retracted: false,
+        }
+    }
+
+    #[test]
+    fn test_permalinks_are_built_per_network() {
+        let posts = vec![
+            post(Network::Twitter, "twitter", "1600", "post-1"),
+            post(Network::Mastodon, "fosstodon", "1090", "post-1"),
+            post(
+                Network::Bluesky,
+                "bluesky",
+                "at://did:plc:someone/app.bsky.feed.post/3k4duaz5vfs2b",
+                "post-2",
+            ),
+            post(Network::Mastodon, "removed", "1091", "post-2"),
+            SyndicatedPost {
+                retracted: true,
+                ..post(Network::Twitter, "twitter", "1601", "post-2")
+            },
+        ];
+        let profiles = HashMap::from([(
+            String::from("fosstodon"),
+            String::from("https://fosstodon.org/@someone"),
+        )]);
+
+        let links = syndication_links(&posts, Key::Uri, &profiles);
+
+        assert_eq!(
+            links.get("http://example.com/post-1").unwrap(),
+            &vec![
+                SyndicationLink {
+                    network: String::from("twitter"),
+                    account: String::from("twitter"),
+                    url: String::from("https://twitter.com/i/web/status/1600"),
+                },
+                SyndicationLink {
+                    network: String::from("mastodon"),
+                    account: String::from("fosstodon"),
+                    url: String::from("https://fosstodon.org/@someone/1090"),
+                },
+            ]
+        );
+        assert_eq!(
+            links.get("http://example.com/post-2").unwrap(),
+            &vec![SyndicationLink {
+                network: String::from("bluesky"),
+                account: String::from("bluesky"),
+                url: String::from("https://bsky.app/profile/did:plc:someone/post/3k4duaz5vfs2b"),
+            }]
+        );
+        assert_eq!(
+            syndication_links(&posts, Key::Guid, &profiles)
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["post-1", "post-2"]
+        );
+    }
+}
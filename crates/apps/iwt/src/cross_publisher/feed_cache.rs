@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use rusqlite::{Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::commons::db;
+use crate::social::Account;
+
+/// `ETag` and `Last-Modified` headers of a feed, sent back as `If-None-Match` and
+/// `If-Modified-Since` on the next run
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// SHA-256 of the configured accounts. The validators are only sent back to a feed syndicated to
+/// the same accounts, otherwise an added account would never get the items of an unchanged feed.
+#[must_use]
+pub fn targets_hash(accounts: &[Account]) -> String {
+    let mut accounts = accounts.iter().map(ToString::to_string).collect::<Vec<_>>();
+    accounts.sort();
+
+    let mut hasher = Sha256::new();
+    for account in accounts {
+        hasher.update(account);
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+pub struct SqliteFeedCache {
+    conn: Rc<Connection>,
+}
+
+impl SqliteFeedCache {
+    pub fn new(conn: Rc<Connection>) -> Self {
+        Self { conn }
+    }
+
+    pub fn init_table(&self) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS feed_cache (
+                    url           TEXT PRIMARY KEY,
+                    etag          TEXT,
+                    last_modified TEXT,
+                    targets       TEXT NOT NULL DEFAULT ''
+                )",
+            (),
+        )?;
+        if !db::has_column(&self.conn, "feed_cache", "targets")? {
+            self.conn.execute(
+                "ALTER TABLE feed_cache ADD COLUMN targets TEXT NOT NULL DEFAULT ''",
+                (),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The validators of the feed, if it was syndicated to the same targets, see `targets_hash`
+    pub fn find(&self, url: &str, targets: &str) -> rusqlite::Result<Option<CacheValidators>> {
+        self.conn
+            .query_row(
+                "SELECT etag, last_modified FROM feed_cache
+                 WHERE url = :url AND targets = :targets",
+                &[(":url", url), (":targets", targets)],
+                |row| {
+                    Ok(CacheValidators {
+                        etag: row.get("etag")?,
+                        last_modified: row.get("last_modified")?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn store(
+        &self,
+        url: &str,
+        targets: &str,
+        validators: &CacheValidators,
+    ) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO feed_cache (url, etag, last_modified, targets)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (url)
+                    DO UPDATE SET etag = excluded.etag, last_modified = excluded.last_modified,
+                        targets = excluded.targets",
+                (url, &validators.etag, &validators.last_modified, targets),
+            )
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use rusqlite::Connection;
+
+    use super::{targets_hash, CacheValidators, SqliteFeedCache};
+    use crate::social::{Account, Network};
+
+    #[test]
+    fn test_validators_are_stored_per_url() {
+        let cache = SqliteFeedCache::new(Rc::new(Connection::open_in_memory().unwrap()));
+        cache.init_table().unwrap();
+        let targets = targets_hash(&[Account::from(Network::Mastodon)]);
+
+        let validators = CacheValidators {
+            etag: Some(String::from("\"v1\"")),
+            last_modified: None,
+        };
+        cache
+            .store("http://example.com/rss.xml", &targets, &validators)
+            .unwrap();
+
+        assert_eq!(
+            cache.find("http://example.com/rss.xml", &targets).unwrap(),
+            Some(validators)
+        );
+        assert_eq!(
+            cache.find("http://example.com/atom.xml", &targets).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_validators_are_not_found_once_the_targets_change() {
+        let cache = SqliteFeedCache::new(Rc::new(Connection::open_in_memory().unwrap()));
+        cache.init_table().unwrap();
+        let mut accounts = vec![Account::from(Network::Mastodon)];
+
+        cache
+            .store(
+                "http://example.com/rss.xml",
+                &targets_hash(&accounts),
+                &CacheValidators::default(),
+            )
+            .unwrap();
+        accounts.push(Account::from(Network::Twitter));
+
+        assert_eq!(
+            cache
+                .find("http://example.com/rss.xml", &targets_hash(&accounts))
+                .unwrap(),
+            None
+        );
+        assert_ne!(
+            cache
+                .find("http://example.com/rss.xml", &targets_hash(&accounts[..1]))
+                .unwrap(),
+            None
+        );
+    }
+}
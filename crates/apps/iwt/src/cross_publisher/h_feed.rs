@@ -0,0 +1,257 @@
+use async_trait::async_trait;
+use rss::{Category, Channel, Guid, Item};
+use scraper::{ElementRef, Html, Selector};
+use serde_json::json;
+use url::Url;
+
+use super::json_feed::extension_map_from_json;
+use super::rss::Client;
+use crate::config::Config;
+
+/// Loads the `h-feed` of a page, so that posts can be syndicated straight from HTML
+pub struct ReqwestClient {
+    /// The `network` or `network:account` targets of the entries
+    targets: Vec<String>,
+}
+
+impl ReqwestClient {
+    /// The entries target the targets of the `h_feed` config, or every configured target
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        let targets = match &config.h_feed {
+            Some(h_feed) if !h_feed.targets.is_empty() => h_feed.targets.clone(),
+            _ => config
+                .targets
+                .iter()
+                .map(|target| target.account().to_string())
+                .collect(),
+        };
+
+        Self { targets }
+    }
+}
+
+#[async_trait(?Send)]
+impl Client for ReqwestClient {
+    async fn get_channel(
+        &self,
+        url: &str,
+    ) -> Result<Option<Channel>, Box<dyn std::error::Error + 'static>> {
+        let response = reqwest::get(url).await?;
+        let base_url = response.url().clone();
+        let html = response.text().await?;
+
+        log::debug!("Response received from url: {}", url);
+
+        let channel = to_channel(&html, &base_url, &self.targets);
+
+        log::debug!(
+            "Successfully loaded h-feed \"{}\", with {} entries",
+            channel.title(),
+            channel.items().len()
+        );
+        Ok(Some(channel))
+    }
+}
+
+/// Maps the top-level `h-entry`s of the page's `h-feed` to the RSS item model, the entries nested
+/// in them (i.e. replies) are left out. Without an `h-feed` the top-level `h-entry`s of the page
+/// are used. As there is no `iwt` extension, the entries target the `targets`, entries having a
+/// `u-syndication` link are already syndicated and are skipped.
+#[must_use]
+pub fn to_channel(html: &str, base_url: &Url, targets: &[String]) -> Channel {
+    let document = Html::parse_document(html);
+    let feed = document
+        .select(&Selector::parse(".h-feed").unwrap())
+        .next()
+        .unwrap_or_else(|| document.root_element());
+
+    let items = properties(feed, "h-entry")
+        .into_iter()
+        .filter_map(|entry| to_item(entry, base_url, targets))
+        .collect();
+
+    Channel {
+        title: first_property(feed, "p-name").map(text).unwrap_or_default(),
+        link: base_url.to_string(),
+        items,
+        ..Default::default()
+    }
+}
+
+fn to_item(entry: ElementRef, base_url: &Url, targets: &[String]) -> Option<Item> {
+    let url = match first_property(entry, "u-url").and_then(|url| url_value(url, base_url)) {
+        Some(url) => url,
+        None => {
+            log::warn!("Skipping h-entry without u-url");
+            return None;
+        }
+    };
+
+    if !properties(entry, "u-syndication").is_empty() {
+        log::info!("{} |> Has u-syndication, skipping", url);
+        return None;
+    }
+
+    let tags = properties(entry, "p-category")
+        .into_iter()
+        .map(text)
+        .collect::<Vec<_>>();
+
+    Some(Item {
+        title: first_property(entry, "p-name").map(text),
+        link: Some(url.clone()),
+        guid: Some(Guid {
+            value: url,
+            permalink: true,
+        }),
+        description: first_property(entry, "e-content").map(|content| content.inner_html()),
+        pub_date: first_property(entry, "dt-published").and_then(|published| {
+            let value = published
+                .value()
+                .attr("datetime")
+                .map_or_else(|| text(published), String::from);
+            chrono::DateTime::parse_from_rfc3339(&value)
+                .map(|date| date.to_rfc2822())
+                .ok()
+        }),
+        categories: tags
+            .iter()
+            .map(|tag| Category {
+                name: tag.clone(),
+                domain: None,
+            })
+            .collect(),
+        extensions: extension_map_from_json(&json!({
+            "targetNetworks": targets,
+            "tags": tags,
+        })),
+        ..Default::default()
+    })
+}
+
+/// Elements of the property which belong to the microformat, the properties of nested
+/// microformats (i.e. the `p-name` of an author's `h-card`) are excluded. With an `h-*` class, the
+/// microformats which aren't nested in another one are selected.
+fn properties<'a>(microformat: ElementRef<'a>, class: &str) -> Vec<ElementRef<'a>> {
+    microformat
+        .select(&Selector::parse(&format!(".{class}")).unwrap())
+        .filter(|element| {
+            element
+                .ancestors()
+                .take_while(|ancestor| ancestor.id() != microformat.id())
+                .filter_map(ElementRef::wrap)
+                .all(|ancestor| !is_microformat_root(ancestor))
+        })
+        .collect()
+}
+
+fn first_property<'a>(microformat: ElementRef<'a>, class: &str) -> Option<ElementRef<'a>> {
+    properties(microformat, class).into_iter().next()
+}
+
+fn is_microformat_root(element: ElementRef) -> bool {
+    element
+        .value()
+        .classes()
+        .any(|class| class.starts_with("h-"))
+}
+
+fn text(element: ElementRef) -> String {
+    element.text().collect::<String>().trim().to_string()
+}
+
+fn url_value(element: ElementRef, base_url: &Url) -> Option<String> {
+    element
+        .value()
+        .attr("href")
+        .or_else(|| element.value().attr("src"))
+        .and_then(|url| base_url.join(url).ok())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod test {
+    use url::Url;
+
+    use super::to_channel;
+    use crate::cross_publisher::rss_item_ext::{IwtRssTargetNetwork, RssItemExt};
+    use crate::social::Network;
+
+    #[test]
+    fn test_to_channel_maps_the_entries_of_the_h_feed() {
+        let html = r#"<!DOCTYPE html>
+            <html><body>
+              <div class="h-entry"><a class="u-url" href="/not-in-the-feed">Elsewhere</a></div>
+              <main class="h-feed">
+                <h1 class="p-name">Some blog</h1>
+                <article class="h-entry">
+                  <h2 class="p-name">Post 1</h2>
+                  <a class="p-author h-card" href="/"><span class="p-name">Someone</span></a>
+                  <div class="e-content"><p>Some <b>post</b></p></div>
+                  <a class="u-url" href="/posts/post-1"><time class="dt-published" datetime="2023-01-02T10:00:00Z">Jan 2</time></a>
+                  <a class="p-category" href="/tags/cat">cat</a>
+                  <div class="p-comment h-entry">
+                    <div class="e-content">Some reply</div>
+                    <a class="u-url" href="https://elsewhere.example/reply-1">Reply</a>
+                  </div>
+                </article>
+                <article class="h-entry">
+                  <div class="e-content"><p>Already syndicated</p></div>
+                  <a class="u-url" href="/posts/post-2">Post 2</a>
+                  <a class="u-syndication" href="https://mastodon.example/@someone/1">Mastodon</a>
+                </article>
+              </main>
+            </body></html>"#;
+
+        let channel = to_channel(
+            html,
+            &Url::parse("http://example.com/posts/").unwrap(),
+            &[String::from("mastodon:fosstodon")],
+        );
+
+        assert_eq!(channel.title(), "Some blog");
+        assert_eq!(channel.items().len(), 1);
+
+        let item = &channel.items()[0];
+        assert_eq!(item.title(), Some("Post 1"));
+        assert_eq!(item.link(), Some("http://example.com/posts/post-1"));
+        assert_eq!(
+            item.guid().unwrap().value(),
+            "http://example.com/posts/post-1"
+        );
+        assert_eq!(item.description(), Some("<p>Some <b>post</b></p>"));
+        assert_eq!(item.pub_date(), Some("Mon, 02 Jan 2023 10:00:00 +0000"));
+
+        let extension = item.get_iwt_extension().unwrap();
+        assert_eq!(extension.tags, vec![String::from("cat")]);
+        assert_eq!(
+            extension.target_networks,
+            vec![IwtRssTargetNetwork {
+                network: Network::Mastodon,
+                account: Some(String::from("fosstodon")),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_to_channel_uses_the_top_level_entries_without_h_feed() {
+        let html = r#"<html><body>
+              <article class="h-entry">
+                <div class="e-content">Some note</div>
+                <a class="u-url" href="http://example.com/notes/1">Permalink</a>
+              </article>
+            </body></html>"#;
+
+        let channel = to_channel(
+            html,
+            &Url::parse("http://example.com/").unwrap(),
+            &[String::from("mastodon")],
+        );
+
+        assert_eq!(
+            channel.items()[0].link(),
+            Some("http://example.com/notes/1")
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use rss::extension::atom::{AtomExtension, Link};
+use rss::{
+    extension::Extension, extension::ExtensionMap, Category, Channel, Enclosure, Guid, Item,
+};
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// JSON Feed 1.1, only the fields used by the syndication, see https://www.jsonfeed.org/version/1.1/
+#[derive(Deserialize)]
+struct JsonFeed {
+    title: String,
+    home_page_url: Option<String>,
+    feed_url: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    hubs: Vec<Hub>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Deserialize)]
+struct Hub {
+    #[serde(rename = "type")]
+    hub_type: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedItem {
+    id: String,
+    url: Option<String>,
+    title: Option<String>,
+    content_html: Option<String>,
+    content_text: Option<String>,
+    date_published: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    /// The `iwt` extension, its fields are named like the elements of the RSS extension
+    #[serde(rename = "_iwt")]
+    iwt: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Attachment {
+    url: String,
+    mime_type: String,
+    size_in_bytes: Option<u64>,
+}
+
+/// Parses the JSON Feed into the RSS item model, the `_iwt` object is converted into the `iwt`
+/// RSS extension so that it can be read by `RssItemExt`
+pub fn read_channel(feed: &[u8]) -> Result<Channel, serde_json::Error> {
+    let feed = serde_json::from_slice::<JsonFeed>(feed)?;
+
+    Ok(Channel {
+        title: feed.title,
+        link: feed.home_page_url.unwrap_or_default(),
+        description: feed.description.unwrap_or_default(),
+        items: feed.items.into_iter().map(to_item).collect(),
+        // The WebSub hub and the topic, as in RSS
+        atom_ext: Some(AtomExtension {
+            links: feed
+                .hubs
+                .into_iter()
+                .filter(|hub| hub.hub_type.eq_ignore_ascii_case("websub"))
+                .map(|hub| Link {
+                    href: hub.url,
+                    rel: String::from("hub"),
+                    ..Default::default()
+                })
+                .chain(feed.feed_url.map(|feed_url| Link {
+                    href: feed_url,
+                    rel: String::from("self"),
+                    ..Default::default()
+                }))
+                .collect(),
+        }),
+        ..Default::default()
+    })
+}
+
+fn to_item(item: JsonFeedItem) -> Item {
+    Item {
+        title: item.title,
+        link: Some(item.url.unwrap_or_else(|| item.id.clone())),
+        guid: Some(Guid {
+            value: item.id,
+            permalink: false,
+        }),
+        description: item.content_html.or(item.content_text),
+        pub_date: item.date_published.and_then(|date| {
+            chrono::DateTime::parse_from_rfc3339(&date)
+                .map(|date| date.to_rfc2822())
+                .ok()
+        }),
+        categories: item
+            .tags
+            .into_iter()
+            .map(|tag| Category {
+                name: tag,
+                domain: None,
+            })
+            .collect(),
+        enclosure: item
+            .attachments
+            .into_iter()
+            .find(|attachment| attachment.mime_type.starts_with("image/"))
+            .map(|attachment| Enclosure {
+                url: attachment.url,
+                length: attachment
+                    .size_in_bytes
+                    .map(|size| size.to_string())
+                    .unwrap_or_default(),
+                mime_type: attachment.mime_type,
+            }),
+        extensions: item
+            .iwt
+            .map(|iwt| extension_map_from_json(&iwt))
+            .unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+/// Builds the `iwt` extension from its JSON representation, which is used by the sources that
+/// don't have XML extensions, i.e. JSON Feed and h-feed
+#[must_use]
+pub fn extension_map_from_json(iwt: &Value) -> ExtensionMap {
+    BTreeMap::from([(
+        String::from("iwt"),
+        BTreeMap::from([(
+            String::from("extension"),
+            vec![to_extension("extension", iwt)],
+        )]),
+    )])
+}
+
+/// Objects become elements with children, arrays become elements with a child for every value,
+/// named in singular (i.e. `targetNetworks` contains `targetNetwork`s), as in the RSS extension
+fn to_extension(name: &str, value: &Value) -> Extension {
+    let mut extension = Extension {
+        name: format!("iwt:{name}"),
+        ..Default::default()
+    };
+
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                extension
+                    .children
+                    .insert(key.clone(), vec![to_extension(key, value)]);
+            }
+        }
+        Value::Array(values) => {
+            let singular = name.strip_suffix('s').unwrap_or(name);
+            extension.children.insert(
+                singular.to_string(),
+                values
+                    .iter()
+                    .map(|value| to_extension(singular, value))
+                    .collect(),
+            );
+        }
+        Value::String(value) => extension.value = Some(value.clone()),
+        Value::Null => {}
+        value => extension.value = Some(value.to_string()),
+    }
+
+    extension
+}
+
+#[cfg(test)]
+mod test {
+    use super::read_channel;
+    use crate::cross_publisher::rss_item_ext::{IwtRssExtension, IwtRssTargetNetwork, RssItemExt};
+    use crate::social::Network;
+
+    #[test]
+    fn test_read_channel_maps_the_items_with_the_iwt_extension() {
+        let feed = r#"{
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": "Some blog",
+            "home_page_url": "http://example.com/",
+            "feed_url": "http://example.com/feed.json",
+            "hubs": [{ "type": "WebSub", "url": "https://hub.example.com/" }],
+            "items": [
+                {
+                    "id": "post-1",
+                    "url": "http://example.com/post-1",
+                    "content_html": "<p>Some post</p>",
+                    "date_published": "2023-01-02T10:00:00Z",
+                    "attachments": [
+                        { "url": "http://example.com/cat.png", "mime_type": "image/png" }
+                    ],
+                    "_iwt": {
+                        "targetNetworks": ["mastodon:fosstodon", "twitter"],
+                        "contentWarning": "Cats",
+                        "tags": ["cat"]
+                    }
+                }
+            ]
+        }"#;
+
+        let channel = read_channel(feed.as_bytes()).unwrap();
+        let item = &channel.items()[0];
+
+        assert_eq!(channel.link(), "http://example.com/");
+        assert_eq!(
+            channel
+                .atom_ext()
+                .unwrap()
+                .links()
+                .iter()
+                .map(|link| (link.rel(), link.href()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("hub", "https://hub.example.com/"),
+                ("self", "http://example.com/feed.json")
+            ]
+        );
+        assert_eq!(item.link(), Some("http://example.com/post-1"));
+        assert_eq!(item.guid().unwrap().value(), "post-1");
+        assert_eq!(item.description(), Some("<p>Some post</p>"));
+        assert_eq!(item.pub_date(), Some("Mon, 02 Jan 2023 10:00:00 +0000"));
+        assert_eq!(
+            item.enclosure().unwrap().url(),
+            "http://example.com/cat.png"
+        );
+        assert_eq!(
+            item.get_iwt_extension(),
+            Some(IwtRssExtension {
+                target_networks: vec![
+                    IwtRssTargetNetwork {
+                        network: Network::Mastodon,
+                        account: Some(String::from("fosstodon")),
+                    },
+                    IwtRssTargetNetwork {
+                        network: Network::Twitter,
+                        account: None,
+                    },
+                ],
+                content_warning: Some(String::from("Cats")),
+                tags: vec![String::from("cat")],
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
+            })
+        );
+    }
+}
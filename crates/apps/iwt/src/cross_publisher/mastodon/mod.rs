@@ -1,34 +1,105 @@
 use std::rc::Rc;
 
+use super::media::{self, Media};
+use super::rate_limit::RateLimiter;
 use super::rss_item_ext::IwtRssExtension;
 use super::syndicated_post::SyndicatedPost;
-use super::target::Target;
+use super::target::{self, PartiallyPublished, Preview, Rejected, Target};
+use crate::commons::auth::token_db::TokenDB;
+use crate::commons::permashort_link::PermashortCitation;
 use crate::commons::{text, url_shortener};
-use crate::social::Network;
+use crate::config::{self, Visibility};
+use crate::social::Account;
+use crate::IwtError;
 use async_trait::async_trait;
 use futures::TryFutureExt;
 use oauth2::AccessToken;
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, StatusCode};
 use rss::Item;
+use sha2::{Digest, Sha256};
 
-pub struct Mastodon<USClient: url_shortener::Client> {
+/// Default limits of Mastodon, instances may be configured differently
+const MAX_ATTACHMENTS: usize = 4;
+const MAX_IMAGE_SIZE: usize = 16 * 1024 * 1024;
+const MAX_DESCRIPTION_LENGTH: usize = 1500;
+/// Polls of an uploaded image before giving up on its processing
+const MAX_MEDIA_POLLS: usize = 10;
+const MEDIA_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Rate limits, Mastodon limits the uploads and the deletions separately from the other requests
+const API: &str = "api";
+const MEDIA: &str = "media";
+const DELETE: &str = "delete";
+
+pub struct Mastodon<DB: TokenDB, USClient: url_shortener::Client> {
+    account: Account,
     base_uri: String,
-    access_token: AccessToken,
+    thread: bool,
+    edits: bool,
+    options: StatusOptions,
+    token_db: Rc<DB>,
     http_client: Client,
     url_shortener_client: Rc<USClient>,
+    rate_limiter: RateLimiter,
+}
+
+/// Options of the published statuses, the items can override them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatusOptions {
+    pub visibility: Option<Visibility>,
+    pub language: Option<String>,
+    pub sensitive: bool,
+    pub in_reply_to_id: Option<String>,
+}
+
+impl StatusOptions {
+    /// The options overridden by the extension of the item for the account
+    #[must_use]
+    pub fn with_extension(&self, extension: &IwtRssExtension, account: &Account) -> Self {
+        Self {
+            visibility: extension.visibility.or(self.visibility),
+            language: extension.language.clone().or_else(|| self.language.clone()),
+            sensitive: extension.sensitive.unwrap_or(self.sensitive),
+            in_reply_to_id: extension
+                .in_reply_to_id(account)
+                .map(str::to_string)
+                .or_else(|| self.in_reply_to_id.clone()),
+        }
+    }
 }
 
-impl<USClient: url_shortener::Client> Mastodon<USClient> {
+impl From<&config::Mastodon> for StatusOptions {
+    fn from(config: &config::Mastodon) -> Self {
+        Self {
+            visibility: config.visibility,
+            language: config.language.clone(),
+            sensitive: config.sensitive,
+            in_reply_to_id: config.in_reply_to_id.clone(),
+        }
+    }
+}
+
+impl<DB: TokenDB, USClient: url_shortener::Client> Mastodon<DB, USClient> {
     pub fn new(
+        account: Account,
         base_uri: String,
-        access_token: AccessToken,
+        thread: bool,
+        edits: bool,
+        options: StatusOptions,
+        token_db: Rc<DB>,
         url_shortener_client: Rc<USClient>,
     ) -> Self {
         Self {
+            account,
             base_uri,
-            access_token,
+            thread,
+            edits,
+            options,
+            token_db,
             http_client: Client::new(),
             url_shortener_client,
+            rate_limiter: RateLimiter::default(),
         }
     }
 }
@@ -37,6 +108,28 @@ impl<USClient: url_shortener::Client> Mastodon<USClient> {
 struct UpdateStatusRequest {
     status: String,
     spoiler_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_reply_to_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visibility: Option<Visibility>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    sensitive: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    media_ids: Vec<String>,
+}
+
+/// Editing a status replaces its attachments, so the existing ones are sent again. The visibility
+/// and the parent of a status can't be edited.
+#[derive(serde::Serialize)]
+struct EditStatusRequest {
+    status: String,
+    spoiler_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    sensitive: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    media_ids: Vec<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -44,8 +137,358 @@ struct MastodonResponse {
     id: String,
 }
 
+#[derive(serde::Deserialize)]
+struct CredentialAccountResponse {
+    id: String,
+}
+
+#[derive(serde::Deserialize)]
+struct AccountStatusResponse {
+    id: String,
+    content: String,
+    in_reply_to_id: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct StatusResponse {
+    #[serde(default)]
+    media_attachments: Vec<MediaAttachmentResponse>,
+}
+
+#[derive(serde::Deserialize)]
+struct MediaAttachmentResponse {
+    id: String,
+}
+
+impl<DB: TokenDB, USClient: url_shortener::Client> Mastodon<DB, USClient> {
+    /// Mastodon returns the status created by an earlier request with the same idempotency key
+    /// instead of creating it again, the keys are kept for an hour
+    async fn post_status(
+        &self,
+        access_token: &AccessToken,
+        idempotency_key: &str,
+        request: &UpdateStatusRequest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.rate_limiter.acquire(API).await?;
+
+        self.http_client
+            .post(format!("{}/api/v1/statuses", self.base_uri))
+            .bearer_auth(access_token.secret())
+            .header("Idempotency-Key", idempotency_key)
+            .json(request)
+            .send()
+            .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
+            .and_then(|response| async move {
+                self.rate_limiter
+                    .check(API, &response)
+                    .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)?;
+                if !response.status().is_success() {
+                    return Err(Box::new(Rejected {
+                        status: response.status().as_u16(),
+                        message: response.text().await.unwrap_or_default(),
+                    }) as Box<dyn std::error::Error>);
+                }
+                let body = response
+                    .text()
+                    .await
+                    .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)?;
+
+                serde_json::from_str::<MastodonResponse>(&body)
+                    .map(|response| response.id)
+                    .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
+            })
+            .await
+    }
+
+    async fn edit_status(
+        &self,
+        access_token: &AccessToken,
+        id: &str,
+        request: &EditStatusRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.rate_limiter.acquire(API).await?;
+        let response = self
+            .http_client
+            .put(format!("{}/api/v1/statuses/{id}", self.base_uri))
+            .bearer_auth(access_token.secret())
+            .json(request)
+            .send()
+            .await?;
+        self.rate_limiter.check(API, &response)?;
+
+        if !response.status().is_success() {
+            return Err(Box::new(IwtError::new(&format!(
+                "Couldn't edit status {id}, status: {}",
+                response.status()
+            ))));
+        }
+
+        Ok(())
+    }
+
+    /// A status which doesn't exist anymore is considered to be deleted
+    async fn delete_status(
+        &self,
+        access_token: &AccessToken,
+        id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.rate_limiter.acquire(DELETE).await?;
+        let response = self
+            .http_client
+            .delete(format!("{}/api/v1/statuses/{id}", self.base_uri))
+            .bearer_auth(access_token.secret())
+            .send()
+            .await?;
+        self.rate_limiter.check(DELETE, &response)?;
+
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(Box::new(IwtError::new(&format!(
+                "Couldn't delete status {id}, status: {}",
+                response.status()
+            ))));
+        }
+
+        Ok(())
+    }
+
+    /// The recent statuses of the account
+    async fn account_statuses(
+        &self,
+        access_token: &AccessToken,
+    ) -> Result<Vec<AccountStatusResponse>, Box<dyn std::error::Error>> {
+        self.rate_limiter.acquire(API).await?;
+        let response = self
+            .http_client
+            .get(format!(
+                "{}/api/v1/accounts/verify_credentials",
+                self.base_uri
+            ))
+            .bearer_auth(access_token.secret())
+            .send()
+            .await?;
+        self.rate_limiter.check(API, &response)?;
+        let account = response
+            .error_for_status()?
+            .json::<CredentialAccountResponse>()
+            .await?;
+
+        self.rate_limiter.acquire(API).await?;
+        let response = self
+            .http_client
+            .get(format!(
+                "{}/api/v1/accounts/{}/statuses?limit=40",
+                self.base_uri, account.id
+            ))
+            .bearer_auth(access_token.secret())
+            .send()
+            .await?;
+        self.rate_limiter.check(API, &response)?;
+        Ok(response
+            .error_for_status()?
+            .json::<Vec<AccountStatusResponse>>()
+            .await?)
+    }
+
+    /// IDs of the attachments of the status
+    async fn media_ids(
+        &self,
+        access_token: &AccessToken,
+        id: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.rate_limiter.acquire(API).await?;
+        let response = self
+            .http_client
+            .get(format!("{}/api/v1/statuses/{id}", self.base_uri))
+            .bearer_auth(access_token.secret())
+            .send()
+            .await?;
+        self.rate_limiter.check(API, &response)?;
+        Ok(response
+            .error_for_status()?
+            .json::<StatusResponse>()
+            .await?
+            .media_attachments
+            .into_iter()
+            .map(|media| media.id)
+            .collect())
+    }
+
+    /// The text of the statuses, a single one or the parts of the thread
+    async fn statuses(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let permashort_citation = self
+            .url_shortener_client
+            .put_uri(post.link.as_ref().unwrap())
+            .await?;
+
+        Ok(self.texts(post, extension, &permashort_citation))
+    }
+
+    fn texts(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+        permashort_citation: &PermashortCitation,
+    ) -> Vec<String> {
+        let status = extension.text(post, &self.account, self.thread);
+        if self.thread {
+            text::thread_with_permashort_citation(status, 500, permashort_citation, &extension.tags)
+        } else {
+            vec![text::shorten_with_permashort_citation(
+                status,
+                500,
+                permashort_citation,
+                &extension.tags,
+            )]
+        }
+    }
+
+    /// Uploads the image and waits until the instance processes it, statuses cannot have
+    /// unprocessed attachments
+    async fn upload_media(
+        &self,
+        access_token: &AccessToken,
+        media: Media,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let mut form = Form::new().part(
+            "file",
+            Part::bytes(media.bytes)
+                .file_name("image")
+                .mime_str(&media.mime_type)?,
+        );
+        if let Some(alt) = media.alt {
+            form = form.text(
+                "description",
+                text::shorten(&alt, MAX_DESCRIPTION_LENGTH).to_string(),
+            );
+        }
+
+        self.rate_limiter.acquire(MEDIA).await?;
+        let response = self
+            .http_client
+            .post(format!("{}/api/v2/media", self.base_uri))
+            .bearer_auth(access_token.secret())
+            .multipart(form)
+            .send()
+            .await?;
+        self.rate_limiter.check(MEDIA, &response)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Box::new(IwtError::new(&format!(
+                "Couldn't upload media, status: {status}"
+            ))));
+        }
+
+        let id = response.json::<MediaAttachmentResponse>().await?.id;
+        if status != StatusCode::ACCEPTED {
+            return Ok(id);
+        }
+
+        // The upload is accepted before it's processed, the attachment is partial until then
+        for _ in 0..MAX_MEDIA_POLLS {
+            tokio::time::sleep(MEDIA_POLL_INTERVAL).await;
+
+            self.rate_limiter.acquire(MEDIA).await?;
+            let response = self
+                .http_client
+                .get(format!("{}/api/v1/media/{id}", self.base_uri))
+                .bearer_auth(access_token.secret())
+                .send()
+                .await?;
+            self.rate_limiter.check(MEDIA, &response)?;
+
+            let status = response.status();
+
+            if status == StatusCode::PARTIAL_CONTENT {
+                continue;
+            }
+            if !status.is_success() {
+                return Err(Box::new(IwtError::new(&format!(
+                    "Couldn't process media {id}, status: {status}"
+                ))));
+            }
+            return Ok(id);
+        }
+
+        Err(Box::new(IwtError::new(&format!(
+            "Media {id} wasn't processed in time"
+        ))))
+    }
+}
+
+impl<DB: TokenDB, USClient: url_shortener::Client> Mastodon<DB, USClient> {
+    /// Publishes the statuses of the post after the `published` parts of its thread
+    async fn publish_thread(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+        published: &[String],
+    ) -> Result<SyndicatedPost, Box<dyn std::error::Error>> {
+        let access_token = self.token_db.get_access_token(&self.account)?;
+
+        let statuses = self.statuses(post, extension).await?;
+        let remaining = statuses.len().saturating_sub(published.len());
+        self.rate_limiter
+            .ensure(API, u32::try_from(remaining).unwrap_or(u32::MAX))
+            .await?;
+
+        // The images are attached to the first part, they have been uploaded already if it's
+        // published
+        let mut media_ids = vec![];
+        if published.is_empty() {
+            for media in
+                media::download_all(&self.http_client, post, MAX_ATTACHMENTS, MAX_IMAGE_SIZE).await
+            {
+                media_ids.push(self.upload_media(&access_token, media).await?);
+            }
+        }
+
+        // Every part of the thread is a reply to the previous one
+        let options = self.options.with_extension(extension, &self.account);
+        let guid = post.guid().map_or("", |guid| guid.value());
+        let mut ids = published.to_vec();
+        for (part, status) in statuses.into_iter().enumerate().skip(published.len()) {
+            let request = UpdateStatusRequest {
+                status,
+                spoiler_text: extension.content_warning.clone(),
+                in_reply_to_id: ids
+                    .last()
+                    .cloned()
+                    .or_else(|| options.in_reply_to_id.clone()),
+                visibility: options.visibility,
+                language: options.language.clone(),
+                sensitive: options.sensitive,
+                media_ids: std::mem::take(&mut media_ids),
+            };
+
+            let idempotency_key = idempotency_key(guid, &self.account, part);
+            match self
+                .post_status(&access_token, &idempotency_key, &request)
+                .await
+            {
+                Ok(id) => ids.push(id),
+                Err(err) if ids.is_empty() => return Err(err),
+                Err(source) => {
+                    return Err(Box::new(PartiallyPublished {
+                        ids,
+                        rest: None,
+                        source,
+                    }))
+                }
+            }
+        }
+
+        let id = ids.remove(0);
+        Ok(SyndicatedPost::new(&self.account, &id, post).with_thread(ids))
+    }
+}
+
 #[async_trait(?Send)]
-impl<WHClient: url_shortener::Client> Target for Mastodon<WHClient> {
+impl<DB: TokenDB, WHClient: url_shortener::Client> Target for Mastodon<DB, WHClient> {
     async fn publish<'a>(
         &self,
         post: &Item,
@@ -53,39 +496,1063 @@ impl<WHClient: url_shortener::Client> Target for Mastodon<WHClient> {
     ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
         log::debug!("processing post: {:?},\nextension: {:?}", post, extension);
 
+        Ok(self.publish_thread(post, extension, &[]).await?)
+    }
+
+    async fn resume<'a>(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+        published: &[String],
+        _rest: Option<&str>,
+    ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
+        Ok(self.publish_thread(post, extension, published).await?)
+    }
+
+    /// Looks for the status with the permashort citation of the post among the recent statuses of
+    /// the account. The citation is in the last part of a thread, its first part is found by
+    /// following the replies back.
+    async fn reconcile<'a>(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+    ) -> Result<Option<SyndicatedPost>, Box<dyn std::error::Error + 'a>> {
+        let access_token = self.token_db.get_access_token(&self.account)?;
+
         let permashort_citation = self
             .url_shortener_client
             .put_uri(post.link.as_ref().unwrap())
             .await?;
-
-        let status = text::shorten_with_permashort_citation(
-            post.description().unwrap(),
-            500,
-            &permashort_citation,
-            &extension.tags,
+        let (uri, citation) = (
+            permashort_citation.to_uri(),
+            permashort_citation.to_string(),
         );
 
-        self.http_client
-            // TODO: make mastodon instance configurable
-            .post(format!("{}/api/v1/statuses", self.base_uri))
-            .bearer_auth(self.access_token.secret().clone())
-            .json(&UpdateStatusRequest {
+        let statuses = self.account_statuses(&access_token).await?;
+        let last = match statuses
+            .iter()
+            .find(|status| status.content.contains(&uri) || status.content.contains(&citation))
+        {
+            Some(last) => last,
+            None => return Ok(None),
+        };
+
+        let in_reply_to_id = self
+            .options
+            .with_extension(extension, &self.account)
+            .in_reply_to_id;
+        let mut ids = vec![last.id.clone()];
+        let mut parent = last.in_reply_to_id.as_ref();
+        while let Some(status) = parent
+            .filter(|_| self.thread)
+            .filter(|id| Some(*id) != in_reply_to_id.as_ref())
+            .and_then(|id| statuses.iter().find(|status| status.id == *id))
+        {
+            ids.insert(0, status.id.clone());
+            parent = status.in_reply_to_id.as_ref();
+        }
+
+        let id = ids.remove(0);
+        Ok(Some(
+            SyndicatedPost::new(&self.account, &id, post).with_thread(ids),
+        ))
+    }
+
+    /// Edits every part of the thread, a thread whose number of parts has changed can't be edited
+    async fn update<'a>(
+        &self,
+        syndicated_post: &SyndicatedPost,
+        post: &Item,
+        extension: &IwtRssExtension,
+    ) -> Result<Option<SyndicatedPost>, Box<dyn std::error::Error + 'a>> {
+        let access_token = self.token_db.get_access_token(&self.account)?;
+
+        let statuses = self.statuses(post, extension).await?;
+        let ids = std::iter::once(&syndicated_post.id)
+            .chain(syndicated_post.thread.iter())
+            .collect::<Vec<_>>();
+        if statuses.len() != ids.len() {
+            log::warn!(
+                "{} |> The edited post has {} parts instead of {}",
+                post.link().unwrap(),
+                statuses.len(),
+                ids.len()
+            );
+            return Ok(None);
+        }
+
+        let options = self.options.with_extension(extension, &self.account);
+        for (i, (id, status)) in ids.iter().zip(statuses).enumerate() {
+            let request = EditStatusRequest {
                 status,
                 spoiler_text: extension.content_warning.clone(),
-            })
-            .send()
-            .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
-            .and_then(|response| async {
-                let body = response.text().await.expect("Response body expected");
+                language: options.language.clone(),
+                sensitive: options.sensitive,
+                media_ids: if i == 0 {
+                    self.media_ids(&access_token, id).await?
+                } else {
+                    vec![]
+                },
+            };
+            self.edit_status(&access_token, id, &request).await?;
+        }
 
-                serde_json::from_str::<MastodonResponse>(&body)
-                    .map(|response| SyndicatedPost::new(Network::Mastodon, &response.id, post))
-                    .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
+        Ok(Some(
+            SyndicatedPost::new(&self.account, &syndicated_post.id, post)
+                .with_thread(syndicated_post.thread.clone()),
+        ))
+    }
+
+    /// Deletes the replies first, so that no part of the thread is left without its parent
+    async fn delete<'a>(
+        &self,
+        syndicated_post: &SyndicatedPost,
+    ) -> Result<(), Box<dyn std::error::Error + 'a>> {
+        let access_token = self.token_db.get_access_token(&self.account)?;
+
+        for id in syndicated_post
+            .thread
+            .iter()
+            .rev()
+            .chain(std::iter::once(&syndicated_post.id))
+        {
+            self.delete_status(&access_token, id).await?;
+        }
+
+        Ok(())
+    }
+
+    fn preview(&self, post: &Item, extension: &IwtRssExtension) -> Preview {
+        Preview {
+            texts: self.texts(post, extension, &target::placeholder_citation()),
+            spoiler_text: extension.content_warning.clone(),
+            tags: extension.tags.clone(),
+            attachments: media::media_references(post)
+                .into_iter()
+                .take(MAX_ATTACHMENTS)
+                .collect(),
+        }
+    }
+
+    fn propagates_edits(&self) -> bool {
+        self.edits
+    }
+
+    fn account(&self) -> &Account {
+        &self.account
+    }
+}
+
+/// The key of a part of the post on the account, the same part of the same post always gets the
+/// same key
+fn idempotency_key(guid: &str, account: &Account, part: usize) -> String {
+    hex::encode(Sha256::digest(format!("{guid}|{account}|{part}")))
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use rss::{GuidBuilder, Item};
+    use serde_json::{json, Value};
+    use wiremock::{
+        http::{HeaderName, Method},
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{Mastodon, StatusOptions};
+    use crate::commons::url_shortener::stubs::StubUrlShortenerClient;
+    use crate::config::Visibility;
+    use crate::cross_publisher::media::MediaReference;
+    use crate::cross_publisher::rate_limit::RateLimited;
+    use crate::cross_publisher::rss_item_ext::{
+        IwtRssExtension, IwtRssOverride, IwtRssTargetNetwork,
+    };
+    use crate::cross_publisher::syndicated_post::{content_hash, SyndicatedPost};
+    use crate::cross_publisher::target::{PartiallyPublished, Target};
+    use crate::social::{Account, Network};
+    use crate::stubs::auth::token_db::stubs::StubTokenDB;
+
+    #[tokio::test]
+    async fn test_publish_as_thread_replies_to_the_previous_part() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "2" })))
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            true,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(format!("<p>{}</p>", "word ".repeat(120).trim())),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        let syndicated = mastodon.publish(&item, &extension).await.unwrap();
+
+        assert_eq!(syndicated.id, "1");
+        assert_eq!(syndicated.thread, vec![String::from("2")]);
+
+        let requests = mock_server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+
+        let first = serde_json::from_slice::<Value>(&requests[0].body).unwrap();
+        let second = serde_json::from_slice::<Value>(&requests[1].body).unwrap();
+        assert!(first["status"].as_str().unwrap().ends_with(" 1/2"));
+        assert_eq!(first.get("in_reply_to_id"), None);
+        assert!(second["status"]
+            .as_str()
+            .unwrap()
+            .ends_with(" 2/2\n (localhost s/asdf)"));
+        assert_eq!(second["in_reply_to_id"], "1");
+
+        let idempotency_keys = requests
+            .iter()
+            .map(|request| {
+                request
+                    .headers
+                    .get(&HeaderName::from("Idempotency-Key"))
+                    .map(|values| values.last().as_str().to_string())
             })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            idempotency_keys,
+            vec![
+                Some(super::idempotency_key(
+                    "post-1",
+                    &Account::from(Network::Mastodon),
+                    0
+                )),
+                Some(super::idempotency_key(
+                    "post-1",
+                    &Account::from(Network::Mastodon),
+                    1
+                )),
+            ]
+        );
+        assert_ne!(idempotency_keys[0], idempotency_keys[1]);
+    }
+
+    #[test]
+    fn test_idempotency_keys_differ_per_account() {
+        assert_ne!(
+            super::idempotency_key("post-1", &Account::new(Network::Mastodon, "personal"), 0),
+            super::idempotency_key("post-1", &Account::new(Network::Mastodon, "project"), 0)
+        );
+        assert_eq!(
+            super::idempotency_key("post-1", &Account::new(Network::Mastodon, "personal"), 0),
+            super::idempotency_key("post-1", &Account::new(Network::Mastodon, "personal"), 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_finds_the_thread_published_by_an_interrupted_run() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/api/v1/accounts/verify_credentials"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "7" })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/accounts/7/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                {
+                    "id": "3",
+                    "content": "<p>Some post 2/2<br>(localhost s/asdf)</p>",
+                    "in_reply_to_id": "2",
+                },
+                { "id": "2", "content": "<p>Some post 1/2</p>", "in_reply_to_id": "1" },
+                { "id": "1", "content": "<p>Some pinned status</p>", "in_reply_to_id": null },
+            ])))
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            true,
+            false,
+            StatusOptions {
+                in_reply_to_id: Some(String::from("1")),
+                ..StatusOptions::default()
+            },
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        let reconciled = mastodon
+            .reconcile(&item, &extension)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(reconciled.id, "2");
+        assert_eq!(reconciled.thread, vec![String::from("3")]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_uses_the_text_override_of_the_account() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::new(Network::Mastodon, "project"),
+            mock_server.uri(),
+            false,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(String::from("<p>Some post</p>")),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: Some(String::from("Some text")),
+            summary: None,
+            overrides: vec![IwtRssOverride {
+                target_network: IwtRssTargetNetwork {
+                    network: Network::Mastodon,
+                    account: Some(String::from("project")),
+                },
+                text: Some(String::from("Some hand-crafted toot")),
+                summary: None,
+                in_reply_to_id: None,
+            }],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        mastodon.publish(&item, &extension).await.unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let status = serde_json::from_slice::<Value>(&requests[0].body).unwrap();
+        assert_eq!(
+            status["status"],
+            "Some hand-crafted toot\n (localhost s/asdf)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_sends_the_status_options_overridden_by_the_item() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            false,
+            false,
+            StatusOptions {
+                visibility: Some(Visibility::Unlisted),
+                language: Some(String::from("en")),
+                sensitive: false,
+                in_reply_to_id: None,
+            },
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(String::from("<p>Some post</p>")),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![
+                IwtRssOverride {
+                    target_network: IwtRssTargetNetwork {
+                        network: Network::Mastodon,
+                        account: Some(String::from("project")),
+                    },
+                    text: None,
+                    summary: None,
+                    in_reply_to_id: Some(String::from("200")),
+                },
+                IwtRssOverride {
+                    target_network: IwtRssTargetNetwork {
+                        network: Network::Mastodon,
+                        account: Some(String::from("mastodon")),
+                    },
+                    text: None,
+                    summary: None,
+                    in_reply_to_id: Some(String::from("100")),
+                },
+            ],
+            visibility: Some(Visibility::Direct),
+            language: None,
+            sensitive: Some(true),
+        };
+
+        mastodon.publish(&item, &extension).await.unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let status = serde_json::from_slice::<Value>(&requests[0].body).unwrap();
+        assert_eq!(status["visibility"], "direct");
+        assert_eq!(status["language"], "en");
+        assert_eq!(status["sensitive"], true);
+        assert_eq!(status["in_reply_to_id"], "100");
+    }
+
+    #[tokio::test]
+    async fn test_publish_is_rate_limited_until_the_reset_of_the_limit() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("X-RateLimit-Remaining", "0")
+                    .insert_header("X-RateLimit-Reset", "2124-03-01T09:05:00.000Z"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            false,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(String::from("<p>Some post</p>")),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        for _ in 0..2 {
+            let err = mastodon.publish(&item, &extension).await.unwrap_err();
+
+            assert_eq!(
+                err.downcast_ref::<RateLimited>()
+                    .map(|rate_limited| rate_limited.until.to_rfc3339()),
+                Some(String::from("2124-03-01T09:05:00+00:00"))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_keeps_the_parts_of_the_thread_published_before_the_rate_limit() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "id": "1" }))
+                    .insert_header("X-RateLimit-Remaining", "1")
+                    .insert_header("X-RateLimit-Reset", "2124-03-01T09:05:00.000Z"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            true,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(format!("<p>{}</p>", "word ".repeat(120).trim())),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        let err = mastodon.publish(&item, &extension).await.unwrap_err();
+
+        let partially_published = err.downcast_ref::<PartiallyPublished>().unwrap();
+        assert_eq!(partially_published.ids, vec![String::from("1")]);
+        assert!(partially_published
+            .source
+            .downcast_ref::<RateLimited>()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_resume_publishes_the_rest_of_the_thread() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "2" })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            true,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(format!("<p>{}</p>", "word ".repeat(120).trim())),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        let syndicated = mastodon
+            .resume(&item, &extension, &[String::from("1")], None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            (syndicated.id.as_str(), syndicated.thread),
+            ("1", vec![String::from("2")])
+        );
+        let requests = mock_server.received_requests().await.unwrap();
+        let status = serde_json::from_slice::<Value>(&requests[0].body).unwrap();
+        assert!(status["status"].as_str().unwrap().contains(" 2/2"));
+        assert_eq!(status["in_reply_to_id"], "1");
+        assert_eq!(
+            requests[0]
+                .headers
+                .get(&HeaderName::from("Idempotency-Key"))
+                .map(|values| values.last().as_str().to_string()),
+            Some(super::idempotency_key(
+                "post-1",
+                &Account::from(Network::Mastodon),
+                1
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_attaches_the_images_of_the_post() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/images/cat.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(vec![1, 2, 3], "image/png"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/media"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "22" })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            false,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+
+        let item = Item {
+            link: Some(format!("{}/posts/post-1", mock_server.uri())),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(String::from(
+                r#"<p>Some post</p><img src="/images/cat.png" alt="A cat">"#,
+            )),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        mastodon.publish(&item, &extension).await.unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let upload = requests
+            .iter()
+            .find(|request| request.url.path() == "/api/v2/media")
+            .unwrap();
+        let upload_body = String::from_utf8_lossy(&upload.body);
+        assert!(upload_body.contains("name=\"description\"\r\n\r\nA cat"));
+
+        let status = requests
+            .iter()
+            .find(|request| request.url.path() == "/api/v1/statuses")
+            .unwrap();
+        let status = serde_json::from_slice::<Value>(&status.body).unwrap();
+        assert_eq!(status["media_ids"], json!(["22"]));
+    }
+
+    #[tokio::test]
+    async fn test_publish_waits_until_the_images_are_processed() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/images/cat.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(vec![1, 2, 3], "image/png"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/media"))
+            .respond_with(ResponseTemplate::new(202).set_body_json(json!({ "id": "22" })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/media/22"))
+            .respond_with(ResponseTemplate::new(206).set_body_json(json!({ "id": "22" })))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/media/22"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "22" })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            false,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+        let item = Item {
+            link: Some(format!("{}/posts/post-1", mock_server.uri())),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(String::from(
+                r#"<p>Some post</p><img src="/images/cat.png">"#,
+            )),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        mastodon.publish(&item, &extension).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_publish_is_rate_limited_while_the_images_are_processed() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/images/cat.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(vec![1, 2, 3], "image/png"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/media"))
+            .respond_with(ResponseTemplate::new(202).set_body_json(json!({ "id": "22" })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/media/22"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("X-RateLimit-Remaining", "0")
+                    .insert_header("X-RateLimit-Reset", "2124-03-01T09:05:00.000Z"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            false,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+        let item = Item {
+            link: Some(format!("{}/posts/post-1", mock_server.uri())),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(String::from(
+                r#"<p>Some post</p><img src="/images/cat.png">"#,
+            )),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        let err = mastodon.publish(&item, &extension).await.unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<RateLimited>()
+                .map(|rate_limited| rate_limited.until.to_rfc3339()),
+            Some(String::from("2124-03-01T09:05:00+00:00"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preview_uses_the_text_of_the_status_without_requests() {
+        let mock_server = MockServer::start().await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            false,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            description: Some(String::from(
+                r#"<p>Some post</p><img src="/image.png" alt="An image">"#,
+            )),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: Some(String::from("Spoiler")),
+            tags: vec![String::from("rust")],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+
+        let preview = mastodon.preview(&item, &extension);
+
+        assert_eq!(
+            preview.texts,
+            vec![String::from("Some post\n#Rust (example.com s/preview)")]
+        );
+        assert_eq!(preview.spoiler_text, Some(String::from("Spoiler")));
+        assert_eq!(
+            preview.attachments,
+            vec![MediaReference {
+                uri: String::from("http://example.com/image.png"),
+                alt: Some(String::from("An image")),
+            }]
+        );
+        assert!(mock_server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_edits_the_status_and_keeps_its_attachments() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/api/v1/statuses/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "1",
+                "media_attachments": [{ "id": "22" }],
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/api/v1/statuses/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            false,
+            true,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(String::from("<p>Some fixed post</p>")),
+            ..Default::default()
+        };
+        let extension = IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        };
+        let syndicated = SyndicatedPost {
+            social_network: Network::Mastodon,
+            account: String::from("mastodon"),
+            id: String::from("1"),
+            original_guid: String::from("post-1"),
+            original_uri: String::from("http://example.com/post-1"),
+            thread: vec![],
+            content_hash: Some(String::from("outdated")),
+            retracted: false,
+        };
+
+        let updated = mastodon
+            .update(&syndicated, &item, &extension)
             .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(updated.id, "1");
+        assert_eq!(
+            updated.content_hash,
+            Some(content_hash(&item, &Account::from(Network::Mastodon)))
+        );
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let edit = requests
+            .iter()
+            .find(|request| request.method == Method::Put)
+            .unwrap();
+        let edit = serde_json::from_slice::<Value>(&edit.body).unwrap();
+        assert!(edit["status"]
+            .as_str()
+            .unwrap()
+            .starts_with("Some fixed post"));
+        assert_eq!(edit["media_ids"], json!(["22"]));
     }
 
-    fn network(&self) -> Network {
-        Network::Mastodon
+    #[tokio::test]
+    async fn test_delete_deletes_the_replies_of_the_thread_first() {
+        let mock_server = MockServer::start().await;
+
+        for (id, status) in [("1", 200), ("2", 404), ("3", 200)] {
+            Mock::given(method("DELETE"))
+                .and(path(format!("/api/v1/statuses/{id}")))
+                .respond_with(ResponseTemplate::new(status))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let mastodon = Mastodon::new(
+            Account::from(Network::Mastodon),
+            mock_server.uri(),
+            true,
+            false,
+            StatusOptions::default(),
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        );
+        let item = Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            ..Default::default()
+        };
+        let syndicated = SyndicatedPost::new(&Account::from(Network::Mastodon), "1", &item)
+            .with_thread(vec![String::from("2"), String::from("3")]);
+
+        mastodon.delete(&syndicated).await.unwrap();
+
+        let deleted = mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| request.url.path().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deleted,
+            vec![
+                "/api/v1/statuses/3",
+                "/api/v1/statuses/2",
+                "/api/v1/statuses/1"
+            ]
+        );
     }
 }
@@ -0,0 +1,248 @@
+use reqwest::{header::CONTENT_TYPE, Client};
+use rss::Item;
+use scraper::{Html, Selector};
+use serde_derive::Serialize;
+use url::Url;
+
+use crate::IwtError;
+
+/// An image of the post, either an enclosure or an `<img>` of the description
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MediaReference {
+    pub uri: String,
+    pub alt: Option<String>,
+}
+
+/// A downloaded image which can be uploaded to the networks
+#[derive(Debug, PartialEq)]
+pub struct Media {
+    pub bytes: Vec<u8>,
+    pub mime_type: String,
+    pub alt: Option<String>,
+}
+
+/// Collects the images of the post, the enclosure comes first, then the images of the description
+/// in document order. Relative URIs are resolved against the link of the post.
+#[must_use]
+pub fn media_references(item: &Item) -> Vec<MediaReference> {
+    let base = item.link().and_then(|link| Url::parse(link).ok());
+    let resolve = |uri: &str| match &base {
+        Some(base) => base
+            .join(uri)
+            .map_or_else(|_| uri.to_string(), String::from),
+        None => uri.to_string(),
+    };
+
+    let mut references = item
+        .enclosure()
+        .filter(|enclosure| enclosure.mime_type().starts_with("image/"))
+        .map(|enclosure| MediaReference {
+            uri: resolve(enclosure.url()),
+            alt: None,
+        })
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    if let Some(description) = item.description() {
+        let fragment = Html::parse_fragment(description);
+        for img in fragment.select(&Selector::parse("img[src]").unwrap()) {
+            let uri = resolve(img.value().attr("src").unwrap());
+
+            if references.iter().all(|reference| reference.uri != uri) {
+                references.push(MediaReference {
+                    uri,
+                    alt: img
+                        .value()
+                        .attr("alt")
+                        .map(str::trim)
+                        .filter(|alt| !alt.is_empty())
+                        .map(String::from),
+                });
+            }
+        }
+    }
+
+    references
+}
+
+/// Downloads the images of the post within the limits of the network. Images which cannot be
+/// downloaded, aren't images or are too large are skipped.
+pub async fn download_all(
+    client: &Client,
+    item: &Item,
+    max_count: usize,
+    max_size: usize,
+) -> Vec<Media> {
+    let mut media = vec![];
+
+    for reference in media_references(item) {
+        if media.len() == max_count {
+            log::warn!("Too many images, skipping {}", reference.uri);
+            continue;
+        }
+
+        match download(client, &reference, max_size).await {
+            Ok(downloaded) => media.push(downloaded),
+            Err(err) => log::warn!("Skipping image {}: {}", reference.uri, err),
+        }
+    }
+
+    media
+}
+
+async fn download(
+    client: &Client,
+    reference: &MediaReference,
+    max_size: usize,
+) -> Result<Media, Box<dyn std::error::Error>> {
+    let response = client.get(&reference.uri).send().await?;
+
+    if !response.status().is_success() {
+        return Err(Box::new(IwtError::new(&format!(
+            "Unexpected status: {}",
+            response.status()
+        ))));
+    }
+
+    let mime_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    if !mime_type.starts_with("image/") {
+        return Err(Box::new(IwtError::new(&format!(
+            "Not an image: {mime_type}"
+        ))));
+    }
+
+    let too_large = |size| Box::new(IwtError::new(&format!("Image is too large: {size} bytes")));
+
+    match response.content_length() {
+        Some(length) if length > max_size as u64 => return Err(too_large(length as usize)),
+        _ => (),
+    }
+
+    // The length is only a hint, the download is stopped as soon as the limit is exceeded
+    let mut response = response;
+    let mut bytes = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() > max_size {
+            return Err(too_large(bytes.len()));
+        }
+    }
+
+    Ok(Media {
+        bytes,
+        mime_type,
+        alt: reference.alt.clone(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use reqwest::Client;
+    use rss::{EnclosureBuilder, Item};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{download_all, media_references, MediaReference};
+
+    #[test]
+    fn test_media_references_should_contain_the_enclosure_and_the_images() {
+        let item = Item {
+            link: Some(String::from("http://example.com/posts/post-1")),
+            description: Some(String::from(
+                r#"<p>Some post <img src="/images/cat.png" alt="A cat"></p>
+                <img src="http://example.com/images/header.jpg">"#,
+            )),
+            enclosure: Some(
+                EnclosureBuilder::default()
+                    .url("http://example.com/images/header.jpg")
+                    .mime_type("image/jpeg")
+                    .build(),
+            ),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            media_references(&item),
+            vec![
+                MediaReference {
+                    uri: String::from("http://example.com/images/header.jpg"),
+                    alt: None,
+                },
+                MediaReference {
+                    uri: String::from("http://example.com/images/cat.png"),
+                    alt: Some(String::from("A cat")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_media_references_should_skip_enclosures_which_are_not_images() {
+        let item = Item {
+            enclosure: Some(
+                EnclosureBuilder::default()
+                    .url("http://example.com/episode-1.mp3")
+                    .mime_type("audio/mpeg")
+                    .build(),
+            ),
+            ..Default::default()
+        };
+
+        assert_eq!(media_references(&item), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_download_all_should_respect_the_limits() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/small.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(vec![0; 10], "image/png"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/large.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(vec![0; 100], "image/png"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/huge.png"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(vec![0; 100], "image/png")
+                    .insert_header("Content-Length", "1000000"),
+            )
+            .expect(0..=1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/page.html"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("<html></html>", "text/html"))
+            .mount(&mock_server)
+            .await;
+
+        let uri = mock_server.uri();
+        let item = Item {
+            description: Some(format!(
+                r#"<img src="{uri}/large.png"><img src="{uri}/huge.png"><img src="{uri}/page.html">
+                <img src="{uri}/small.png" alt="Small"><img src="{uri}/small.png?again">"#
+            )),
+            ..Default::default()
+        };
+
+        let media = download_all(&Client::new(), &item, 1, 50).await;
+
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0].bytes, vec![0; 10]);
+        assert_eq!(media[0].mime_type, "image/png");
+        assert_eq!(media[0].alt, Some(String::from("Small")));
+    }
+}
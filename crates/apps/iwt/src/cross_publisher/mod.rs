@@ -1,53 +1,196 @@
 
+use std::path::Path;
 use std::rc::Rc;
+use std::time::Duration;
 
 use crate::commons::auth::token_db::SqliteTokenDB;
 use crate::commons::url_shortener::ReqwestClient;
-use crate::config::Config;
-use mastodon::Mastodon;
+use crate::config::{Config, TargetKind};
+use crate::error::Error;
+use bluesky::Bluesky;
+use feed_cache::SqliteFeedCache;
+use mastodon::{Mastodon, StatusOptions};
+use outbox::Outbox;
+use report::Report;
 use rusqlite::Connection;
 use syndicated_post::SqliteSyndycatedPostStorage;
 use target::Target;
 use twitter::Twitter;
+use webmention::SqliteSentWebmentionStorage;
 
+mod atom;
+pub mod backfeed;
+mod bluesky;
+pub mod daemon;
+pub mod export;
+mod feed_cache;
+mod h_feed;
+mod json_feed;
 mod mastodon;
+mod media;
+mod outbox;
+mod rate_limit;
+pub mod report;
 mod rss;
 mod rss_item_ext;
 mod syndicate;
 mod syndicated_post;
 mod target;
 mod twitter;
+mod webmention;
+mod websub;
 
-pub async fn execute(config: &Config, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let conn = Rc::new(Connection::open(&config.db.path).expect("Couldn't open DB"));
-
-    let token_db = Rc::new(SqliteTokenDB::new(Rc::clone(&conn)));
-
-    let url_shortener_client = Rc::new(ReqwestClient::new(
-        &config.url_shortener.protocol,
-        &config.url_shortener.domain,
-        config.url_shortener.put_base_uri.as_ref(),
-    ));
-
-    let targets: Vec<Box<dyn Target>> = vec![
-        Box::new(Twitter::new(
-            config.twitter.client_id.clone(),
-            token_db,
-            Rc::clone(&url_shortener_client),
-        )),
-        Box::new(Mastodon::new(
-            config.mastodon.base_uri.clone(),
-            config.mastodon.access_token.clone(),
-            Rc::clone(&url_shortener_client),
-        )),
-    ];
-
-    let storage = SqliteSyndycatedPostStorage::new(Rc::clone(&conn));
-    storage
-        .init_table()
-        .expect("Couldn't initialise post storage");
-
-    syndicate::syndicate(config, &rss::ReqwestClient, &targets, &storage, dry_run).await
+/// Targets, storage, outbox and feed clients, shared by `cross-publish`, `daemon` and `unpublish`
+struct Pipeline {
+    targets: Vec<Box<dyn Target>>,
+    storage: SqliteSyndycatedPostStorage,
+    outbox: Outbox,
+    rss_client: rss::ReqwestClient,
+    webmention: Option<webmention::Sender>,
+}
+
+impl Pipeline {
+    /// A dry run doesn't write to the db: the tables it creates or migrates are rolled back with
+    /// its transaction, which is never committed, and a missing db isn't created
+    fn new(config: &Config, dry_run: bool) -> Result<Self, Error> {
+        let conn = if dry_run && !Path::new(&config.db.path).exists() {
+            Connection::open_in_memory()?
+        } else {
+            Connection::open(&config.db.path)?
+        };
+        if dry_run {
+            conn.execute_batch("BEGIN")?;
+        }
+        let conn = Rc::new(conn);
+
+        let token_db = Rc::new(SqliteTokenDB::new(Rc::clone(&conn)));
+        token_db.init_table()?;
+
+        let url_shortener_client = Rc::new(ReqwestClient::new(
+            &config.url_shortener.protocol,
+            &config.url_shortener.domain,
+            config.url_shortener.put_base_uri.as_ref(),
+        ));
+
+        let targets = config
+            .targets
+            .iter()
+            .map(|target| -> Result<Box<dyn Target>, Error> {
+                Ok(match &target.kind {
+                    TargetKind::Twitter(twitter) => Box::new(Twitter::new(
+                        target.account(),
+                        twitter.client_id.clone(),
+                        twitter.thread,
+                        target.edits,
+                        Rc::clone(&token_db),
+                        Rc::clone(&url_shortener_client),
+                    )?),
+                    TargetKind::Mastodon(mastodon) => Box::new(Mastodon::new(
+                        target.account(),
+                        mastodon.base_uri.clone(),
+                        mastodon.thread,
+                        target.edits,
+                        StatusOptions::from(mastodon),
+                        Rc::clone(&token_db),
+                        Rc::clone(&url_shortener_client),
+                    )),
+                    TargetKind::Bluesky(bluesky) => Box::new(Bluesky::new(
+                        target.account(),
+                        bluesky.base_uri.clone(),
+                        bluesky.identifier.clone(),
+                        bluesky.app_password.clone(),
+                        target.edits,
+                        Rc::clone(&url_shortener_client),
+                    )),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if targets.is_empty() {
+            log::warn!("No targets are configured, nothing will be syndicated");
+        }
+
+        let storage = SqliteSyndycatedPostStorage::new(Rc::clone(&conn));
+        storage.init_table()?;
+
+        let outbox = Outbox::new(Rc::clone(&conn), &config.outbox);
+        outbox.init_table()?;
+
+        let feed_cache = SqliteFeedCache::new(Rc::clone(&conn));
+        feed_cache.init_table()?;
+        let accounts = targets
+            .iter()
+            .map(|target| target.account().clone())
+            .collect::<Vec<_>>();
+
+        let webmention = match &config.webmention {
+            Some(webmention) => {
+                let sent_webmentions = SqliteSentWebmentionStorage::new(Rc::clone(&conn));
+                sent_webmentions.init_table()?;
+                Some(webmention::Sender::new(
+                    sent_webmentions,
+                    Duration::from_secs(webmention.timeout),
+                )?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            targets,
+            storage,
+            outbox,
+            rss_client: rss::ReqwestClient::new(feed_cache, &accounts),
+            webmention,
+        })
+    }
+}
+
+/// Syndicates the feeds after retrying the failed publishes, the outcomes are reported
+pub async fn execute(config: &Config, dry_run: bool) -> Result<Report, Error> {
+    let pipeline = Pipeline::new(config, dry_run)?;
+
+    let mut report = syndicate::retry_failed(
+        &pipeline.targets,
+        &pipeline.storage,
+        &pipeline.outbox,
+        dry_run,
+    )
+    .await;
+
+    let rss_report = syndicate::syndicate(
+        config,
+        &pipeline.rss_client,
+        &pipeline.targets,
+        &pipeline.storage,
+        Some(&pipeline.outbox),
+        pipeline.webmention.as_ref(),
+        dry_run,
+    )
+    .await;
+    report.entries.extend(rss_report.entries);
+
+    if let Some(h_feed) = &config.h_feed {
+        let h_feed_report = syndicate::syndicate_feeds(
+            &h_feed.urls,
+            &h_feed::ReqwestClient::new(config),
+            &pipeline.targets,
+            &pipeline.storage,
+            Some(&pipeline.outbox),
+            pipeline.webmention.as_ref(),
+            dry_run,
+        )
+        .await;
+        report.entries.extend(h_feed_report.entries);
+    }
+
+    Ok(report)
+}
+
+/// Deletes the syndicated copies of the post with the guid, they are kept as retracted
+pub async fn unpublish(config: &Config, guid: &str, dry_run: bool) -> Result<(), Error> {
+    let pipeline = Pipeline::new(config, dry_run)?;
+
+    syndicate::unpublish(guid, &pipeline.targets, &pipeline.storage, dry_run).await
 }
 
 #[cfg(test)]
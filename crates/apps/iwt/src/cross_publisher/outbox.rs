@@ -0,0 +1,589 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use rss::Item;
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row};
+
+use crate::commons::db;
+use crate::config;
+use crate::social::Account;
+
+/// A publish which has failed, it's retried at `next_attempt_at` (a Unix timestamp) unless it has
+/// been dead-lettered. Scheduled publishes have no error, they are due at their `publishAt`.
+#[derive(Debug, PartialEq)]
+pub struct FailedPublish {
+    pub item: Item,
+    pub account: String,
+    pub error: String,
+    pub attempts: u32,
+    pub next_attempt_at: i64,
+    pub dead_lettered: bool,
+    /// IDs of the parts of a thread published before it failed, the retry resumes after them
+    pub published: Vec<String>,
+    /// The text left to publish after them, see `PartiallyPublished`
+    pub rest: Option<String>,
+}
+
+impl FailedPublish {
+    /// Fails with `FromSqlConversionFailure` if the stored item or parts can't be decoded
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            item: from_json(row, "item")?,
+            account: row.get("account")?,
+            error: row.get("error")?,
+            attempts: row.get("attempts")?,
+            next_attempt_at: row.get("next_attempt_at")?,
+            dead_lettered: row.get("dead_lettered")?,
+            published: from_json(row, "published")?,
+            rest: row.get("rest")?,
+        })
+    }
+}
+
+/// A publish of the outbox whose stored post can't be decoded
+#[derive(Debug, PartialEq)]
+pub struct UndecodablePublish {
+    pub guid: String,
+    pub error: String,
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(row: &Row, column: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(&row.get::<_, String>(column)?).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(
+            row.as_ref().column_index(column).unwrap_or_default(),
+            Type::Text,
+            Box::new(err),
+        )
+    })
+}
+
+/// The failed and the scheduled publishes of the posts per account. The failed ones are retried
+/// with an exponential backoff so that the APIs aren't hammered during outages. The publishes in
+/// flight are kept too, until their copy is stored.
+pub struct Outbox {
+    conn: Rc<Connection>,
+    backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: u32,
+}
+
+impl Outbox {
+    pub fn new(conn: Rc<Connection>, config: &config::Outbox) -> Self {
+        Self {
+            conn,
+            backoff: Duration::from_secs(config.backoff),
+            max_backoff: Duration::from_secs(config.max_backoff),
+            max_attempts: config.max_attempts,
+        }
+    }
+
+    pub fn init_table(&self) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                    guid            TEXT NOT NULL,
+                    account         TEXT NOT NULL,
+                    social_network  TEXT NOT NULL,
+                    item            TEXT NOT NULL,
+                    error           TEXT NOT NULL,
+                    attempts        INTEGER NOT NULL,
+                    next_attempt_at INTEGER NOT NULL,
+                    dead_lettered   INTEGER NOT NULL DEFAULT 0,
+                    published       TEXT NOT NULL DEFAULT '[]',
+                    rest            TEXT,
+                    updated_at      TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (guid, account)
+                )",
+            (),
+        )?;
+        if !db::has_column(&self.conn, "outbox", "published")? {
+            self.conn.execute(
+                "ALTER TABLE outbox ADD COLUMN published TEXT NOT NULL DEFAULT '[]'",
+                (),
+            )?;
+        }
+        if !db::has_column(&self.conn, "outbox", "rest")? {
+            self.conn
+                .execute("ALTER TABLE outbox ADD COLUMN rest TEXT", ())?;
+        }
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS in_flight (
+                    guid            TEXT NOT NULL,
+                    account         TEXT NOT NULL,
+                    social_network  TEXT NOT NULL,
+                    PRIMARY KEY (guid, account)
+                )",
+                (),
+            )
+            .map(|_| ())
+    }
+
+    pub fn find(&self, guid: &str, account: &Account) -> rusqlite::Result<Option<FailedPublish>> {
+        self.conn
+            .query_row(
+                "SELECT item, account, error, attempts, next_attempt_at, dead_lettered, published,
+                        rest
+                 FROM outbox WHERE guid = ?1 AND account = ?2",
+                (guid, &account.name),
+                FailedPublish::from_row,
+            )
+            .optional()
+    }
+
+    /// The publishes of the account which are due to be retried at `now`, and the ones which can't
+    /// be decoded. These are dead-lettered with the decoding error, unless it's a dry run.
+    pub fn due(
+        &self,
+        account: &Account,
+        now: i64,
+        dry_run: bool,
+    ) -> rusqlite::Result<(Vec<FailedPublish>, Vec<UndecodablePublish>)> {
+        let mut statement = self.conn.prepare(
+            "SELECT guid, item, account, error, attempts, next_attempt_at, dead_lettered, published,
+                    rest
+             FROM outbox
+             WHERE account = ?1 AND dead_lettered = 0 AND next_attempt_at <= ?2
+             ORDER BY next_attempt_at",
+        )?;
+
+        let mut failed = vec![];
+        let mut undecodable = vec![];
+        let mut rows = statement.query((&account.name, now))?;
+        while let Some(row) = rows.next()? {
+            match FailedPublish::from_row(row) {
+                Ok(publish) => failed.push(publish),
+                Err(rusqlite::Error::FromSqlConversionFailure(_, _, err)) => {
+                    undecodable.push(UndecodablePublish {
+                        guid: row.get("guid")?,
+                        error: format!("The stored post can't be decoded: {err}"),
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        if !dry_run {
+            for publish in &undecodable {
+                self.conn.execute(
+                    "UPDATE outbox SET dead_lettered = 1, error = ?3, updated_at = CURRENT_TIMESTAMP
+                     WHERE guid = ?1 AND account = ?2",
+                    (&publish.guid, &account.name, &publish.error),
+                )?;
+            }
+        }
+
+        Ok((failed, undecodable))
+    }
+
+    /// Keeps the post until it's due at `publish_at`. A post scheduled again is moved to its new
+    /// time, unless its publish has failed already.
+    pub fn schedule(
+        &self,
+        item: &Item,
+        account: &Account,
+        publish_at: i64,
+    ) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO outbox
+                   (guid, account, social_network, item, error, attempts, next_attempt_at)
+                 VALUES (?1, ?2, ?3, ?4, '', 0, ?5)
+                 ON CONFLICT (guid, account) DO UPDATE SET
+                   item = excluded.item,
+                   next_attempt_at = excluded.next_attempt_at,
+                   updated_at = CURRENT_TIMESTAMP
+                 WHERE attempts = 0",
+                (
+                    item.guid().map_or("", |guid| guid.value()),
+                    &account.name,
+                    account.network.to_string(),
+                    serde_json::to_string(item).unwrap(),
+                    publish_at,
+                ),
+            )
+            .map(|_| ())
+    }
+
+    /// Postpones the publish until `until` without counting a failed attempt, i.e. until the rate
+    /// limit of the network resets
+    pub fn defer(
+        &self,
+        item: &Item,
+        account: &Account,
+        error: &str,
+        until: i64,
+    ) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO outbox
+                   (guid, account, social_network, item, error, attempts, next_attempt_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)
+                 ON CONFLICT (guid, account) DO UPDATE SET
+                   item = excluded.item,
+                   error = excluded.error,
+                   next_attempt_at = excluded.next_attempt_at,
+                   updated_at = CURRENT_TIMESTAMP",
+                (
+                    item.guid().map_or("", |guid| guid.value()),
+                    &account.name,
+                    account.network.to_string(),
+                    serde_json::to_string(item).unwrap(),
+                    error,
+                    until,
+                ),
+            )
+            .map(|_| ())
+    }
+
+    /// Records the failed attempt and schedules the next one, the backoff is doubled on every
+    /// attempt. The publish is dead-lettered after `max_attempts`.
+    pub fn record_failure(
+        &self,
+        item: &Item,
+        account: &Account,
+        error: &str,
+        now: i64,
+    ) -> rusqlite::Result<FailedPublish> {
+        let guid = item.guid().map_or("", |guid| guid.value());
+        let (attempts, published, rest) = self
+            .find(guid, account)?
+            .map_or((0, vec![], None), |failed| {
+                (failed.attempts, failed.published, failed.rest)
+            });
+        let attempts = attempts + 1;
+        let dead_lettered = attempts >= self.max_attempts;
+        let backoff = self
+            .backoff
+            .saturating_mul(2_u32.saturating_pow(attempts - 1))
+            .min(self.max_backoff);
+        let next_attempt_at = now + i64::try_from(backoff.as_secs()).unwrap_or(i64::MAX - now);
+
+        self.conn.execute(
+            "INSERT INTO outbox
+               (guid, account, social_network, item, error, attempts, next_attempt_at, dead_lettered)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (guid, account) DO UPDATE SET
+               item = excluded.item,
+               error = excluded.error,
+               attempts = excluded.attempts,
+               next_attempt_at = excluded.next_attempt_at,
+               dead_lettered = excluded.dead_lettered,
+               updated_at = CURRENT_TIMESTAMP",
+            (
+                guid,
+                &account.name,
+                account.network.to_string(),
+                serde_json::to_string(item).unwrap(),
+                error,
+                attempts,
+                next_attempt_at,
+                dead_lettered,
+            ),
+        )?;
+
+        Ok(FailedPublish {
+            item: item.clone(),
+            account: account.name.clone(),
+            error: String::from(error),
+            attempts,
+            next_attempt_at,
+            dead_lettered,
+            published,
+            rest,
+        })
+    }
+
+    /// Keeps the IDs of the published parts of a thread and the text left to publish, so that the
+    /// next attempt doesn't publish them again
+    pub fn keep_published(
+        &self,
+        guid: &str,
+        account: &Account,
+        published: &[String],
+        rest: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "UPDATE outbox SET published = ?3, rest = ?4 WHERE guid = ?1 AND account = ?2",
+                (
+                    guid,
+                    &account.name,
+                    serde_json::to_string(published).unwrap(),
+                    rest,
+                ),
+            )
+            .map(|_| ())
+    }
+
+    /// Marks the publish in flight before it's sent. A publish which is still in flight on the next
+    /// attempt may have been published by an interrupted run.
+    pub fn mark_in_flight(&self, item: &Item, account: &Account) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO in_flight (guid, account, social_network)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT (guid, account) DO NOTHING",
+                (
+                    item.guid().map_or("", |guid| guid.value()),
+                    &account.name,
+                    account.network.to_string(),
+                ),
+            )
+            .map(|_| ())
+    }
+
+    /// Clears the in flight mark of a publish which has definitely failed, so that the next
+    /// attempt doesn't look for its copy
+    pub fn clear_in_flight(&self, guid: &str, account: &Account) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "DELETE FROM in_flight WHERE guid = ?1 AND account = ?2",
+                (guid, &account.name),
+            )
+            .map(|_| ())
+    }
+
+    pub fn is_in_flight(&self, guid: &str, account: &Account) -> rusqlite::Result<bool> {
+        self.conn
+            .query_row(
+                "SELECT 1 FROM in_flight WHERE guid = ?1 AND account = ?2",
+                (guid, &account.name),
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+    }
+
+    /// Removes the publish once it has succeeded
+    pub fn remove(&self, guid: &str, account: &Account) -> rusqlite::Result<()> {
+        for table in ["outbox", "in_flight"] {
+            self.conn.execute(
+                &format!("DELETE FROM {table} WHERE guid = ?1 AND account = ?2"),
+                (guid, &account.name),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use rss::{GuidBuilder, Item};
+    use rusqlite::Connection;
+
+    use super::Outbox;
+    use crate::config;
+    use crate::social::{Account, Network};
+
+    fn outbox() -> Outbox {
+        let outbox = Outbox::new(
+            Rc::new(Connection::open_in_memory().unwrap()),
+            &config::Outbox {
+                backoff: 60,
+                max_backoff: 300,
+                max_attempts: 4,
+            },
+        );
+        outbox.init_table().unwrap();
+        outbox
+    }
+
+    fn item() -> Item {
+        Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_failures_are_retried_with_backoff_until_dead_lettered() {
+        let outbox = outbox();
+        let account = Account::from(Network::Mastodon);
+
+        let next_attempts = (1..=4)
+            .map(|_| {
+                let failed = outbox
+                    .record_failure(&item(), &account, "Service Unavailable", 1000)
+                    .unwrap();
+                (failed.next_attempt_at, failed.dead_lettered)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            next_attempts,
+            vec![(1060, false), (1120, false), (1240, false), (1300, true)]
+        );
+        assert_eq!(
+            outbox.find("post-1", &account).unwrap().unwrap().item,
+            item()
+        );
+        assert!(outbox.due(&account, 2000, false).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_due_failures_are_found_per_account() {
+        let outbox = outbox();
+        let personal = Account::new(Network::Mastodon, "personal");
+        let project = Account::new(Network::Mastodon, "project");
+
+        outbox
+            .record_failure(&item(), &personal, "Bad Gateway", 1000)
+            .unwrap();
+
+        assert!(outbox.due(&personal, 1059, false).unwrap().0.is_empty());
+        assert_eq!(outbox.due(&personal, 1060, false).unwrap().0.len(), 1);
+        assert!(outbox.due(&project, 1060, false).unwrap().0.is_empty());
+
+        outbox.remove("post-1", &personal).unwrap();
+
+        assert_eq!(outbox.find("post-1", &personal).unwrap(), None);
+    }
+
+    #[test]
+    fn test_scheduled_publishes_are_due_at_their_time() {
+        let outbox = outbox();
+        let account = Account::from(Network::Mastodon);
+
+        outbox.schedule(&item(), &account, 5000).unwrap();
+        outbox.schedule(&item(), &account, 3000).unwrap();
+
+        assert!(outbox.due(&account, 2999, false).unwrap().0.is_empty());
+        assert_eq!(outbox.due(&account, 3000, false).unwrap().0[0].attempts, 0);
+
+        let failed = outbox
+            .record_failure(&item(), &account, "Bad Gateway", 3000)
+            .unwrap();
+        outbox.schedule(&item(), &account, 9000).unwrap();
+
+        assert_eq!(outbox.find("post-1", &account).unwrap(), Some(failed));
+    }
+
+    #[test]
+    fn test_deferred_publishes_keep_their_failed_attempts() {
+        let outbox = outbox();
+        let account = Account::from(Network::Mastodon);
+
+        outbox
+            .record_failure(&item(), &account, "Bad Gateway", 1000)
+            .unwrap();
+        outbox
+            .defer(&item(), &account, "Rate limited", 5000)
+            .unwrap();
+
+        let deferred = outbox.find("post-1", &account).unwrap().unwrap();
+        assert_eq!(
+            (
+                deferred.attempts,
+                deferred.error.as_str(),
+                deferred.next_attempt_at
+            ),
+            (1, "Rate limited", 5000)
+        );
+    }
+
+    #[test]
+    fn test_published_parts_are_kept_until_they_are_removed() {
+        let outbox = outbox();
+        let account = Account::from(Network::Mastodon);
+        let published = vec![String::from("1"), String::from("2")];
+
+        outbox
+            .record_failure(&item(), &account, "Bad Gateway", 1000)
+            .unwrap();
+        outbox
+            .keep_published("post-1", &account, &published, Some("the rest"))
+            .unwrap();
+        let failed = outbox
+            .record_failure(&item(), &account, "Bad Gateway", 2000)
+            .unwrap();
+
+        assert_eq!(
+            (failed.published.clone(), failed.rest.as_deref()),
+            (published.clone(), Some("the rest"))
+        );
+        let found = outbox.find("post-1", &account).unwrap().unwrap();
+        assert_eq!(
+            (found.published, found.rest.as_deref()),
+            (published, Some("the rest"))
+        );
+    }
+
+    #[test]
+    fn test_publishes_are_in_flight_until_they_are_removed() {
+        let outbox = outbox();
+        let account = Account::from(Network::Mastodon);
+
+        assert!(!outbox.is_in_flight("post-1", &account).unwrap());
+
+        outbox.mark_in_flight(&item(), &account).unwrap();
+        outbox.mark_in_flight(&item(), &account).unwrap();
+
+        assert!(outbox.is_in_flight("post-1", &account).unwrap());
+        assert!(!outbox
+            .is_in_flight("post-1", &Account::from(Network::Twitter))
+            .unwrap());
+
+        outbox.remove("post-1", &account).unwrap();
+
+        assert!(!outbox.is_in_flight("post-1", &account).unwrap());
+    }
+
+    #[test]
+    fn test_undecodable_publishes_are_dead_lettered() {
+        let outbox = outbox();
+        let account = Account::from(Network::Mastodon);
+        outbox
+            .record_failure(&item(), &account, "Service Unavailable", 1000)
+            .unwrap();
+        outbox
+            .conn
+            .execute("UPDATE outbox SET item = 'not json'", ())
+            .unwrap();
+
+        assert!(outbox.find("post-1", &account).is_err());
+        let (due, undecodable) = outbox.due(&account, 2000, false).unwrap();
+        assert_eq!(due, vec![]);
+        assert_eq!(undecodable[0].guid, "post-1");
+        assert_eq!(outbox.due(&account, 2000, false).unwrap(), (vec![], vec![]));
+
+        let (dead_lettered, error) = outbox
+            .conn
+            .query_row("SELECT dead_lettered, error FROM outbox", (), |row| {
+                Ok((row.get::<_, bool>(0)?, row.get::<_, String>(1)?))
+            })
+            .unwrap();
+        assert!(dead_lettered);
+        assert!(error.starts_with("The stored post can't be decoded"));
+    }
+
+    #[test]
+    fn test_undecodable_publishes_are_not_dead_lettered_by_a_dry_run() {
+        let outbox = outbox();
+        let account = Account::from(Network::Mastodon);
+        outbox
+            .record_failure(&item(), &account, "Service Unavailable", 1000)
+            .unwrap();
+        outbox
+            .conn
+            .execute("UPDATE outbox SET item = 'not json'", ())
+            .unwrap();
+
+        for _ in 0..2 {
+            let (due, undecodable) = outbox.due(&account, 2000, true).unwrap();
+            assert_eq!((due, undecodable.len()), (vec![], 1));
+        }
+
+        let (dead_lettered, error) = outbox
+            .conn
+            .query_row("SELECT dead_lettered, error FROM outbox", (), |row| {
+                Ok((row.get::<_, bool>(0)?, row.get::<_, String>(1)?))
+            })
+            .unwrap();
+        assert!(!dead_lettered);
+        assert_eq!(error, "Service Unavailable");
+    }
+}
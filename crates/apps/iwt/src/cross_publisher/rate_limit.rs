@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+
+/// Requests kept in reserve, the limit is considered reached before it's used up
+const RESERVE: u32 = 1;
+/// A reached limit is waited for if it resets within this, otherwise the request is deferred
+const MAX_WAIT: i64 = 60;
+/// The wait after a 429 response which doesn't tell when the limit resets
+const DEFAULT_RESET: i64 = 15 * 60;
+
+/// The request isn't sent until the rate limit of the endpoint resets
+#[derive(Debug, PartialEq)]
+pub struct RateLimited {
+    pub until: DateTime<Utc>,
+}
+
+impl Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rate limited until {}", self.until.to_rfc3339())
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+#[derive(Debug, PartialEq)]
+struct Limit {
+    remaining: u32,
+    reset: DateTime<Utc>,
+}
+
+/// The rate limits of a target per endpoint, as reported by the headers of the last response:
+/// `X-RateLimit-*` of Mastodon and `x-rate-limit-*` of Twitter. The concurrent requests reserve
+/// their part of the remaining limit.
+#[derive(Default)]
+pub struct RateLimiter {
+    limits: RefCell<HashMap<&'static str, Limit>>,
+}
+
+impl RateLimiter {
+    /// Waits until a request to the endpoint is allowed, or fails if the limit doesn't reset soon
+    pub async fn acquire(&self, endpoint: &'static str) -> Result<(), RateLimited> {
+        self.wait(endpoint, 1, true).await
+    }
+
+    /// Waits until the limit allows all the requests without reserving them, i.e. before the first
+    /// part of a thread, so that a thread isn't stopped halfway by the limit
+    pub async fn ensure(&self, endpoint: &'static str, requests: u32) -> Result<(), RateLimited> {
+        self.wait(endpoint, requests, false).await
+    }
+
+    async fn wait(
+        &self,
+        endpoint: &'static str,
+        requests: u32,
+        reserve: bool,
+    ) -> Result<(), RateLimited> {
+        let now = Utc::now();
+        let reset = {
+            let mut limits = self.limits.borrow_mut();
+            match limits.get_mut(endpoint) {
+                Some(limit) if limit.reset <= now => {
+                    limits.remove(endpoint);
+                    return Ok(());
+                }
+                Some(limit) if limit.remaining >= requests + RESERVE => {
+                    if reserve {
+                        limit.remaining -= requests;
+                    }
+                    return Ok(());
+                }
+                Some(limit) => limit.reset,
+                None => return Ok(()),
+            }
+        };
+
+        if reset - now > Duration::seconds(MAX_WAIT) {
+            return Err(RateLimited { until: reset });
+        }
+
+        log::info!(
+            "Rate limit of {} is reached, waiting until {}",
+            endpoint,
+            reset
+        );
+        tokio::time::sleep((reset - now).to_std().unwrap_or_default()).await;
+        self.limits.borrow_mut().remove(endpoint);
+        Ok(())
+    }
+
+    /// Updates the limit of the endpoint from the response, a 429 response fails with the time
+    /// the limit resets
+    pub fn check(&self, endpoint: &'static str, response: &Response) -> Result<(), RateLimited> {
+        self.update(endpoint, response.headers());
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let until = self
+                .limits
+                .borrow()
+                .get(endpoint)
+                .map(|limit| limit.reset)
+                .or_else(|| retry_after(response.headers()))
+                .unwrap_or_else(|| Utc::now() + Duration::seconds(DEFAULT_RESET));
+            return Err(RateLimited { until });
+        }
+
+        Ok(())
+    }
+
+    fn update(&self, endpoint: &'static str, headers: &HeaderMap) {
+        let remaining = header(
+            headers,
+            &["x-ratelimit-remaining", "x-rate-limit-remaining"],
+        )
+        .and_then(|remaining| remaining.parse().ok());
+        let reset =
+            header(headers, &["x-ratelimit-reset", "x-rate-limit-reset"]).and_then(parse_reset);
+
+        if let (Some(remaining), Some(reset)) = (remaining, reset) {
+            self.limits
+                .borrow_mut()
+                .insert(endpoint, Limit { remaining, reset });
+        }
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Mastodon sends the reset as an ISO 8601 time, Twitter as a Unix timestamp
+fn parse_reset(reset: &str) -> Option<DateTime<Utc>> {
+    match reset.parse::<i64>() {
+        Ok(timestamp) => Utc.timestamp_opt(timestamp, 0).single(),
+        Err(_) => DateTime::parse_from_rfc3339(reset)
+            .ok()
+            .map(|reset| reset.with_timezone(&Utc)),
+    }
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<DateTime<Utc>> {
+    header(headers, &[RETRY_AFTER.as_str()])
+        .and_then(|seconds| seconds.parse().ok())
+        .map(|seconds| Utc::now() + Duration::seconds(seconds))
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, TimeZone, Utc};
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use super::{parse_reset, RateLimited, RateLimiter};
+
+    fn headers(remaining: u32, reset: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from(remaining));
+        headers.insert("X-RateLimit-Reset", HeaderValue::from_str(reset).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_reset_is_parsed_as_timestamp_or_iso_8601() {
+        assert_eq!(
+            parse_reset("1709283900").map(|reset| reset.to_rfc3339()),
+            Some(String::from("2024-03-01T09:05:00+00:00"))
+        );
+        assert_eq!(
+            parse_reset("2024-03-01T09:05:00.000Z").map(|reset| reset.to_rfc3339()),
+            Some(String::from("2024-03-01T09:05:00+00:00"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_acquire_defers_when_the_limit_is_reached_until_a_later_reset() {
+        let rate_limiter = RateLimiter::default();
+        let reset = Utc.timestamp_opt(Utc::now().timestamp() + 600, 0).unwrap();
+        rate_limiter.update("statuses", &headers(2, &reset.timestamp().to_string()));
+
+        assert_eq!(rate_limiter.acquire("statuses").await, Ok(()));
+        assert_eq!(
+            rate_limiter.acquire("statuses").await,
+            Err(RateLimited { until: reset })
+        );
+        assert_eq!(rate_limiter.acquire("media").await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_ensure_checks_the_limit_of_every_request_without_reserving_them() {
+        let rate_limiter = RateLimiter::default();
+        let reset = Utc.timestamp_opt(Utc::now().timestamp() + 600, 0).unwrap();
+        rate_limiter.update("statuses", &headers(3, &reset.timestamp().to_string()));
+
+        assert_eq!(rate_limiter.ensure("statuses", 2).await, Ok(()));
+        assert_eq!(rate_limiter.ensure("statuses", 2).await, Ok(()));
+        assert_eq!(
+            rate_limiter.ensure("statuses", 3).await,
+            Err(RateLimited { until: reset })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_acquire_allows_the_requests_after_the_reset() {
+        let rate_limiter = RateLimiter::default();
+        let reset = Utc::now() - Duration::seconds(1);
+        rate_limiter.update("statuses", &headers(0, &reset.to_rfc3339()));
+
+        assert_eq!(rate_limiter.acquire("statuses").await, Ok(()));
+        assert!(rate_limiter.limits.borrow().is_empty());
+    }
+}
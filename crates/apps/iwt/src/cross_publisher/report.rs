@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+
+use clap::ValueEnum;
+use serde_derive::Serialize;
+
+use super::target::Preview;
+use crate::error::Error;
+use crate::social::Account;
+
+/// The format of the report printed by `cross-publish`
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// What happened to a post on a target, or to a feed
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Outcome {
+    Published,
+    /// The copy published by an interrupted run has been found and stored
+    Reconciled,
+    Edited,
+    Deleted,
+    /// The post has been syndicated before and it's unchanged
+    AlreadySyndicated,
+    /// The post isn't syndicated to the account
+    NotTargeted,
+    /// Publishing, editing or deleting is skipped due to `--dry-run`, the posts which would be
+    /// published or edited are previewed
+    DryRun {
+        preview: Option<Preview>,
+    },
+    /// The post isn't due yet, it's kept in the outbox until its `publishAt`
+    Scheduled {
+        publish_at: String,
+    },
+    /// The feed hasn't changed since it was syndicated the last time
+    Unchanged,
+    /// Nothing has been done, i.e. the post has been retracted or its retry isn't due yet
+    Skipped {
+        reason: String,
+    },
+    Failed {
+        kind: &'static str,
+        error: String,
+    },
+}
+
+impl Outcome {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Published => "published",
+            Self::Reconciled => "reconciled",
+            Self::Edited => "edited",
+            Self::Deleted => "deleted",
+            Self::AlreadySyndicated => "already syndicated",
+            Self::NotTargeted => "not targeted",
+            Self::DryRun { .. } => "dry run",
+            Self::Scheduled { .. } => "scheduled",
+            Self::Unchanged => "unchanged",
+            Self::Skipped { .. } => "skipped",
+            Self::Failed { .. } => "failed",
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scheduled { publish_at } => write!(f, "scheduled at {publish_at}"),
+            Self::Skipped { reason } => write!(f, "skipped: {reason}"),
+            Self::Failed { kind, error } => write!(f, "failed ({kind}): {error}"),
+            outcome => write!(f, "{}", outcome.name()),
+        }
+    }
+}
+
+/// The outcome of a post on a target. The outcomes of the feeds have no account, the retries from
+/// the outbox have no feed.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Entry {
+    pub feed: Option<String>,
+    pub guid: Option<String>,
+    pub account: Option<String>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn new(
+        feed: Option<&str>,
+        guid: Option<&str>,
+        account: Option<&Account>,
+        result: Result<Outcome, Error>,
+    ) -> Self {
+        Self {
+            feed: feed.map(String::from),
+            guid: guid.map(String::from),
+            account: account.map(Account::to_string),
+            outcome: result.unwrap_or_else(|err| Outcome::Failed {
+                kind: err.kind(),
+                error: err.to_string(),
+            }),
+        }
+    }
+}
+
+/// Outcomes of a syndication run, per feed, post and target
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub entries: Vec<Entry>,
+}
+
+impl Report {
+    pub fn failures(&self) -> impl Iterator<Item = &Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.outcome, Outcome::Failed { .. }))
+    }
+
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Writes the report as a table followed by the number of outcomes, or as JSON. The previews
+    /// of the dry runs are written below their rows.
+    pub fn write(&self, format: Format, out: &mut impl Write) -> std::io::Result<()> {
+        match format {
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut *out, self)?;
+                writeln!(out)
+            }
+            Format::Table => self.write_table(out),
+        }
+    }
+
+    fn write_table(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let rows = self
+            .entries
+            .iter()
+            .map(|entry| {
+                [
+                    entry.feed.as_deref().unwrap_or("-").to_string(),
+                    entry.guid.as_deref().unwrap_or("-").to_string(),
+                    entry.account.as_deref().unwrap_or("-").to_string(),
+                    entry.outcome.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let header = ["FEED", "POST", "TARGET", "OUTCOME"].map(String::from);
+        let mut widths = header.clone().map(|column| column.len());
+        for row in &rows {
+            for (width, column) in widths.iter_mut().zip(row) {
+                *width = (*width).max(column.len());
+            }
+        }
+
+        let previews = self.entries.iter().map(|entry| match &entry.outcome {
+            Outcome::DryRun {
+                preview: Some(preview),
+            } => Some(preview),
+            _ => None,
+        });
+        for (row, preview) in std::iter::once((&header, None)).chain(rows.iter().zip(previews)) {
+            writeln!(
+                out,
+                "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+            )?;
+            if let Some(preview) = preview {
+                write!(out, "{preview}")?;
+            }
+        }
+
+        let mut counts = BTreeMap::new();
+        for entry in &self.entries {
+            *counts.entry(entry.outcome.name()).or_insert(0) += 1;
+        }
+        let summary = counts
+            .iter()
+            .map(|(name, count)| format!("{name}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "\n{}", if summary.is_empty() { "-" } else { &summary })
+    }
+}
+
+impl FromIterator<Entry> for Report {
+    fn from_iter<T: IntoIterator<Item = Entry>>(iter: T) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{Entry, Format, Outcome, Report};
+    use crate::error::Error;
+    use crate::social::{Account, Network};
+
+    fn report() -> Report {
+        let account = Account::new(Network::Mastodon, "project");
+        Report {
+            entries: vec![
+                Entry::new(
+                    Some("http://example.com/rss.xml"),
+                    Some("post-1"),
+                    Some(&account),
+                    Ok(Outcome::Published),
+                ),
+                Entry::new(
+                    Some("http://example.com/rss.xml"),
+                    None,
+                    Some(&account),
+                    Err(Error::item(None, "Rss Item doesn't have a guid")),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_report_is_written_as_a_table() {
+        let mut out = vec![];
+
+        report().write(Format::Table, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+FEED                        POST    TARGET            OUTCOME
+http://example.com/rss.xml  post-1  mastodon:project  published
+http://example.com/rss.xml  -       mastodon:project  failed (item): Item error: Rss Item doesn't have a guid
+
+failed: 1, published: 1
+"
+        );
+    }
+
+    #[test]
+    fn test_report_is_written_as_json() {
+        let mut out = vec![];
+
+        report().write(Format::Json, &mut out).unwrap();
+
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&out).unwrap(),
+            json!({
+                "entries": [
+                    {
+                        "feed": "http://example.com/rss.xml",
+                        "guid": "post-1",
+                        "account": "mastodon:project",
+                        "outcome": "published",
+                    },
+                    {
+                        "feed": "http://example.com/rss.xml",
+                        "guid": null,
+                        "account": "mastodon:project",
+                        "outcome": "failed",
+                        "kind": "item",
+                        "error": "Item error: Rss Item doesn't have a guid",
+                    },
+                ]
+            })
+        );
+        assert!(!report().is_success());
+    }
+}
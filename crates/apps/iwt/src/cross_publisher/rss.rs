@@ -1,33 +1,129 @@
+use std::{cell::RefCell, collections::HashMap};
+
 use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
 use rss::Channel;
 
-pub struct ReqwestClient;
+use super::feed_cache::{self, CacheValidators, SqliteFeedCache};
+use super::{atom, json_feed};
+use crate::social::Account;
 
-#[async_trait]
+#[async_trait(?Send)]
 pub trait Client {
-    /// Loads RSS feed from the given URL a parse it into a Channel
-    async fn get_channel(&self, url: &str)
-        -> Result<Channel, Box<dyn std::error::Error + 'static>>;
+    /// Loads RSS, Atom or JSON feed from the given URL a parse it into a Channel, returns `None` if
+    /// the feed hasn't changed since it was syndicated the last time
+    async fn get_channel(
+        &self,
+        url: &str,
+    ) -> Result<Option<Channel>, Box<dyn std::error::Error + 'static>>;
+
+    /// Called when every item of the feed has been syndicated
+    fn feed_syndicated(&self, _url: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+/// Loads the feeds with conditional GET requests, as long as they are syndicated to the same
+/// accounts
+pub struct ReqwestClient {
+    cache: SqliteFeedCache,
+    /// Hash of the configured accounts, see `feed_cache::targets_hash`
+    targets: String,
+    http_client: reqwest::Client,
+    /// Validators of the loaded feeds, they are cached once the feed has been syndicated,
+    /// otherwise the failed items wouldn't be retried while the feed doesn't change
+    loaded: RefCell<HashMap<String, CacheValidators>>,
 }
 
-#[async_trait]
+impl ReqwestClient {
+    pub fn new(cache: SqliteFeedCache, accounts: &[Account]) -> Self {
+        Self {
+            cache,
+            targets: feed_cache::targets_hash(accounts),
+            http_client: reqwest::Client::new(),
+            loaded: RefCell::default(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
 impl Client for ReqwestClient {
     async fn get_channel(
         &self,
         url: &str,
-    ) -> Result<Channel, Box<dyn std::error::Error + 'static>> {
-        let feed = reqwest::get(url).await?.bytes().await?;
+    ) -> Result<Option<Channel>, Box<dyn std::error::Error + 'static>> {
+        let mut request = self.http_client.get(url);
+        if let Some(validators) = self.cache.find(url, &self.targets)? {
+            if let Some(etag) = validators.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = validators.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            log::info!("{} |> Not modified since the last run, skipping", url);
+            return Ok(None);
+        }
+
+        let validators = cache_validators(response.headers());
+        let feed = response.error_for_status()?.bytes().await?;
 
         log::debug!("Response received from url: {}", url);
 
-        let channel = Channel::read_from(&feed[..])?;
+        let channel = read_channel(&feed)?;
 
         log::debug!(
             "Successfully loaded channel \"{}\", with {} items",
             channel.title(),
             channel.items().len()
         );
-        Ok(channel)
+
+        self.loaded.borrow_mut().insert(url.to_string(), validators);
+
+        Ok(Some(channel))
+    }
+
+    fn feed_syndicated(&self, url: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(validators) = self.loaded.borrow_mut().remove(url) {
+            self.cache.store(url, &self.targets, &validators)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn cache_validators(headers: &HeaderMap) -> CacheValidators {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    };
+
+    CacheValidators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    }
+}
+
+/// Detects the format of the feed, RSS 2.0, Atom and JSON Feed are supported
+pub fn read_channel(feed: &[u8]) -> Result<Channel, Box<dyn std::error::Error + 'static>> {
+    if feed.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{') {
+        return Ok(json_feed::read_channel(feed)?);
+    }
+
+    match Channel::read_from(feed) {
+        Err(rss::Error::InvalidStartTag) => {
+            Ok(atom::to_channel(&atom_syndication::Feed::read_from(feed)?))
+        }
+        result => Ok(result?),
     }
 }
 
@@ -48,6 +144,7 @@ pub mod stubs {
 
     pub struct StubRssClient {
         pub urls: Arc<Mutex<Vec<String>>>,
+        pub syndicated_urls: std::sync::Mutex<Vec<String>>,
         items: HashMap<String, Vec<Item>>,
     }
 
@@ -56,6 +153,7 @@ pub mod stubs {
             Self {
                 items: items.clone(),
                 urls: Arc::default(),
+                syndicated_urls: std::sync::Mutex::default(),
             }
         }
     }
@@ -100,12 +198,12 @@ pub mod stubs {
         result
     }
 
-    #[async_trait]
+    #[async_trait(?Send)]
     impl Client for StubRssClient {
         async fn get_channel(
             &self,
             url: &str,
-        ) -> Result<Channel, Box<dyn std::error::Error + 'static>> {
+        ) -> Result<Option<Channel>, Box<dyn std::error::Error + 'static>> {
             let mut urls = self.urls.lock().await;
             urls.push(url.to_owned());
 
@@ -114,22 +212,32 @@ pub mod stubs {
                     let should_fail = parsed
                         .query_pairs()
                         .any(|(key, value)| &*key == "failure" && &*value == "1");
+                    let not_modified = parsed
+                        .query_pairs()
+                        .any(|(key, value)| &*key == "not_modified" && &*value == "1");
 
                     if should_fail {
                         Err(Box::new(RssClientError))
+                    } else if not_modified {
+                        Ok(None)
                     } else {
                         let channel = Channel {
-                            items: self.items.get(&url.to_string()).unwrap().clone(),
+                            items: self.items.get(url).unwrap().clone(),
                             link: url.to_owned(),
                             ..Default::default()
                         };
 
-                        Ok(channel)
+                        Ok(Some(channel))
                     }
                 }
                 _ => panic!("Invalid url: {url}"),
             }
         }
+
+        fn feed_syndicated(&self, url: &str) -> Result<(), Box<dyn std::error::Error>> {
+            self.syndicated_urls.lock().unwrap().push(url.to_owned());
+            Ok(())
+        }
     }
 
     #[derive(Debug)]
@@ -141,3 +249,110 @@ pub mod stubs {
     }
     impl std::error::Error for RssClientError {}
 }
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use rusqlite::Connection;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{read_channel, Client, ReqwestClient};
+    use crate::cross_publisher::feed_cache::SqliteFeedCache;
+    use crate::social::{Account, Network};
+
+    #[tokio::test]
+    async fn test_get_channel_skips_feeds_not_modified_since_they_were_syndicated() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/rss.xml"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rss.xml"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_raw(
+                        r#"<rss version="2.0"><channel><title>RSS</title></channel></rss>"#,
+                        "application/rss+xml",
+                    ),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let cache = SqliteFeedCache::new(Rc::new(Connection::open_in_memory().unwrap()));
+        cache.init_table().unwrap();
+        let client = ReqwestClient::new(cache, &[Account::from(Network::Mastodon)]);
+        let url = format!("{}/rss.xml", mock_server.uri());
+
+        // The validators are only cached once the feed has been syndicated
+        assert!(client.get_channel(&url).await.unwrap().is_some());
+        assert!(client.get_channel(&url).await.unwrap().is_some());
+
+        client.feed_syndicated(&url).unwrap();
+
+        assert!(client.get_channel(&url).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_loads_the_whole_feed_once_a_target_is_added() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/rss.xml"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rss.xml"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_raw(
+                        r#"<rss version="2.0"><channel><title>RSS</title></channel></rss>"#,
+                        "application/rss+xml",
+                    ),
+            )
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        SqliteFeedCache::new(Rc::clone(&conn)).init_table().unwrap();
+        let url = format!("{}/rss.xml", mock_server.uri());
+        let mut accounts = vec![Account::from(Network::Mastodon)];
+
+        let client = ReqwestClient::new(SqliteFeedCache::new(Rc::clone(&conn)), &accounts);
+        assert!(client.get_channel(&url).await.unwrap().is_some());
+        client.feed_syndicated(&url).unwrap();
+
+        accounts.push(Account::from(Network::Twitter));
+        let client = ReqwestClient::new(SqliteFeedCache::new(Rc::clone(&conn)), &accounts);
+        assert!(client.get_channel(&url).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_read_channel_detects_the_format() {
+        let rss = r#"<?xml version="1.0"?>
+            <rss version="2.0"><channel><title>RSS</title><link>http://example.com/</link>
+            <description></description></channel></rss>"#;
+        let atom = r#"<?xml version="1.0"?>
+            <feed xmlns="http://www.w3.org/2005/Atom"><title>Atom</title><id>some-id</id>
+            <updated>2023-01-02T10:00:00Z</updated></feed>"#;
+        let json = r#"
+            { "version": "https://jsonfeed.org/version/1.1", "title": "JSON Feed", "items": [] }"#;
+
+        assert_eq!(read_channel(rss.as_bytes()).unwrap().title(), "RSS");
+        assert_eq!(read_channel(atom.as_bytes()).unwrap().title(), "Atom");
+        assert_eq!(read_channel(json.as_bytes()).unwrap().title(), "JSON Feed");
+        assert!(read_channel(b"<html></html>").is_err());
+    }
+}
@@ -1,6 +1,9 @@
 
+use chrono::{DateTime, FixedOffset};
 use rss::{extension::Extension, Item};
 
+use crate::config::{self, Visibility};
+use crate::error::Error;
 use crate::social;
 
 /// Rust representation of the Indieweb Tools RSS extension
@@ -12,15 +15,154 @@ pub struct IwtRssExtension {
     pub content_warning: Option<String>,
     /// Tags of the item
     pub tags: Vec<String>,
+    /// The item has been deleted, its syndicated copies are deleted too
+    pub deleted: bool,
+    /// The item isn't syndicated before this time
+    pub publish_at: Option<DateTime<FixedOffset>>,
+    /// Delays of `publish_at` per network or account
+    pub publish_offsets: Vec<IwtRssPublishOffset>,
+    /// Text published instead of the description
+    pub text: Option<String>,
+    /// Shorter text published instead of the description, when the post isn't a thread
+    pub summary: Option<String>,
+    /// Texts and summaries per network or account
+    pub overrides: Vec<IwtRssOverride>,
+    /// Visibility of the status, this is only used by Mastodon
+    pub visibility: Option<Visibility>,
+    /// ISO 639 language code of the post, this is only used by Mastodon
+    pub language: Option<String>,
+    /// The attachments are sensitive, this is only used by Mastodon
+    pub sensitive: Option<bool>,
 }
 
+impl IwtRssExtension {
+    /// When the item is due on the account, `publish_at` delayed by the most specific offset
+    /// matching the account
+    #[must_use]
+    pub fn scheduled_at(&self, account: &social::Account) -> Option<DateTime<FixedOffset>> {
+        let offset = self
+            .publish_offsets
+            .iter()
+            .filter(|offset| offset.target_network.matches(account))
+            .max_by_key(|offset| offset.target_network.account.is_some())
+            .map_or(0, |offset| offset.seconds);
+
+        self.publish_at
+            .map(|publish_at| publish_at + chrono::Duration::seconds(offset))
+    }
+
+    /// The text published on the account: the override of the account, of its network, the
+    /// global one or the description of the post. Threads prefer the text, single posts the
+    /// summary.
+    #[must_use]
+    pub fn text<'a>(&'a self, post: &'a Item, account: &social::Account, thread: bool) -> &'a str {
+        let pick = |text: &'a Option<String>, summary: &'a Option<String>| {
+            let (preferred, other) = if thread {
+                (text, summary)
+            } else {
+                (summary, text)
+            };
+            preferred.as_deref().or(other.as_deref())
+        };
+
+        let mut overrides = self
+            .overrides
+            .iter()
+            .filter(|scoped| scoped.target_network.matches(account))
+            .collect::<Vec<_>>();
+        overrides.sort_by_key(|scoped| scoped.target_network.account.is_none());
+
+        overrides
+            .iter()
+            .find_map(|scoped| pick(&scoped.text, &scoped.summary))
+            .or_else(|| pick(&self.text, &self.summary))
+            .or_else(|| post.description())
+            .unwrap_or_default()
+    }
+
+    /// The status replied to on the account, status ids are only known by their instance so only
+    /// the overrides of the account are considered
+    #[must_use]
+    pub fn in_reply_to_id(&self, account: &social::Account) -> Option<&str> {
+        self.overrides
+            .iter()
+            .filter(|scoped| {
+                scoped.target_network.account.is_some() && scoped.target_network.matches(account)
+            })
+            .find_map(|scoped| scoped.in_reply_to_id.as_deref())
+    }
+}
+
+/// A `network` or a `network:account` value of `iwt:targetNetwork`, the former targets every
+/// account of the network
 #[derive(Debug, PartialEq)]
 pub struct IwtRssTargetNetwork {
     pub network: social::Network,
+    pub account: Option<String>,
+}
+
+impl IwtRssTargetNetwork {
+    /// Parses `network` or `network:account`, unknown networks are `None`
+    fn parse(value: &str) -> Option<Self> {
+        let (network, account) = match value.split_once(':') {
+            Some((network, account)) => (network, Some(account.to_string())),
+            None => (value, None),
+        };
+        let network = match network {
+            "twitter" => social::Network::Twitter,
+            "mastodon" => social::Network::Mastodon,
+            "bluesky" => social::Network::Bluesky,
+            _ => return None,
+        };
+
+        Some(Self { network, account })
+    }
+
+    #[must_use]
+    pub fn matches(&self, account: &social::Account) -> bool {
+        self.network == account.network
+            && self
+                .account
+                .as_ref()
+                .is_none_or(|name| *name == account.name)
+    }
+}
+
+/// A `network=seconds` or a `network:account=seconds` value of `iwt:publishOffset`
+#[derive(Debug, PartialEq)]
+pub struct IwtRssPublishOffset {
+    pub target_network: IwtRssTargetNetwork,
+    pub seconds: i64,
+}
+
+impl IwtRssPublishOffset {
+    fn parse(value: &str) -> Option<Self> {
+        let (target_network, seconds) = value.split_once('=')?;
+
+        Some(Self {
+            target_network: IwtRssTargetNetwork::parse(target_network.trim())?,
+            seconds: seconds.trim().parse().ok()?,
+        })
+    }
+}
+
+/// An `iwt:override` of the text and the summary for the network or account of its
+/// `iwt:targetNetwork`, and of the status replied to for an account
+#[derive(Debug, PartialEq)]
+pub struct IwtRssOverride {
+    pub target_network: IwtRssTargetNetwork,
+    pub text: Option<String>,
+    pub summary: Option<String>,
+    pub in_reply_to_id: Option<String>,
 }
 
 pub trait RssItemExt {
+    /// The extension of the item, `None` if it has none or if it can't be decoded
     fn get_iwt_extension(&self) -> Option<IwtRssExtension>;
+
+    /// Fails with `Error::Item` if the extension can't be decoded, i.e. an invalid `publishAt`
+    /// mustn't publish the item right away
+    fn decode_iwt_extension(&self) -> Result<Option<IwtRssExtension>, Error>;
 }
 
 fn get_children<'a>(ext: &'a Extension, key: &str) -> Vec<&'a Extension> {
@@ -43,6 +185,10 @@ fn get_value<'a>(ext: &'a Extension, key: &str) -> Option<&'a str> {
 
 impl RssItemExt for Item {
     fn get_iwt_extension(&self) -> Option<IwtRssExtension> {
+        self.decode_iwt_extension().ok().flatten()
+    }
+
+    fn decode_iwt_extension(&self) -> Result<Option<IwtRssExtension>, Error> {
         // todo!()
         self.extensions()
             .get(&"iwt".to_string())
@@ -52,35 +198,137 @@ impl RssItemExt for Item {
                 let target_networks = get_key(iwt_extension, "targetNetworks")
                     .iter()
                     .flat_map(|target_networks| get_children(target_networks, "targetNetwork"))
-                    .map(|target_network| {
-                        let target_network_name = target_network.value().unwrap();
-                        match target_network_name {
-                            "twitter" => IwtRssTargetNetwork {
-                                network: social::Network::Twitter,
-                            },
-                            "mastodon" => IwtRssTargetNetwork {
-                                network: social::Network::Mastodon,
-                            },
-                            _ => panic!("Unknown netowrk: {target_network_name}"),
+                    .filter_map(|target_network| {
+                        let value = target_network.value()?;
+                        let target_network = IwtRssTargetNetwork::parse(value);
+                        if target_network.is_none() {
+                            log::warn!(
+                                "{} |> Unknown network is skipped: {value}",
+                                self.link().unwrap_or_default()
+                            );
                         }
+                        target_network
                     })
                     .collect::<Vec<_>>();
 
                 let tags = get_children(iwt_extension, "tags")
                     .iter()
                     .flat_map(|tags| get_children(tags, "tag"))
-                    .map(|tag| tag.value().unwrap().to_string())
+                    .filter_map(|tag| tag.value().map(str::to_string))
                     .collect();
 
                 let content_warning =
                     get_value(iwt_extension, "contentWarning").map(std::borrow::ToOwned::to_owned);
 
-                IwtRssExtension {
+                let deleted = get_value(iwt_extension, "deleted")
+                    .is_some_and(|deleted| deleted.trim().eq_ignore_ascii_case("true"));
+
+                let publish_at = match get_value(iwt_extension, "publishAt") {
+                    Some(publish_at) => Some(
+                        DateTime::parse_from_rfc3339(publish_at.trim()).map_err(|err| {
+                            Error::item(
+                                self.guid().map(rss::Guid::value),
+                                &format!("Invalid publishAt {publish_at}: {err}"),
+                            )
+                        })?,
+                    ),
+                    None => None,
+                };
+
+                let publish_offsets = get_children(iwt_extension, "publishOffsets")
+                    .iter()
+                    .flat_map(|offsets| get_children(offsets, "publishOffset"))
+                    .filter_map(|offset| {
+                        let value = offset.value()?;
+                        let offset = IwtRssPublishOffset::parse(value);
+                        if offset.is_none() {
+                            log::warn!(
+                                "{} |> Invalid publishOffset is skipped: {value}",
+                                self.link().unwrap_or_default()
+                            );
+                        }
+                        offset
+                    })
+                    .collect();
+
+                let text = get_value(iwt_extension, "text").map(str::to_string);
+                let summary = get_value(iwt_extension, "summary").map(str::to_string);
+
+                let overrides = get_children(iwt_extension, "overrides")
+                    .iter()
+                    .flat_map(|overrides| get_children(overrides, "override"))
+                    .filter_map(|scoped| {
+                        let value = get_value(scoped, "targetNetwork")?;
+                        let target_network = IwtRssTargetNetwork::parse(value);
+                        if target_network.is_none() {
+                            log::warn!(
+                                "{} |> Override of an unknown network is skipped: {value}",
+                                self.link().unwrap_or_default()
+                            );
+                        }
+                        let target_network = target_network?;
+                        let in_reply_to_id = get_value(scoped, "inReplyToId").map(str::to_string);
+                        if in_reply_to_id.is_some() && target_network.account.is_none() {
+                            log::warn!(
+                                "{} |> inReplyToId of a network is ignored, it needs an account: {value}",
+                                self.link().unwrap_or_default()
+                            );
+                        }
+                        Some(IwtRssOverride {
+                            target_network,
+                            text: get_value(scoped, "text").map(str::to_string),
+                            summary: get_value(scoped, "summary").map(str::to_string),
+                            in_reply_to_id,
+                        })
+                    })
+                    .collect();
+
+                let visibility = get_value(iwt_extension, "visibility").and_then(|visibility| {
+                    let visibility = visibility.trim().parse::<Visibility>();
+                    if let Err(err) = &visibility {
+                        log::warn!("{} |> {err}", self.link().unwrap_or_default());
+                    }
+                    visibility.ok()
+                });
+
+                let language = get_value(iwt_extension, "language").and_then(|language| {
+                    let language = language.trim();
+                    if !config::is_language_code(language) {
+                        log::warn!(
+                            "{} |> Language which isn't an ISO 639-1 code is ignored: {language}",
+                            self.link().unwrap_or_default()
+                        );
+                        return None;
+                    }
+                    Some(language.to_string())
+                });
+
+                let sensitive = get_value(iwt_extension, "sensitive")
+                    .map(|sensitive| sensitive.trim().eq_ignore_ascii_case("true"));
+
+                if get_value(iwt_extension, "inReplyToId").is_some() {
+                    log::warn!(
+                        "{} |> inReplyToId is ignored outside of the override of an account",
+                        self.link().unwrap_or_default()
+                    );
+                }
+
+                Ok(IwtRssExtension {
                     target_networks,
                     content_warning,
                     tags,
-                }
+                    deleted,
+                    publish_at,
+                    publish_offsets,
+                    text,
+                    summary,
+                    overrides,
+                    visibility,
+                    language,
+                    sensitive,
+                })
             })
+            .transpose()
     }
 }
 
@@ -90,8 +338,6 @@ pub mod stubs {
 
     use rss::extension::{Extension, ExtensionBuilder, ExtensionMap};
 
-    use crate::social;
-
     fn create_extension(name: &str, value: &str) -> Extension {
         ExtensionBuilder::default()
             .name(name.to_string())
@@ -115,8 +361,8 @@ pub mod stubs {
             .build()
     }
 
-    fn create_iwt_extension(
-        target_networks: &[social::Network],
+    fn create_iwt_extension<T: ToString>(
+        target_networks: &[T],
         content_warning: Option<String>,
         tags: &[&str],
     ) -> Extension {
@@ -159,8 +405,9 @@ pub mod stubs {
 
         create_extension_with_children("iwt:extension", children)
     }
-    pub fn create_iwt_extension_map(
-        target_networks: &[social::Network],
+    /// Target networks are either `social::Network`s or `network:account` strings
+    pub fn create_iwt_extension_map<T: ToString>(
+        target_networks: &[T],
         content_warning: Option<String>,
         tags: &[&str],
     ) -> ExtensionMap {
@@ -175,18 +422,70 @@ pub mod stubs {
 
         extensions
     }
+
+    /// Adds the text and the summary to the extension, globally or as an override of the target
+    /// network
+    pub fn with_text(
+        mut extensions: ExtensionMap,
+        target_network: Option<&str>,
+        text: Option<&str>,
+        summary: Option<&str>,
+    ) -> ExtensionMap {
+        let iwt_extension = &mut extensions
+            .get_mut("iwt")
+            .unwrap()
+            .get_mut("extension")
+            .unwrap()[0];
+
+        let mut children = vec![];
+        if let Some(text) = text {
+            children.push(("text", vec![create_extension("iwt:text", text)]));
+        }
+        if let Some(summary) = summary {
+            children.push(("summary", vec![create_extension("iwt:summary", summary)]));
+        }
+
+        match target_network {
+            Some(target_network) => {
+                children.push((
+                    "targetNetwork",
+                    vec![create_extension("iwt:targetNetwork", target_network)],
+                ));
+                iwt_extension
+                    .children
+                    .entry(String::from("overrides"))
+                    .or_insert_with(|| {
+                        vec![create_extension_with_children("iwt:overrides", vec![])]
+                    })[0]
+                    .children
+                    .entry(String::from("override"))
+                    .or_default()
+                    .push(create_extension_with_children("iwt:override", children));
+            }
+            None => {
+                for (key, exts) in children {
+                    iwt_extension.children.insert(key.to_string(), exts);
+                }
+            }
+        }
+
+        extensions
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
+        config::Visibility,
         cross_publisher::rss_item_ext::{IwtRssExtension, IwtRssTargetNetwork},
-        social,
+        social::{self, Account},
     };
-    use rss::Item;
+    use rss::{GuidBuilder, Item};
+    use serde_json::json;
 
-    use super::stubs::create_iwt_extension_map;
+    use super::stubs::{create_iwt_extension_map, with_text};
     use super::RssItemExt;
+    use crate::cross_publisher::json_feed::extension_map_from_json;
 
     #[test]
     fn test_get_iwt_extension_should_return_none_when_extension_is_not_available() {
@@ -201,7 +500,7 @@ mod test {
     fn test_get_iwt_extension_should_return_the_extension_with_zero_target_networks_if_no_children()
     {
         let item = Item {
-            extensions: create_iwt_extension_map(&[], None, &Vec::new()),
+            extensions: create_iwt_extension_map::<social::Network>(&[], None, &Vec::new()),
             ..Default::default()
         };
         let extension = item.get_iwt_extension();
@@ -211,7 +510,16 @@ mod test {
             Some(IwtRssExtension {
                 target_networks: vec![],
                 content_warning: None,
-                tags: Vec::new()
+                tags: Vec::new(),
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
             })
         );
     }
@@ -233,17 +541,122 @@ mod test {
             Some(IwtRssExtension {
                 target_networks: vec![
                     IwtRssTargetNetwork {
-                        network: social::Network::Mastodon
+                        network: social::Network::Mastodon,
+                        account: None
                     },
                     IwtRssTargetNetwork {
-                        network: social::Network::Twitter
+                        network: social::Network::Twitter,
+                        account: None
                     },
                 ],
                 content_warning: None,
-                tags: Vec::new()
+                tags: Vec::new(),
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
             })
         );
     }
+
+    #[test]
+    fn test_get_iwt_extension_should_return_the_extension_with_bluesky_target_network() {
+        let item = Item {
+            extensions: create_iwt_extension_map(&[social::Network::Bluesky], None, &Vec::new()),
+            ..Default::default()
+        };
+        let extension = item.get_iwt_extension();
+
+        assert_eq!(
+            extension,
+            Some(IwtRssExtension {
+                target_networks: vec![IwtRssTargetNetwork {
+                    network: social::Network::Bluesky,
+                    account: None
+                },],
+                content_warning: None,
+                tags: Vec::new(),
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_iwt_extension_should_return_the_extension_with_target_accounts() {
+        let item = Item {
+            extensions: create_iwt_extension_map(
+                &["mastodon:personal", "twitter"],
+                None,
+                &Vec::new(),
+            ),
+            ..Default::default()
+        };
+        let extension = item.get_iwt_extension();
+
+        assert_eq!(
+            extension,
+            Some(IwtRssExtension {
+                target_networks: vec![
+                    IwtRssTargetNetwork {
+                        network: social::Network::Mastodon,
+                        account: Some("personal".to_string())
+                    },
+                    IwtRssTargetNetwork {
+                        network: social::Network::Twitter,
+                        account: None
+                    },
+                ],
+                content_warning: None,
+                tags: Vec::new(),
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_target_network_matches_every_account_of_the_network() {
+        let target_network = IwtRssTargetNetwork {
+            network: social::Network::Mastodon,
+            account: None,
+        };
+
+        assert!(target_network.matches(&Account::new(social::Network::Mastodon, "personal")));
+        assert!(target_network.matches(&Account::new(social::Network::Mastodon, "project")));
+        assert!(!target_network.matches(&Account::from(social::Network::Twitter)));
+    }
+
+    #[test]
+    fn test_target_network_with_account_matches_only_the_account() {
+        let target_network = IwtRssTargetNetwork {
+            network: social::Network::Mastodon,
+            account: Some("personal".to_string()),
+        };
+
+        assert!(target_network.matches(&Account::new(social::Network::Mastodon, "personal")));
+        assert!(!target_network.matches(&Account::new(social::Network::Mastodon, "project")));
+    }
+
     #[test]
     fn test_get_iwt_extension_should_return_the_extension_with_content_warning() {
         let item = Item {
@@ -260,10 +673,20 @@ mod test {
             extension,
             Some(IwtRssExtension {
                 target_networks: vec![IwtRssTargetNetwork {
-                    network: social::Network::Mastodon
+                    network: social::Network::Mastodon,
+                    account: None
                 },],
                 content_warning: Some("This is a content_warning".to_string()),
-                tags: Vec::new()
+                tags: Vec::new(),
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
             })
         );
     }
@@ -284,11 +707,211 @@ mod test {
             extension,
             Some(IwtRssExtension {
                 target_networks: vec![IwtRssTargetNetwork {
-                    network: social::Network::Mastodon
+                    network: social::Network::Mastodon,
+                    account: None
                 },],
                 content_warning: Some("This is a content_warning".to_string()),
-                tags: vec!["tag-1".to_string(), "tag-2".to_string()]
+                tags: vec!["tag-1".to_string(), "tag-2".to_string()],
+                deleted: false,
+                publish_at: None,
+                publish_offsets: vec![],
+                text: None,
+                summary: None,
+                overrides: vec![],
+                visibility: None,
+                language: None,
+                sensitive: None,
             })
         );
     }
+
+    #[test]
+    fn test_get_iwt_extension_should_return_the_extension_marked_deleted() {
+        let item = Item {
+            extensions: extension_map_from_json(&json!({
+                "targetNetworks": ["mastodon"],
+                "deleted": true,
+            })),
+            ..Default::default()
+        };
+
+        assert!(item.get_iwt_extension().unwrap().deleted);
+    }
+
+    #[test]
+    fn test_get_iwt_extension_should_return_the_schedule_delayed_per_network_and_account() {
+        let item = Item {
+            extensions: extension_map_from_json(&json!({
+                "targetNetworks": ["mastodon", "twitter"],
+                "publishAt": "2024-03-01T09:00:00+01:00",
+                "publishOffsets": ["mastodon=3600", "mastodon:personal=7200", "myspace=60"],
+            })),
+            ..Default::default()
+        };
+        let extension = item.get_iwt_extension().unwrap();
+
+        let scheduled_at =
+            |account: &Account| extension.scheduled_at(account).unwrap().to_rfc3339();
+        assert_eq!(
+            scheduled_at(&Account::from(social::Network::Twitter)),
+            "2024-03-01T09:00:00+01:00"
+        );
+        assert_eq!(
+            scheduled_at(&Account::new(social::Network::Mastodon, "project")),
+            "2024-03-01T10:00:00+01:00"
+        );
+        assert_eq!(
+            scheduled_at(&Account::new(social::Network::Mastodon, "personal")),
+            "2024-03-01T11:00:00+01:00"
+        );
+    }
+
+    #[test]
+    fn test_decode_iwt_extension_should_fail_on_an_invalid_schedule() {
+        let item = Item {
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            extensions: extension_map_from_json(&json!({
+                "targetNetworks": ["mastodon"],
+                "publishAt": "tomorrow morning",
+            })),
+            ..Default::default()
+        };
+
+        let err = item.decode_iwt_extension().unwrap_err();
+
+        assert_eq!(err.kind(), "item");
+        assert!(err.to_string().contains("post-1"));
+        assert_eq!(item.get_iwt_extension(), None);
+    }
+
+    #[test]
+    fn test_get_iwt_extension_should_return_the_texts_overridden_per_network_and_account() {
+        let extensions = create_iwt_extension_map(&["mastodon", "twitter"], None, &Vec::new());
+        let extensions = with_text(extensions, None, Some("Global text"), None);
+        let extensions = with_text(extensions, Some("twitter"), None, Some("Short tweet"));
+        let extensions = with_text(
+            extensions,
+            Some("mastodon:personal"),
+            Some("Personal toot"),
+            Some("Personal summary"),
+        );
+        let item = Item {
+            description: Some(String::from("Description")),
+            extensions,
+            ..Default::default()
+        };
+        let extension = item.get_iwt_extension().unwrap();
+
+        assert_eq!(extension.text.as_deref(), Some("Global text"));
+        assert_eq!(extension.overrides.len(), 2);
+        let text = |account: &Account, thread| extension.text(&item, account, thread);
+        assert_eq!(
+            text(&Account::from(social::Network::Twitter), false),
+            "Short tweet"
+        );
+        assert_eq!(
+            text(&Account::from(social::Network::Twitter), true),
+            "Short tweet"
+        );
+        assert_eq!(
+            text(&Account::new(social::Network::Mastodon, "personal"), true),
+            "Personal toot"
+        );
+        assert_eq!(
+            text(&Account::new(social::Network::Mastodon, "personal"), false),
+            "Personal summary"
+        );
+        assert_eq!(
+            text(&Account::new(social::Network::Mastodon, "project"), false),
+            "Global text"
+        );
+
+        let item = Item {
+            description: Some(String::from("Description")),
+            extensions: create_iwt_extension_map(&["mastodon"], None, &Vec::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            item.get_iwt_extension().unwrap().text(
+                &item,
+                &Account::from(social::Network::Mastodon),
+                false
+            ),
+            "Description"
+        );
+    }
+
+    #[test]
+    fn test_get_iwt_extension_should_return_the_status_options() {
+        let item = Item {
+            extensions: extension_map_from_json(&json!({
+                "targetNetworks": ["mastodon"],
+                "visibility": "unlisted",
+                "language": "de",
+                "sensitive": true,
+                "overrides": [
+                    { "targetNetwork": "mastodon:personal", "inReplyToId": "100" },
+                ],
+            })),
+            ..Default::default()
+        };
+        let extension = item.get_iwt_extension().unwrap();
+
+        assert_eq!(
+            (
+                extension.visibility,
+                extension.language.as_deref(),
+                extension.sensitive,
+                extension.in_reply_to_id(&Account::new(social::Network::Mastodon, "personal"))
+            ),
+            (
+                Some(Visibility::Unlisted),
+                Some("de"),
+                Some(true),
+                Some("100")
+            )
+        );
+    }
+
+    #[test]
+    fn test_in_reply_to_id_is_only_scoped_per_account() {
+        let item = Item {
+            extensions: extension_map_from_json(&json!({
+                "targetNetworks": ["mastodon"],
+                "language": "german",
+                "inReplyToId": "1",
+                "overrides": [
+                    { "targetNetwork": "mastodon", "inReplyToId": "2" },
+                    { "targetNetwork": "mastodon:personal", "inReplyToId": "3" },
+                ],
+            })),
+            ..Default::default()
+        };
+        let extension = item.get_iwt_extension().unwrap();
+
+        assert_eq!(
+            (
+                extension.in_reply_to_id(&Account::new(social::Network::Mastodon, "personal")),
+                extension.in_reply_to_id(&Account::new(social::Network::Mastodon, "project")),
+                extension.language.as_deref()
+            ),
+            (Some("3"), None, None)
+        );
+    }
+
+    #[test]
+    fn test_get_iwt_extension_should_skip_unknown_target_networks() {
+        let item = Item {
+            extensions: create_iwt_extension_map(&["myspace", "mastodon"], None, &Vec::new()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            item.get_iwt_extension().unwrap().target_networks,
+            vec![IwtRssTargetNetwork {
+                network: social::Network::Mastodon,
+                account: None
+            }]
+        );
+    }
 }
\ No newline at end of file
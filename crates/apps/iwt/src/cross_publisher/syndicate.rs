@@ -1,12 +1,19 @@
 
 use super::rss;
-use ::rss::Channel;
-use futures::{Future, FutureExt, StreamExt, TryFutureExt};
-
-use super::rss_item_ext::RssItemExt;
+use ::rss::{Channel, Guid, Item};
+use chrono::{DateTime, FixedOffset};
+use futures::{Future, StreamExt};
+
+use super::outbox::Outbox;
+use super::rate_limit::RateLimited;
+use super::report::{Entry, Outcome, Report};
+use super::rss_item_ext::{IwtRssExtension, RssItemExt};
 use super::syndicated_post;
-use super::target::Target;
-use crate::{Config, IwtError};
+use super::target::{PartiallyPublished, Rejected, Target};
+use super::webmention;
+use crate::error::Error;
+use crate::social::Account;
+use crate::Config;
 
 /// Orchestrates syndication
 pub async fn syndicate<R, S>(
@@ -14,172 +21,860 @@ pub async fn syndicate<R, S>(
     rss_client: &R,
     targets: &[Box<dyn Target>],
     storage: &S,
+    outbox: Option<&Outbox>,
+    webmention: Option<&webmention::Sender>,
     dry_run: bool,
-) -> Result<(), Box<dyn std::error::Error>>
+) -> Report
 where
     R: rss::Client,
     S: syndicated_post::Storage,
 {
     log::debug!("Received config: {:?}", config);
-    run_and_collect(config.rss.urls.iter(), |url| {
-        rss_client
-            .get_channel(url)
-            .and_then(|channel| syndycate_channel(channel, targets, storage, dry_run))
+    syndicate_feeds(
+        &config.rss.urls,
+        rss_client,
+        targets,
+        storage,
+        outbox,
+        webmention,
+        dry_run,
+    )
+    .await
+}
+
+/// Syndicates the feeds loaded by the client, i.e. the `h-feed`s of HTML pages
+pub async fn syndicate_feeds<R, S>(
+    urls: &[String],
+    client: &R,
+    targets: &[Box<dyn Target>],
+    storage: &S,
+    outbox: Option<&Outbox>,
+    webmention: Option<&webmention::Sender>,
+    dry_run: bool,
+) -> Report
+where
+    R: rss::Client,
+    S: syndicated_post::Storage,
+{
+    run_all(urls.iter(), |url| async move {
+        let feed_error = |source| Error::Feed {
+            url: url.clone(),
+            source,
+        };
+
+        let channel = match client.get_channel(url).await {
+            Ok(Some(channel)) => channel,
+            Ok(None) => return vec![entry(Some(url), None, None, Ok(Outcome::Unchanged))],
+            Err(source) => return vec![entry(Some(url), None, None, Err(feed_error(source)))],
+        };
+
+        let mut report =
+            syndycate_channel(url, channel, targets, storage, outbox, webmention, dry_run).await;
+        // The feed is syndicated again on the next run, until every item has been syndicated
+        if !dry_run && report.is_success() {
+            if let Err(source) = client.feed_syndicated(url) {
+                report
+                    .entries
+                    .push(entry(Some(url), None, None, Err(feed_error(source))));
+            }
+        }
+        report.entries
     })
     .await
+    .into_iter()
+    .flatten()
+    .collect()
 }
 
-/// Syndicates a single channel
-async fn syndycate_channel<S: syndicated_post::Storage>(
+/// Syndicates a single channel, then sends the Webmentions of the syndicated posts
+pub async fn syndycate_channel<S: syndicated_post::Storage>(
+    feed: &str,
     channel: Channel,
     targets: &[Box<dyn Target>],
     storage: &S,
+    outbox: Option<&Outbox>,
+    webmention: Option<&webmention::Sender>,
     dry_run: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    run_and_collect(targets.iter(), |target| {
-        run_and_collect(channel.items.iter(), |post| {
-            log::info!(
-                "{} |> Syndicating post to {}",
-                post.link().unwrap(),
-                target.network().to_string()
-            );
-            let stored = storage.find(&post.guid.as_ref().unwrap().value, &target.network());
+) -> Report {
+    let mut report = syndicate_items(feed, &channel, targets, storage, outbox, dry_run).await;
+
+    match webmention {
+        Some(webmention) if !dry_run => {
+            let failures = run_all(channel.items().iter(), |post| async move {
+                let guid = post.guid()?;
+                let is_syndicated = targets.iter().any(|target| {
+                    matches!(
+                        storage.find(guid.value(), target.account()),
+                        Ok(Some(syndicated)) if !syndicated.retracted
+                    )
+                });
+                if !is_syndicated {
+                    return None;
+                }
 
-            // println!("Post: {:?}", post);
+                let err = webmention.send_all(post).await.err()?;
+                Some(entry(Some(feed), Some(guid.value()), None, Err(err)))
+            })
+            .await;
+            report.entries.extend(failures.into_iter().flatten());
+        }
+        _ => {}
+    };
 
-            async {
-                match stored {
-                    Ok(None) => {
-                        log::info!(
-                            "{} |> Post not found in DB, syndycating to {}",
-                            post.link().unwrap(),
-                            target.network().to_string()
-                        );
+    report
+}
 
-                        if let Some(extension) = post.get_iwt_extension() {
-                            if extension
-                                .target_networks
-                                .iter()
-                                .any(|tn| tn.network == target.network())
-                            {
-                                if dry_run {
-                                    log::info!(
-                                        "{} |> Publishing to {} is skipped due to --dry-run",
-                                        post.link().unwrap(),
-                                        target.network().to_string()
-                                    );
-                                    Ok(())
-                                } else {
-                                    log::info!(
-                                        "{} |> Publishing to {}",
-                                        post.link().unwrap(),
-                                        target.network().to_string()
-                                    );
-                                    let result = target
-                                        .publish(post, &extension)
-                                        .map(|result| {
-                                            result.and_then(|syndicated| {
-                                                storage.store(syndicated).map_err(|err| {
-                                                    Box::new(err) as Box<dyn std::error::Error>
-                                                })
-                                            })
-                                        })
-                                        .await;
-                                    log::info!(
-                                        "{} |> Published to {}",
-                                        post.link().unwrap(),
-                                        target.network().to_string()
-                                    );
-
-                                    result
-                                }
-                            } else {
-                                log::info!(
-                                    "{} |> Not configured to be syndicated to {}",
-                                    post.link().unwrap(),
-                                    target.network().to_string()
-                                );
-                                Ok(())
-                            }
-                        } else {
-                            Err(
-                                Box::new(IwtError::new("Rss Item doesn't have an IWT extension"))
-                                    as Box<dyn std::error::Error>,
-                            )
-                        }
-                    }
-                    Ok(Some(_)) => {
+async fn syndicate_items<S: syndicated_post::Storage>(
+    feed: &str,
+    channel: &Channel,
+    targets: &[Box<dyn Target>],
+    storage: &S,
+    outbox: Option<&Outbox>,
+    dry_run: bool,
+) -> Report {
+    run_all(targets.iter(), |target| {
+        run_all(channel.items.iter(), move |post| async move {
+            let result = syndicate_item(post, target.as_ref(), storage, outbox, dry_run).await;
+            entry(
+                Some(feed),
+                post.guid().map(Guid::value),
+                Some(target.account()),
+                result,
+            )
+        })
+    })
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Syndicates the post to the target. A malformed post fails on its own, the other posts of the
+/// feed are syndicated regardless.
+async fn syndicate_item<S: syndicated_post::Storage>(
+    post: &Item,
+    target: &dyn Target,
+    storage: &S,
+    outbox: Option<&Outbox>,
+    dry_run: bool,
+) -> Result<Outcome, Error> {
+    let (guid, link) = identify(post)?;
+    log::info!("{} |> Syndicating post to {}", link, target.account());
+
+    let deleted = post
+        .get_iwt_extension()
+        .is_some_and(|extension| extension.deleted);
+
+    // println!("Post: {:?}", post);
+
+    match storage.find(guid, target.account())? {
+        Some(syndicated) if syndicated.retracted => {
+            log::info!("{} |> Has been retracted from {}", link, target.account());
+            Ok(Outcome::Skipped {
+                reason: String::from("retracted"),
+            })
+        }
+        Some(syndicated) if deleted => retract(&syndicated, target, storage, dry_run).await,
+        None if deleted => {
+            log::info!(
+                "{} |> Has been deleted, not syndicating to {}",
+                link,
+                target.account()
+            );
+            discard(guid, target, outbox, dry_run)?;
+            Ok(Outcome::Skipped {
+                reason: String::from("deleted"),
+            })
+        }
+        None => {
+            log::info!(
+                "{} |> Post not found in DB, syndycating to {}",
+                link,
+                target.account()
+            );
+
+            if let Some(extension) = post.decode_iwt_extension()? {
+                if extension
+                    .target_networks
+                    .iter()
+                    .any(|tn| tn.matches(target.account()))
+                {
+                    let scheduled_at = extension
+                        .scheduled_at(target.account())
+                        .filter(|scheduled_at| *scheduled_at > chrono::Utc::now());
+                    if let Some(scheduled_at) = scheduled_at {
+                        schedule(post, scheduled_at, target, outbox, dry_run)
+                    } else if dry_run {
                         log::info!(
-                            "{} |> Has been already syndicated to {}",
-                            post.link().unwrap(),
-                            target.network().to_string()
+                            "{} |> Publishing to {} is skipped due to --dry-run",
+                            link,
+                            target.account()
                         );
-                        Ok(())
+                        Ok(Outcome::DryRun {
+                            preview: Some(target.preview(post, &extension)),
+                        })
+                    } else {
+                        publish(post, &extension, target, storage, outbox).await
                     }
-                    Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+                } else {
+                    log::info!(
+                        "{} |> Not configured to be syndicated to {}",
+                        link,
+                        target.account()
+                    );
+                    discard(guid, target, outbox, dry_run)?;
+                    Ok(Outcome::NotTargeted)
                 }
+            } else {
+                Err(Error::item(
+                    Some(guid),
+                    "Rss Item doesn't have an IWT extension",
+                ))
             }
-        })
+        }
+        Some(syndicated) if target.propagates_edits() => {
+            propagate_edit(syndicated, post, target, storage, dry_run).await
+        }
+        Some(_) => {
+            log::info!(
+                "{} |> Has been already syndicated to {}",
+                link,
+                target.account()
+            );
+            Ok(Outcome::AlreadySyndicated)
+        }
+    }
+}
+
+/// The guid and the link of the post, the posts without them can't be syndicated
+fn identify(post: &Item) -> Result<(&str, &str), Error> {
+    match (post.guid(), post.link()) {
+        (Some(guid), Some(link)) => Ok((guid.value(), link)),
+        (Some(guid), None) => Err(Error::item(
+            Some(guid.value()),
+            "Rss Item doesn't have a link",
+        )),
+        (None, _) => Err(Error::item(None, "Rss Item doesn't have a guid")),
+    }
+}
+
+/// Drops the publish of the post from the outbox, i.e. of a scheduled post which has been deleted
+/// or isn't targeted anymore
+fn discard(
+    guid: &str,
+    target: &dyn Target,
+    outbox: Option<&Outbox>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    match outbox {
+        Some(outbox) if !dry_run => Ok(outbox.remove(guid, target.account())?),
+        _ => Ok(()),
+    }
+}
+
+/// Defers the post which isn't due yet, it's kept in the outbox and published by the first run
+/// after its time, even if the feed is unchanged by then
+fn schedule(
+    post: &Item,
+    scheduled_at: DateTime<FixedOffset>,
+    target: &dyn Target,
+    outbox: Option<&Outbox>,
+    dry_run: bool,
+) -> Result<Outcome, Error> {
+    let (_, link) = identify(post)?;
+    log::info!(
+        "{} |> Publishing to {} is scheduled at {}",
+        link,
+        target.account(),
+        scheduled_at
+    );
+
+    match outbox {
+        Some(outbox) if !dry_run => {
+            outbox.schedule(post, target.account(), scheduled_at.timestamp())?;
+        }
+        _ => {}
+    }
+
+    Ok(Outcome::Scheduled {
+        publish_at: scheduled_at.to_rfc3339(),
+    })
+}
+
+/// Publishes the post and stores the syndicated copy. A failed publish is recorded in the outbox,
+/// it isn't attempted again until its retry is due.
+async fn publish<S: syndicated_post::Storage>(
+    post: &Item,
+    extension: &IwtRssExtension,
+    target: &dyn Target,
+    storage: &S,
+    outbox: Option<&Outbox>,
+) -> Result<Outcome, Error> {
+    let (guid, link) = identify(post)?;
+    let now = chrono::Utc::now().timestamp();
+
+    let mut parts = vec![];
+    let mut rest = None;
+    if let Some(outbox) = outbox {
+        match outbox.find(guid, target.account())? {
+            Some(failed) if failed.dead_lettered => {
+                log::warn!(
+                    "{} |> Publishing to {} was given up after {} attempts, last error: {}",
+                    link,
+                    target.account(),
+                    failed.attempts,
+                    failed.error
+                );
+                return Ok(Outcome::Skipped {
+                    reason: format!("given up after {} attempts", failed.attempts),
+                });
+            }
+            // A scheduled post has no error, it's published once it's due according to the feed,
+            // it may have been moved earlier
+            Some(failed) if !failed.error.is_empty() && failed.next_attempt_at > now => {
+                log::info!(
+                    "{} |> Publishing to {} is retried in {}s",
+                    link,
+                    target.account(),
+                    failed.next_attempt_at - now
+                );
+                return Ok(Outcome::Skipped {
+                    reason: format!("retried in {}s", failed.next_attempt_at - now),
+                });
+            }
+            Some(failed) => {
+                parts = failed.published;
+                rest = failed.rest;
+            }
+            None => {}
+        }
+    }
+
+    // A publish stays in flight until its copy is stored, i.e. if the run is interrupted or the
+    // response of the network is lost
+    let in_flight = match outbox {
+        Some(outbox) => {
+            let in_flight = outbox.is_in_flight(guid, target.account())?;
+            outbox.mark_in_flight(post, target.account())?;
+            in_flight
+        }
+        None => false,
+    };
+    let reconciled = if in_flight {
+        log::warn!(
+            "{} |> Publishing to {} has been interrupted, looking for the published copy",
+            link,
+            target.account()
+        );
+        target.reconcile(post, extension).await
+    } else {
+        Ok(None)
+    };
+
+    let published = match reconciled {
+        Ok(Some(syndicated)) => Ok((syndicated, Outcome::Reconciled)),
+        Ok(None) if parts.is_empty() => {
+            log::info!("{} |> Publishing to {}", link, target.account());
+            target
+                .publish(post, extension)
+                .await
+                .map(|syndicated| (syndicated, Outcome::Published))
+        }
+        Ok(None) => {
+            log::info!(
+                "{} |> Resuming the thread on {} after {} published parts",
+                link,
+                target.account(),
+                parts.len()
+            );
+            target
+                .resume(post, extension, &parts, rest.as_deref())
+                .await
+                .map(|syndicated| (syndicated, Outcome::Published))
+        }
+        Err(err) => Err(err),
+    };
+
+    match published {
+        Ok((syndicated, outcome)) => {
+            storage.store(syndicated)?;
+            if let Some(outbox) = outbox {
+                outbox.remove(guid, target.account())?;
+            }
+            log::info!(
+                "{} |> Published to {} ({})",
+                link,
+                target.account(),
+                outcome
+            );
+            Ok(outcome)
+        }
+        Err(err) => {
+            // The published parts of a thread are kept, the next attempt resumes after them
+            let partially_published = err.downcast_ref::<PartiallyPublished>();
+            if let Some(partially_published) = partially_published {
+                parts.clone_from(&partially_published.ids);
+                rest.clone_from(&partially_published.rest);
+            }
+            let cause = partially_published.map_or(err.as_ref(), |partially_published| {
+                partially_published.source.as_ref()
+            });
+            // Nothing has been published, the next attempt doesn't have to look for the copy
+            if let (Some(outbox), true) = (outbox, has_definitely_failed(cause)) {
+                outbox.clear_in_flight(guid, target.account())?;
+            }
+            let rate_limited = cause.downcast_ref::<RateLimited>();
+
+            if let Some(rate_limited) = rate_limited {
+                log::warn!(
+                    "{} |> Publishing to {} is deferred: {}",
+                    link,
+                    target.account(),
+                    rate_limited
+                );
+                if let Some(outbox) = outbox {
+                    outbox.defer(
+                        post,
+                        target.account(),
+                        &rate_limited.to_string(),
+                        rate_limited.until.timestamp(),
+                    )?;
+                    outbox.keep_published(guid, target.account(), &parts, rest.as_deref())?;
+                }
+                return Ok(Outcome::Skipped {
+                    reason: format!("rate limited until {}", rate_limited.until.to_rfc3339()),
+                });
+            }
+            if let Some(outbox) = outbox {
+                let failed =
+                    outbox.record_failure(post, target.account(), &err.to_string(), now)?;
+                outbox.keep_published(guid, target.account(), &parts, rest.as_deref())?;
+                if failed.dead_lettered {
+                    log::error!(
+                        "{} |> Publishing to {} failed {} times, giving up",
+                        link,
+                        target.account(),
+                        failed.attempts
+                    );
+                } else {
+                    log::warn!(
+                        "{} |> Publishing to {} failed, retrying in {}s",
+                        link,
+                        target.account(),
+                        failed.next_attempt_at - now
+                    );
+                }
+            }
+            Err(Error::target(target.account(), guid, err))
+        }
+    }
+}
+
+/// The network has refused the publish, or it hasn't been reached at all
+fn has_definitely_failed(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.is::<Rejected>()
+        || err.is::<RateLimited>()
+        || err
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_connect)
+}
+
+/// Publishes the posts of the outbox whose retry or schedule is due. They are published from the
+/// outbox, the feeds may not contain them anymore.
+pub async fn retry_failed<S: syndicated_post::Storage>(
+    targets: &[Box<dyn Target>],
+    storage: &S,
+    outbox: &Outbox,
+    dry_run: bool,
+) -> Report {
+    let now = chrono::Utc::now().timestamp();
+
+    run_all(targets.iter(), |target| async move {
+        let (due, undecodable) = match outbox.due(target.account(), now, dry_run) {
+            Ok(due) => due,
+            Err(err) => return vec![entry(None, None, Some(target.account()), Err(err.into()))],
+        };
+
+        // The undecodable publishes are given up, a dry run only reports them
+        let mut entries = undecodable
+            .iter()
+            .map(|publish| {
+                let message = if dry_run {
+                    format!("{}, it would be given up", publish.error)
+                } else {
+                    format!("{}, it's given up", publish.error)
+                };
+                entry(
+                    None,
+                    Some(&publish.guid),
+                    Some(target.account()),
+                    Err(Error::item(Some(&publish.guid), &message)),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        entries.extend(
+            run_all(due.iter(), |failed| async move {
+                let post = &failed.item;
+                let result = retry(
+                    post,
+                    failed.attempts,
+                    target.as_ref(),
+                    storage,
+                    outbox,
+                    dry_run,
+                );
+                entry(
+                    None,
+                    post.guid().map(Guid::value),
+                    Some(target.account()),
+                    result.await,
+                )
+            })
+            .await,
+        );
+        entries
+    })
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Publishes the post of the outbox again, unless it has been deleted, un-targeted, scheduled later
+/// or published since
+async fn retry<S: syndicated_post::Storage>(
+    post: &Item,
+    attempts: u32,
+    target: &dyn Target,
+    storage: &S,
+    outbox: &Outbox,
+    dry_run: bool,
+) -> Result<Outcome, Error> {
+    let (guid, link) = identify(post)?;
+
+    let extension = post
+        .decode_iwt_extension()?
+        .ok_or_else(|| Error::item(Some(guid), "Rss Item doesn't have an IWT extension"))?;
+    if extension.deleted {
+        discard(guid, target, Some(outbox), dry_run)?;
+        return Ok(Outcome::Skipped {
+            reason: String::from("deleted"),
+        });
+    }
+    if !extension
+        .target_networks
+        .iter()
+        .any(|tn| tn.matches(target.account()))
+    {
+        discard(guid, target, Some(outbox), dry_run)?;
+        return Ok(Outcome::NotTargeted);
+    }
+    if let Some(scheduled_at) = extension
+        .scheduled_at(target.account())
+        .filter(|scheduled_at| *scheduled_at > chrono::Utc::now())
+    {
+        return schedule(post, scheduled_at, target, Some(outbox), dry_run);
+    }
+    if storage.find(guid, target.account())?.is_some() {
+        discard(guid, target, Some(outbox), dry_run)?;
+        return Ok(Outcome::AlreadySyndicated);
+    }
+
+    if dry_run {
+        log::info!(
+            "{} |> Retrying on {} is skipped due to --dry-run",
+            link,
+            target.account()
+        );
+        return Ok(Outcome::DryRun {
+            preview: Some(target.preview(post, &extension)),
+        });
+    }
+
+    if attempts == 0 {
+        log::info!(
+            "{} |> Publishing to {} as scheduled",
+            link,
+            target.account()
+        );
+    } else {
+        log::info!(
+            "{} |> Retrying on {}, {} failed attempts so far",
+            link,
+            target.account(),
+            attempts
+        );
+    }
+    publish(post, &extension, target, storage, Some(outbox)).await
+}
+
+/// Edits the syndicated copy if the description of the post has changed since it was syndicated.
+/// The copies which can't be edited are outdated from then on, it's logged only once.
+async fn propagate_edit<S: syndicated_post::Storage>(
+    syndicated: syndicated_post::SyndicatedPost,
+    post: &Item,
+    target: &dyn Target,
+    storage: &S,
+    dry_run: bool,
+) -> Result<Outcome, Error> {
+    let (guid, link) = identify(post)?;
+    let content_hash = syndicated_post::content_hash(post, target.account());
+
+    match &syndicated.content_hash {
+        Some(stored) if stored == &content_hash => {
+            log::info!(
+                "{} |> Has been already syndicated to {}",
+                link,
+                target.account()
+            );
+            return Ok(Outcome::AlreadySyndicated);
+        }
+        // Syndicated before the hashes were stored, the current content is assumed to be the
+        // syndicated one
+        None => {
+            if !dry_run {
+                storage.update(&syndicated_post::SyndicatedPost {
+                    content_hash: Some(content_hash),
+                    ..syndicated
+                })?;
+            }
+            return Ok(Outcome::AlreadySyndicated);
+        }
+        Some(_) => {}
+    }
+
+    let extension = post
+        .decode_iwt_extension()?
+        .ok_or_else(|| Error::item(Some(guid), "Rss Item doesn't have an IWT extension"))?;
+
+    if dry_run {
+        log::info!(
+            "{} |> Editing on {} is skipped due to --dry-run",
+            link,
+            target.account()
+        );
+        return Ok(Outcome::DryRun {
+            preview: Some(target.preview(post, &extension)),
+        });
+    }
+
+    log::info!(
+        "{} |> Has been edited, editing on {}",
+        link,
+        target.account()
+    );
+    let (updated, outcome) = match target
+        .update(&syndicated, post, &extension)
+        .await
+        .map_err(|err| Error::target(target.account(), guid, err))?
+    {
+        Some(updated) => (updated, Outcome::Edited),
+        None => {
+            log::warn!(
+                "{} |> The syndicated copy on {} can't be edited, it's outdated",
+                link,
+                target.account()
+            );
+            (
+                syndicated_post::SyndicatedPost {
+                    content_hash: Some(content_hash),
+                    ..syndicated
+                },
+                Outcome::Skipped {
+                    reason: String::from("can't be edited"),
+                },
+            )
+        }
+    };
+
+    storage.update(&updated)?;
+    Ok(outcome)
+}
+
+/// Deletes every syndicated copy of the post, i.e. of a post which isn't in the feeds anymore. The
+/// copies on accounts which aren't configured anymore can't be deleted, they are reported after
+/// the others have been deleted.
+pub async fn unpublish<S: syndicated_post::Storage>(
+    guid: &str,
+    targets: &[Box<dyn Target>],
+    storage: &S,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let mut copies = vec![];
+    let mut unconfigured = vec![];
+    for syndicated in storage.find_all(guid)? {
+        if syndicated.retracted {
+            continue;
+        }
+        match targets
+            .iter()
+            .find(|target| target.account().name == syndicated.account)
+        {
+            Some(target) => copies.push((target, syndicated)),
+            None => unconfigured.push(syndicated),
+        }
+    }
+
+    if copies.is_empty() && unconfigured.is_empty() {
+        return Err(Error::item(
+            Some(guid),
+            "It has no syndicated copies to delete",
+        ));
+    }
+
+    run_all(copies.iter(), |(target, syndicated)| {
+        retract(syndicated, target.as_ref(), storage, dry_run)
     })
     .await
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+
+    if unconfigured.is_empty() {
+        return Ok(());
+    }
+    Err(Error::item(
+        Some(guid),
+        &format!(
+            "The copies on {} can't be deleted, their accounts aren't configured",
+            unconfigured
+                .iter()
+                .map(|syndicated| format!("{} ({})", syndicated.account, syndicated.id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    ))
+}
+
+/// Deletes the syndicated copy from the network, the post is kept as retracted
+async fn retract<S: syndicated_post::Storage>(
+    syndicated: &syndicated_post::SyndicatedPost,
+    target: &dyn Target,
+    storage: &S,
+    dry_run: bool,
+) -> Result<Outcome, Error> {
+    if dry_run {
+        log::info!(
+            "{} |> Deleting from {} is skipped due to --dry-run",
+            syndicated.original_uri,
+            target.account()
+        );
+        return Ok(Outcome::DryRun { preview: None });
+    }
+
+    log::info!(
+        "{} |> Deleting from {}",
+        syndicated.original_uri,
+        target.account()
+    );
+    target
+        .delete(syndicated)
+        .await
+        .map_err(|err| Error::target(target.account(), &syndicated.original_guid, err))?;
+    storage.retract(syndicated)?;
+    log::info!(
+        "{} |> Deleted from {}",
+        syndicated.original_uri,
+        target.account()
+    );
+
+    Ok(Outcome::Deleted)
 }
 
-async fn run_and_collect<C, I, F, Fu>(items: C, f: F) -> Result<(), Box<dyn std::error::Error>>
+/// The entry of the report, the failures are logged as they happen
+fn entry(
+    feed: Option<&str>,
+    guid: Option<&str>,
+    account: Option<&Account>,
+    result: Result<Outcome, Error>,
+) -> Entry {
+    if let Err(err) = &result {
+        log::error!("{} |> {}", guid.or(feed).unwrap_or("outbox"), err);
+    }
+    Entry::new(feed, guid, account, result)
+}
+
+/// Runs `f` on the items concurrently, the outputs are in the order of the items
+async fn run_all<C, I, F, Fu>(items: C, f: F) -> Vec<Fu::Output>
 where
     C: Iterator<Item = I>,
-    // TODO: understand why this didn't work: Fn(I) -> dyn Future<Output = Result<(), Box<dyn std::error::Error>>>
-    //       or: Fn(I) -> Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error>>>>>
     F: Fn(I) -> Fu,
-    Fu: Future<Output = Result<(), Box<dyn std::error::Error>>>,
+    Fu: Future,
 {
     futures::stream::iter(items)
         .map(f)
-        .buffer_unordered(10)
+        .buffered(10)
         .collect::<Vec<_>>()
         .await
-        .into_iter()
-        .collect()
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
+    use std::rc::Rc;
     use std::sync::Arc;
 
-    use oauth2::{AccessToken, ClientId};
+    use oauth2::ClientId;
     use rss::Item;
-
-    use super::syndicated_post::{Storage, SyndicatedPost};
-    use crate::config::{Config, Mastodon, Rss, Twitter, UrlShortener, DB};
+    use rusqlite::Connection;
+    use serde_json::json;
+
+    use super::syndicated_post::{content_hash, Storage, SyndicatedPost};
+    use crate::config::{
+        self, Config, Daemon, Mastodon, Rss, Target, TargetKind, Twitter, UrlShortener, DB,
+    };
+    use crate::cross_publisher::json_feed::extension_map_from_json;
     use crate::cross_publisher::rss::stubs::gen_items_with_extension;
     use crate::cross_publisher::rss_item_ext::stubs::create_iwt_extension_map;
     use crate::cross_publisher::rss_item_ext::RssItemExt;
     use crate::cross_publisher::stubs::rss::{gen_items, StubRssClient};
     use crate::cross_publisher::stubs::syndycated_post::SyndicatedPostStorageStub;
-    use crate::cross_publisher::stubs::target::FailingStubTarget;
     use crate::cross_publisher::stubs::target::StubTarget;
-    use crate::social::{self, Network};
+    use crate::cross_publisher::stubs::target::{FailingStubTarget, PartialStubTarget};
+    use crate::social::{self, Account, Network};
 
-    use super::syndicate;
+    use super::Outbox;
+    use super::{retry_failed, syndicate, unpublish};
+    use crate::cross_publisher::report::{Entry, Outcome, Report};
 
     fn config(urls: Vec<String>) -> Config {
         Config {
             rss: Rss { urls },
+            h_feed: None,
             db: DB {
                 path: String::from("some/path"),
             },
-            twitter: Twitter {
-                client_id: ClientId::new(String::from("some_client_id")),
-            },
-            mastodon: Mastodon {
-                base_uri: String::from("https://example.com/mastodon"),
-                access_token: AccessToken::new(String::from("some-access-token")),
-            },
+            targets: vec![
+                Target {
+                    name: None,
+                    edits: false,
+                    kind: TargetKind::Twitter(Twitter {
+                        client_id: ClientId::new(String::from("some_client_id")),
+                        thread: false,
+                    }),
+                },
+                Target {
+                    name: None,
+                    edits: false,
+                    kind: TargetKind::Mastodon(Mastodon {
+                        base_uri: String::from("https://example.com/mastodon"),
+                        thread: false,
+                        visibility: None,
+                        language: None,
+                        sensitive: false,
+                        in_reply_to_id: None,
+                    }),
+                },
+            ],
             url_shortener: UrlShortener {
                 protocol: String::from("http"),
                 domain: String::from("shortly"),
                 put_base_uri: Some(String::from("http://localhost:9000")),
             },
+            daemon: Daemon::default(),
+            outbox: config::Outbox::default(),
+            websub: None,
+            webmention: None,
         }
     }
 
@@ -193,15 +888,17 @@ mod test {
         let stub_target = StubTarget::new(Network::Mastodon);
         let targets = vec![stub_target.into()];
 
-        syndicate(
+        assert!(syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             false,
         )
         .await
-        .expect("Should be Ok()");
+        .is_success());
 
         let calls = (*client_calls).lock().await;
 
@@ -219,15 +916,17 @@ mod test {
         let stub_target = StubTarget::new(Network::Mastodon);
         let targets = vec![stub_target.into()];
 
-        syndicate(
+        assert!(syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             false,
         )
         .await
-        .expect("Should be Ok()");
+        .is_success());
 
         let calls = (*client_calls).lock().await;
 
@@ -244,21 +943,98 @@ mod test {
         let target_calls = Arc::clone(&stub_target.calls);
         let targets = vec![stub_target.into()];
 
-        syndicate(
+        assert!(syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             false,
         )
         .await
-        .expect("Should be Ok()");
+        .is_success());
 
         let calls = (*target_calls).lock().await;
 
         assert_eq!(*calls, *gen_items(&[feed]).get(feed).unwrap());
     }
 
+    #[tokio::test]
+    async fn test_syndycate_skips_feeds_which_were_not_modified() {
+        let feed = "http://example.com/rss.xml?not_modified=1";
+        let config = config(vec![feed.to_string()]);
+
+        let client = StubRssClient::new(&gen_items(&[feed]));
+        let stub_target = StubTarget::new(Network::Mastodon);
+        let target_calls = Arc::clone(&stub_target.calls);
+        let targets = vec![stub_target.into()];
+
+        assert!(syndicate(
+            &config,
+            &client,
+            &targets,
+            &SyndicatedPostStorageStub::default(),
+            None,
+            None,
+            false,
+        )
+        .await
+        .is_success());
+
+        assert_eq!(*(*target_calls).lock().await, vec![]);
+        assert_eq!(
+            *client.syndicated_urls.lock().unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_marks_only_the_fully_syndicated_feeds_as_syndicated() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let client = StubRssClient::new(&gen_items(&[feed]));
+        let targets = vec![StubTarget::new(Network::Mastodon).into()];
+
+        assert!(syndicate(
+            &config,
+            &client,
+            &targets,
+            &SyndicatedPostStorageStub::default(),
+            None,
+            None,
+            false,
+        )
+        .await
+        .is_success());
+
+        assert_eq!(*client.syndicated_urls.lock().unwrap(), vec![feed]);
+
+        let client = StubRssClient::new(&gen_items(&[feed]));
+        let targets = vec![
+            FailingStubTarget::default().into(),
+            StubTarget::new(Network::Mastodon).into(),
+        ];
+
+        let report = syndicate(
+            &config,
+            &client,
+            &targets,
+            &SyndicatedPostStorageStub::default(),
+            None,
+            None,
+            false,
+        )
+        .await;
+
+        assert!(!report.is_success());
+        assert_eq!(
+            *client.syndicated_urls.lock().unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
     #[tokio::test]
     async fn test_syndycate_should_skip_published_posts() {
         let feed = "http://example.com/rss.xml";
@@ -275,16 +1051,18 @@ mod test {
         for item in items.get(feed).unwrap() {
             storage
                 .store(SyndicatedPost::new(
-                    Network::Mastodon,
+                    &Account::from(Network::Mastodon),
                     &String::from("id"),
                     item,
                 ))
                 .unwrap();
         }
 
-        syndicate(&config, &client, &targets, &storage, false)
-            .await
-            .expect("Should be Ok()");
+        assert!(
+            syndicate(&config, &client, &targets, &storage, None, None, false)
+                .await
+                .is_success()
+        );
 
         let calls = (*target_calls).lock().await;
 
@@ -306,15 +1084,17 @@ mod test {
 
         let targets = vec![stub_target1.into(), stub_target2.into()];
 
-        syndicate(
+        assert!(syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             false,
         )
         .await
-        .expect("Should be Ok()");
+        .is_success());
 
         let calls1 = (*target_calls1).lock().await;
         let calls2 = (*target_calls2).lock().await;
@@ -385,15 +1165,17 @@ mod test {
 
         let targets = vec![stub_target1.into(), stub_target2.into()];
 
-        syndicate(
+        assert!(syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             false,
         )
         .await
-        .expect("Should be Ok()");
+        .is_success());
 
         let calls1 = (*target_calls1).lock().await;
         let calls2 = (*target_calls2).lock().await;
@@ -445,21 +1227,27 @@ mod test {
 
         let targets = vec![stub_target1.into(), stub_target2.into()];
 
-        syndicate(
+        let report = syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             true,
         )
-        .await
-        .expect("Should be Ok()");
+        .await;
 
         let calls1 = (*target_calls1).lock().await;
         let calls2 = (*target_calls2).lock().await;
 
         assert!(calls1.is_empty());
         assert!(calls2.is_empty());
+        assert_eq!(report.entries.len(), 16);
+        assert!(report
+            .entries
+            .iter()
+            .all(|entry| matches!(entry.outcome, Outcome::DryRun { preview: Some(_) })));
     }
 
     #[tokio::test]
@@ -480,16 +1268,18 @@ mod test {
 
         let targets = vec![stub_target1.into(), stub_target2.into()];
 
-        let result = syndicate(
+        let report = syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             false,
         )
         .await;
 
-        assert!(result.is_err());
+        assert_eq!(failure_kinds(&report), vec!["feed"]);
 
         let calls1 = (*target_calls1).lock().await;
         let calls2 = (*target_calls2).lock().await;
@@ -512,47 +1302,128 @@ mod test {
 
         let targets = vec![stub_target1.into(), stub_target2.into()];
 
-        let result = syndicate(
+        let report = syndicate(
             &config,
             &client,
             &targets,
             &SyndicatedPostStorageStub::default(),
+            None,
+            None,
             false,
         )
         .await;
 
-        assert!(result.is_err());
+        assert_eq!(failure_kinds(&report), vec!["target"; 8]);
 
         let calls2 = (*target_calls2).lock().await;
         assert_eq!(*calls2, merged_items(&items, &[feed1, feed2]));
     }
 
     #[tokio::test]
-    async fn test_syndycate_should_store_the_syndicated_posts() {
-        let feed1 = "http://example.com/rss.xml";
-        let feed2 = "https://blog.example.com/rss.xml";
-        let config = config(vec![feed1.to_string(), feed2.to_string()]);
+    async fn test_syndycate_reports_the_outcome_of_every_post_and_target() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
 
-        let items = gen_items(&[feed1, feed2]);
+        let items = gen_items_with_extension(
+            &[feed],
+            2,
+            0,
+            &create_iwt_extension_map(&[social::Network::Mastodon], None, &Vec::new()),
+        );
         let client = StubRssClient::new(&items);
-        let stub_target1 = StubTarget::new(Network::Mastodon);
-        let stub_target2 = StubTarget::new(Network::Twitter);
-
-        let targets = vec![stub_target1.into(), stub_target2.into()];
+        let targets = vec![
+            StubTarget::new(Network::Mastodon).into(),
+            StubTarget::new(Network::Twitter).into(),
+        ];
         let storage = SyndicatedPostStorageStub::default();
+        storage
+            .store(SyndicatedPost::new(
+                &Account::from(Network::Mastodon),
+                "0",
+                &items.get(feed).unwrap()[0],
+            ))
+            .unwrap();
+
+        let report = syndicate(&config, &client, &targets, &storage, None, None, false).await;
+
+        let entry = |post: &str, account: &str, outcome| Entry {
+            feed: Some(String::from(feed)),
+            guid: Some(format!("{feed}/{post}")),
+            account: Some(String::from(account)),
+            outcome,
+        };
+        assert_eq!(
+            report.entries,
+            vec![
+                entry("post-0", "mastodon", Outcome::AlreadySyndicated),
+                entry("post-1", "mastodon", Outcome::Published),
+                entry("post-0", "twitter", Outcome::NotTargeted),
+                entry("post-1", "twitter", Outcome::NotTargeted),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_publishes_when_single_item_is_malformed() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let mut items = gen_items(&[feed]);
+        items.get_mut(feed).unwrap()[1].guid = None;
+        let client = StubRssClient::new(&items);
+        let stub_target = StubTarget::new(Network::Mastodon);
+        let target_calls = Arc::clone(&stub_target.calls);
+
+        let report = syndicate(
+            &config,
+            &client,
+            &[stub_target.into()],
+            &SyndicatedPostStorageStub::default(),
+            None,
+            None,
+            false,
+        )
+        .await;
+
+        assert_eq!(failure_kinds(&report), vec!["item"]);
+
+        let mut published = items.remove(feed).unwrap();
+        published.remove(1);
+        assert_eq!(*target_calls.lock().await, published);
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_should_store_the_syndicated_posts() {
+        let feed1 = "http://example.com/rss.xml";
+        let feed2 = "https://blog.example.com/rss.xml";
+        let config = config(vec![feed1.to_string(), feed2.to_string()]);
+
+        let items = gen_items(&[feed1, feed2]);
+        let client = StubRssClient::new(&items);
+        let stub_target1 = StubTarget::new(Network::Mastodon);
+        let stub_target2 = StubTarget::new(Network::Twitter);
+
+        let targets = vec![stub_target1.into(), stub_target2.into()];
+        let storage = SyndicatedPostStorageStub::default();
+
+        assert!(
+            syndicate(&config, &client, &targets, &storage, None, None, false)
+                .await
+                .is_success()
+        );
 
-        syndicate(&config, &client, &targets, &storage, false)
-            .await
-            .expect("Should be Ok()");
-
         let mut expected = merged_items(&items, &[feed1, feed2])
             .iter()
             .enumerate()
             .map(|(i, item)| SyndicatedPost {
                 social_network: Network::Mastodon,
+                account: String::from("mastodon"),
                 id: i.to_string(),
                 original_guid: String::from(item.guid().unwrap().value()),
                 original_uri: String::from(item.link().unwrap()),
+                thread: vec![],
+                content_hash: Some(content_hash(item, &Account::from(Network::Mastodon))),
+                retracted: false,
             })
             .collect::<Vec<_>>();
 
@@ -562,9 +1433,13 @@ mod test {
                 .enumerate()
                 .map(|(i, item)| SyndicatedPost {
                     social_network: Network::Twitter,
+                    account: String::from("twitter"),
                     id: i.to_string(),
                     original_guid: String::from(item.guid().unwrap().value()),
                     original_uri: String::from(item.link().unwrap()),
+                    thread: vec![],
+                    content_hash: Some(content_hash(item, &Account::from(Network::Twitter))),
+                    retracted: false,
                 })
                 .collect::<Vec<_>>(),
         );
@@ -590,4 +1465,754 @@ mod test {
         assert_eq!(posts.len(), expected.len());
         assert_eq!(*posts, expected);
     }
+    #[tokio::test]
+    async fn test_syndycate_publishes_to_the_selected_accounts_of_a_network() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let mut items = gen_items_with_extension(
+            &[feed],
+            1,
+            0,
+            &create_iwt_extension_map(&["mastodon:personal"], None, &Vec::new()),
+        );
+        items.get_mut(feed).unwrap().extend(
+            gen_items_with_extension(
+                &[feed],
+                1,
+                1,
+                &create_iwt_extension_map(&[social::Network::Mastodon], None, &Vec::new()),
+            )
+            .get(feed)
+            .unwrap()
+            .iter()
+            .cloned(),
+        );
+
+        let client = StubRssClient::new(&items);
+        let personal = StubTarget::with_account(Account::new(Network::Mastodon, "personal"));
+        let personal_calls = Arc::clone(&personal.calls);
+        let project = StubTarget::with_account(Account::new(Network::Mastodon, "project"));
+        let project_calls = Arc::clone(&project.calls);
+
+        let targets = vec![personal.into(), project.into()];
+        let storage = SyndicatedPostStorageStub::default();
+
+        assert!(
+            syndicate(&config, &client, &targets, &storage, None, None, false)
+                .await
+                .is_success()
+        );
+
+        let items = items.get(feed).unwrap();
+        assert_eq!(*personal_calls.lock().await, *items);
+        assert_eq!(*project_calls.lock().await, items[1..]);
+
+        let posts = storage.posts.lock().unwrap();
+        assert_eq!(
+            posts
+                .iter()
+                .filter(|post| post.account == "personal")
+                .count(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_propagates_the_edits_of_syndicated_posts() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items(&[feed]);
+        let client = StubRssClient::new(&items);
+        let mut stub_target = StubTarget::new(Network::Mastodon);
+        stub_target.edits = true;
+        let updates = Arc::clone(&stub_target.updates);
+        let targets = vec![stub_target.into()];
+
+        let storage = SyndicatedPostStorageStub::default();
+        let items = items.get(feed).unwrap();
+        let edited = &items[0];
+        let account = Account::from(Network::Mastodon);
+        storage
+            .store(SyndicatedPost {
+                content_hash: Some(String::from("before the edit")),
+                ..SyndicatedPost::new(&account, "0", edited)
+            })
+            .unwrap();
+        storage
+            .store(SyndicatedPost::new(&account, "1", &items[1]))
+            .unwrap();
+
+        assert!(
+            syndicate(&config, &client, &targets, &storage, None, None, false)
+                .await
+                .is_success()
+        );
+
+        assert_eq!(*updates.lock().await, vec![edited.clone()]);
+        assert_eq!(
+            storage
+                .find(edited.guid().unwrap().value(), &account)
+                .unwrap()
+                .unwrap()
+                .content_hash,
+            Some(content_hash(edited, &account))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_stores_the_missing_content_hash_unless_dry_run() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items(&[feed]);
+        let client = StubRssClient::new(&items);
+        let mut stub_target = StubTarget::new(Network::Mastodon);
+        stub_target.edits = true;
+        let updates = Arc::clone(&stub_target.updates);
+        let targets = vec![stub_target.into()];
+
+        let storage = SyndicatedPostStorageStub::default();
+        let account = Account::from(Network::Mastodon);
+        for (id, item) in items.get(feed).unwrap().iter().enumerate() {
+            storage
+                .store(SyndicatedPost {
+                    content_hash: None,
+                    ..SyndicatedPost::new(&account, &id.to_string(), item)
+                })
+                .unwrap();
+        }
+        let stored_hashes = || {
+            storage
+                .posts
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|post| post.content_hash.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(
+            syndicate(&config, &client, &targets, &storage, None, None, true)
+                .await
+                .is_success()
+        );
+        assert_eq!(stored_hashes(), vec![None; items.get(feed).unwrap().len()]);
+
+        assert!(
+            syndicate(&config, &client, &targets, &storage, None, None, false)
+                .await
+                .is_success()
+        );
+        assert_eq!(
+            stored_hashes(),
+            items
+                .get(feed)
+                .unwrap()
+                .iter()
+                .map(|item| Some(content_hash(item, &account)))
+                .collect::<Vec<_>>()
+        );
+        assert!(updates.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_deletes_the_copies_of_deleted_posts() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items_with_extension(
+            &[feed],
+            2,
+            0,
+            &extension_map_from_json(&json!({
+                "targetNetworks": ["mastodon"],
+                "deleted": true,
+            })),
+        );
+        let client = StubRssClient::new(&items);
+        let stub_target = StubTarget::new(Network::Mastodon);
+        let calls = Arc::clone(&stub_target.calls);
+        let deletions = Arc::clone(&stub_target.deletions);
+        let targets = vec![stub_target.into()];
+
+        let storage = SyndicatedPostStorageStub::default();
+        let account = Account::from(Network::Mastodon);
+        let deleted = &items.get(feed).unwrap()[0];
+        storage
+            .store(SyndicatedPost::new(&account, "1", deleted))
+            .unwrap();
+
+        for _ in 0..2 {
+            assert!(
+                syndicate(&config, &client, &targets, &storage, None, None, false)
+                    .await
+                    .is_success()
+            );
+        }
+
+        assert!(calls.lock().await.is_empty());
+        assert_eq!(*deletions.lock().await, vec![String::from("1")]);
+        assert!(
+            storage
+                .find(deleted.guid().unwrap().value(), &account)
+                .unwrap()
+                .unwrap()
+                .retracted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_defers_the_posts_until_they_are_due_on_the_network() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items_with_extension(
+            &[feed],
+            1,
+            0,
+            &extension_map_from_json(&json!({
+                "targetNetworks": ["mastodon", "twitter"],
+                "publishAt": "2020-01-01T00:00:00Z",
+                "publishOffsets": ["twitter=3153600000"],
+            })),
+        );
+        let post = &items.get(feed).unwrap()[0];
+        let client = StubRssClient::new(&items);
+        let mastodon = StubTarget::new(Network::Mastodon);
+        let twitter = StubTarget::new(Network::Twitter);
+        let calls = [Arc::clone(&mastodon.calls), Arc::clone(&twitter.calls)];
+        let targets = vec![mastodon.into(), twitter.into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+
+        let report = syndicate(
+            &config,
+            &client,
+            &targets,
+            &storage,
+            Some(&outbox),
+            None,
+            false,
+        )
+        .await;
+
+        assert_eq!(
+            report
+                .entries
+                .iter()
+                .map(|entry| &entry.outcome)
+                .collect::<Vec<_>>(),
+            vec![
+                &Outcome::Published,
+                &Outcome::Scheduled {
+                    publish_at: String::from("2119-12-08T00:00:00+00:00")
+                }
+            ]
+        );
+        assert_eq!(*calls[0].lock().await, vec![post.clone()]);
+        assert!(calls[1].lock().await.is_empty());
+        let scheduled = outbox
+            .find(
+                post.guid().unwrap().value(),
+                &Account::from(Network::Twitter),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (scheduled.attempts, scheduled.next_attempt_at),
+            (0, 4731436800)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_drops_the_scheduled_posts_which_are_deleted_or_not_targeted() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+        let scheduled = |extension: serde_json::Value| {
+            gen_items_with_extension(&[feed], 1, 0, &extension_map_from_json(&extension))
+        };
+
+        let mastodon = StubTarget::new(Network::Mastodon);
+        let twitter = StubTarget::new(Network::Twitter);
+        let calls = [Arc::clone(&mastodon.calls), Arc::clone(&twitter.calls)];
+        let targets = vec![mastodon.into(), twitter.into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+
+        let items = scheduled(json!({
+            "targetNetworks": ["mastodon", "twitter"],
+            "publishAt": "2119-12-08T00:00:00Z",
+        }));
+        let guid = items.get(feed).unwrap()[0]
+            .guid()
+            .unwrap()
+            .value()
+            .to_string();
+        syndicate(
+            &config,
+            &StubRssClient::new(&items),
+            &targets,
+            &storage,
+            Some(&outbox),
+            None,
+            false,
+        )
+        .await;
+        let updated = [
+            scheduled(json!({
+                "targetNetworks": ["mastodon"],
+                "publishAt": "2119-12-08T00:00:00Z",
+                "deleted": true,
+            })),
+            scheduled(json!({
+                "targetNetworks": ["mastodon"],
+                "publishAt": "2119-12-08T00:00:00Z",
+            })),
+        ];
+        for items in &updated {
+            syndicate(
+                &config,
+                &StubRssClient::new(items),
+                &targets,
+                &storage,
+                Some(&outbox),
+                None,
+                false,
+            )
+            .await;
+        }
+
+        assert_eq!(
+            outbox
+                .find(&guid, &Account::from(Network::Twitter))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            outbox
+                .find(&guid, &Account::from(Network::Mastodon))
+                .unwrap()
+                .map(|scheduled| scheduled.next_attempt_at),
+            Some(4731436800)
+        );
+        assert!(calls[0].lock().await.is_empty());
+        assert!(calls[1].lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unpublish_deletes_the_copies_of_every_account() {
+        let items = gen_items(&["http://example.com/rss.xml"]);
+        let post = &items.get("http://example.com/rss.xml").unwrap()[0];
+        let guid = post.guid().unwrap().value();
+
+        let mastodon = StubTarget::new(Network::Mastodon);
+        let twitter = StubTarget::new(Network::Twitter);
+        let deletions = [
+            Arc::clone(&mastodon.deletions),
+            Arc::clone(&twitter.deletions),
+        ];
+        let targets = vec![mastodon.into(), twitter.into()];
+
+        let storage = SyndicatedPostStorageStub::default();
+        storage
+            .store(SyndicatedPost::new(
+                &Account::from(Network::Mastodon),
+                "1",
+                post,
+            ))
+            .unwrap();
+        storage
+            .store(SyndicatedPost::new(
+                &Account::from(Network::Twitter),
+                "2",
+                post,
+            ))
+            .unwrap();
+
+        unpublish(guid, &targets, &storage, false)
+            .await
+            .expect("Should be Ok()");
+
+        assert_eq!(*deletions[0].lock().await, vec![String::from("1")]);
+        assert_eq!(*deletions[1].lock().await, vec![String::from("2")]);
+        assert!(storage
+            .posts
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|post| post.retracted));
+        assert!(unpublish(guid, &targets, &storage, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unpublish_reports_the_copies_of_unconfigured_accounts() {
+        let items = gen_items(&["http://example.com/rss.xml"]);
+        let post = &items.get("http://example.com/rss.xml").unwrap()[0];
+        let guid = post.guid().unwrap().value();
+
+        let mastodon = StubTarget::new(Network::Mastodon);
+        let deletions = Arc::clone(&mastodon.deletions);
+        let targets = vec![mastodon.into()];
+
+        let storage = SyndicatedPostStorageStub::default();
+        storage
+            .store(SyndicatedPost::new(
+                &Account::from(Network::Mastodon),
+                "1",
+                post,
+            ))
+            .unwrap();
+        storage
+            .store(SyndicatedPost::new(
+                &Account::new(Network::Mastodon, "removed"),
+                "2",
+                post,
+            ))
+            .unwrap();
+
+        let err = unpublish(guid, &targets, &storage, false)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("removed (2)"));
+        assert_eq!(*deletions.lock().await, vec![String::from("1")]);
+        assert_eq!(
+            storage
+                .posts
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|post| post.retracted)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+    }
+
+    fn failure_kinds(report: &Report) -> Vec<&'static str> {
+        report
+            .failures()
+            .filter_map(|entry| match entry.outcome {
+                Outcome::Failed { kind, .. } => Some(kind),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn outbox() -> Outbox {
+        let outbox = Outbox::new(
+            Rc::new(Connection::open_in_memory().unwrap()),
+            &config::Outbox::default(),
+        );
+        outbox.init_table().unwrap();
+        outbox
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_does_not_retry_failed_publishes_before_they_are_due() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items(&[feed]);
+        let client = StubRssClient::new(&items);
+        let targets = vec![FailingStubTarget::default().into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+
+        let first = syndicate(
+            &config,
+            &client,
+            &targets,
+            &storage,
+            Some(&outbox),
+            None,
+            false,
+        )
+        .await;
+        let second = syndicate(
+            &config,
+            &client,
+            &targets,
+            &storage,
+            Some(&outbox),
+            None,
+            false,
+        )
+        .await;
+
+        assert!(!first.is_success());
+        assert!(second.is_success());
+        let failed = outbox
+            .find(
+                items.get(feed).unwrap()[0].guid().unwrap().value(),
+                &Account::from(Network::Twitter),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.attempts, 1);
+        assert_eq!(failed.error, "RssClientError");
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_reconciles_the_posts_published_by_an_interrupted_run() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items(&[feed]);
+        let post = &items.get(feed).unwrap()[0];
+        let account = Account::from(Network::Mastodon);
+        let client = StubRssClient::new(&items);
+        let stub_target = StubTarget::new(Network::Mastodon);
+        let calls = Arc::clone(&stub_target.calls);
+        calls.lock().await.push(post.clone());
+        let targets = vec![stub_target.into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+        outbox.mark_in_flight(post, &account).unwrap();
+
+        let report = syndicate(
+            &config,
+            &client,
+            &targets,
+            &storage,
+            Some(&outbox),
+            None,
+            false,
+        )
+        .await;
+
+        assert_eq!(report.entries[0].outcome, Outcome::Reconciled);
+        assert_eq!(calls.lock().await.len(), items.get(feed).unwrap().len());
+        assert_eq!(
+            storage
+                .find(post.guid().unwrap().value(), &account)
+                .unwrap()
+                .map(|syndicated| syndicated.id),
+            Some(String::from("0"))
+        );
+        assert!(!outbox
+            .is_in_flight(post.guid().unwrap().value(), &account)
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_keeps_in_flight_only_the_publishes_which_may_have_been_published() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items(&[feed]);
+        let guid = items.get(feed).unwrap()[0].guid().unwrap().value();
+        let account = Account::from(Network::Twitter);
+        let client = StubRssClient::new(&items);
+        let storage = SyndicatedPostStorageStub::default();
+        let rejected = outbox();
+        let unknown = outbox();
+
+        syndicate(
+            &config,
+            &client,
+            &[FailingStubTarget::rejected().into()],
+            &storage,
+            Some(&rejected),
+            None,
+            false,
+        )
+        .await;
+        syndicate(
+            &config,
+            &client,
+            &[FailingStubTarget::default().into()],
+            &storage,
+            Some(&unknown),
+            None,
+            false,
+        )
+        .await;
+
+        assert!(!rejected.is_in_flight(guid, &account).unwrap());
+        assert!(unknown.is_in_flight(guid, &account).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_syndycate_defers_the_publishes_until_the_rate_limit_resets() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+
+        let items = gen_items(&[feed]);
+        let client = StubRssClient::new(&items);
+        let until = chrono::Utc::now() + chrono::Duration::minutes(15);
+        let targets = vec![FailingStubTarget::rate_limited(until).into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+
+        let report = syndicate(
+            &config,
+            &client,
+            &targets,
+            &storage,
+            Some(&outbox),
+            None,
+            false,
+        )
+        .await;
+
+        assert!(report.is_success());
+        let deferred = outbox
+            .find(
+                items.get(feed).unwrap()[0].guid().unwrap().value(),
+                &Account::from(Network::Twitter),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (deferred.attempts, deferred.next_attempt_at),
+            (0, until.timestamp())
+        );
+        assert!(
+            retry_failed(&targets, &storage, &outbox, false)
+                .await
+                .entries
+                .is_empty(),
+            "the deferred publishes aren't due before the reset"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_checks_the_targets_and_the_schedule_of_the_stored_post() {
+        let feed = "http://example.com/rss.xml";
+        let items = gen_items_with_extension(
+            &[feed],
+            1,
+            0,
+            &extension_map_from_json(&json!({
+                "targetNetworks": ["twitter"],
+                "publishAt": "2119-12-08T00:00:00Z",
+            })),
+        );
+        let post = &items.get(feed).unwrap()[0];
+        let guid = post.guid().unwrap().value();
+
+        let mastodon = StubTarget::new(Network::Mastodon);
+        let twitter = StubTarget::new(Network::Twitter);
+        let calls = [Arc::clone(&mastodon.calls), Arc::clone(&twitter.calls)];
+        let targets = vec![mastodon.into(), twitter.into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+        outbox
+            .schedule(post, &Account::from(Network::Mastodon), 0)
+            .unwrap();
+        outbox
+            .schedule(post, &Account::from(Network::Twitter), 0)
+            .unwrap();
+
+        let report = retry_failed(&targets, &storage, &outbox, false).await;
+
+        assert_eq!(
+            report
+                .entries
+                .iter()
+                .map(|entry| &entry.outcome)
+                .collect::<Vec<_>>(),
+            vec![
+                &Outcome::NotTargeted,
+                &Outcome::Scheduled {
+                    publish_at: String::from("2119-12-08T00:00:00+00:00")
+                }
+            ]
+        );
+        assert!(calls[0].lock().await.is_empty());
+        assert!(calls[1].lock().await.is_empty());
+        assert_eq!(
+            outbox
+                .find(guid, &Account::from(Network::Mastodon))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            outbox
+                .find(guid, &Account::from(Network::Twitter))
+                .unwrap()
+                .map(|scheduled| scheduled.next_attempt_at),
+            Some(4731436800)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_resumes_the_partially_published_threads() {
+        let feed = "http://example.com/rss.xml";
+        let config = config(vec![feed.to_string()]);
+        let items = gen_items(&[feed]);
+        let post = &items.get(feed).unwrap()[0];
+        let guid = post.guid().unwrap().value();
+        let account = Account::from(Network::Twitter);
+
+        let stub_target = PartialStubTarget::new(Network::Twitter);
+        let resumed = Arc::clone(&stub_target.resumed);
+        let targets = vec![stub_target.into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+
+        let report = syndicate(
+            &config,
+            &StubRssClient::new(&items),
+            &targets,
+            &storage,
+            Some(&outbox),
+            None,
+            false,
+        )
+        .await;
+
+        assert!(!report.is_success());
+        assert_eq!(
+            outbox.find(guid, &account).unwrap().unwrap().published,
+            vec![String::from("1")]
+        );
+
+        // Due right away
+        outbox
+            .record_failure(post, &account, "Service Unavailable", 0)
+            .unwrap();
+        let report = retry_failed(&targets, &storage, &outbox, false).await;
+
+        assert_eq!(report.entries[0].outcome, Outcome::Published);
+        assert_eq!(*resumed.lock().await, vec![vec![String::from("1")]]);
+        let syndicated = storage.find(guid, &account).unwrap().unwrap();
+        assert_eq!(
+            (syndicated.id.as_str(), syndicated.thread),
+            ("1", vec![String::from("2")])
+        );
+        assert_eq!(outbox.find(guid, &account).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_publishes_the_due_posts_from_the_outbox() {
+        let items = gen_items(&["http://example.com/rss.xml"]);
+        let post = &items.get("http://example.com/rss.xml").unwrap()[0];
+        let account = Account::from(Network::Mastodon);
+
+        let stub_target = StubTarget::new(Network::Mastodon);
+        let calls = Arc::clone(&stub_target.calls);
+        let targets = vec![stub_target.into()];
+        let storage = SyndicatedPostStorageStub::default();
+        let outbox = outbox();
+        outbox
+            .record_failure(post, &account, "Service Unavailable", 0)
+            .unwrap();
+
+        assert!(retry_failed(&targets, &storage, &outbox, false)
+            .await
+            .is_success());
+
+        assert_eq!(*calls.lock().await, vec![post.clone()]);
+        assert!(storage
+            .find(post.guid().unwrap().value(), &account)
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            outbox.find(post.guid().unwrap().value(), &account).unwrap(),
+            None
+        );
+    }
 }
\ No newline at end of file
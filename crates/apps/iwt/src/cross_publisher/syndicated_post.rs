@@ -1,27 +1,85 @@
 use std::rc::Rc;
 
 use rss::Item;
-use rusqlite::Connection;
+use rusqlite::{named_params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
 
-use crate::social::Network;
+use super::rss_item_ext::RssItemExt;
+use crate::commons::db;
+use crate::social::{Account, Network};
 
 #[derive(Debug, PartialEq, Clone)] // TODO: Clone is only needed for the tests
 pub struct SyndicatedPost {
     pub social_network: Network,
+    /// Name of the account the post was syndicated with
+    pub account: String,
     pub id: String,
     pub original_guid: String,
     pub original_uri: String,
+    /// IDs of the replies if the post was syndicated as a thread, `id` is the first part
+    pub thread: Vec<String>,
+    /// Hash of the syndicated content, posts syndicated before it was stored don't have one
+    pub content_hash: Option<String>,
+    /// The copy has been deleted from the network, the row is kept as history
+    pub retracted: bool,
 }
 
 impl SyndicatedPost {
-    pub fn new(social_network: Network, id: &str, item: &Item) -> Self {
+    pub fn new(account: &Account, id: &str, item: &Item) -> Self {
         Self {
-            social_network,
+            social_network: account.network.clone(),
+            account: account.name.clone(),
             id: String::from(id),
             original_guid: String::from(item.guid().unwrap().value()),
             original_uri: String::from(item.link().unwrap()),
+            thread: vec![],
+            content_hash: Some(content_hash(item, account)),
+            retracted: false,
         }
     }
+
+    #[must_use]
+    pub fn with_thread(self, thread: Vec<String>) -> Self {
+        Self { thread, ..self }
+    }
+
+    /// The row of `SELECT id, social_network, account, original_guid, original_uri, thread,
+    /// content_hash, retracted_at`
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            social_network: row.get(1)?,
+            account: row.get(2)?,
+            original_guid: row.get(3)?,
+            original_uri: row.get(4)?,
+            thread: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+            content_hash: row.get(6)?,
+            retracted: row.get::<_, Option<String>>(7)?.is_some(),
+        })
+    }
+}
+
+/// SHA-256 of what is published on the account: the text of single posts and of threads, the
+/// content warning and the tags. Edits of the original post are detected with it, so an edited
+/// override only changes the hash of the accounts it applies to.
+#[must_use]
+pub fn content_hash(item: &Item, account: &Account) -> String {
+    let mut hasher = Sha256::new();
+    match item.get_iwt_extension() {
+        Some(extension) => {
+            for thread in [false, true] {
+                hasher.update(extension.text(item, account, thread));
+                hasher.update([0]);
+            }
+            hasher.update(extension.content_warning.unwrap_or_default());
+            for tag in &extension.tags {
+                hasher.update([0]);
+                hasher.update(tag);
+            }
+        }
+        None => hasher.update(item.description().unwrap_or_default()),
+    }
+    hex::encode(hasher.finalize())
 }
 
 #[derive(Debug)]
@@ -32,7 +90,10 @@ pub enum StorageError {
 
 impl std::fmt::Display for StorageError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "StorageError")
+        match self {
+            StorageError::PersistenceError(message) => write!(f, "StorageError: {message}"),
+            StorageError::SqlError(err) => write!(f, "StorageError: {err}"),
+        }
     }
 }
 
@@ -44,13 +105,27 @@ impl From<rusqlite::Error> for StorageError {
 
 impl std::error::Error for StorageError {}
 
+impl From<StorageError> for crate::error::Error {
+    fn from(e: StorageError) -> Self {
+        crate::error::Error::Storage {
+            source: Box::new(e),
+        }
+    }
+}
+
 pub trait Storage {
     fn store(&self, syndicated_post: SyndicatedPost) -> Result<(), StorageError>;
     fn find(
         &self,
         original_guid: &str,
-        social_network: &Network,
+        account: &Account,
     ) -> Result<Option<SyndicatedPost>, StorageError>;
+    /// The copies of the post on every account, including the retracted ones
+    fn find_all(&self, original_guid: &str) -> Result<Vec<SyndicatedPost>, StorageError>;
+    /// Updates the thread and the content hash of an edited post
+    fn update(&self, syndicated_post: &SyndicatedPost) -> Result<(), StorageError>;
+    /// Marks the post as retracted after its copy has been deleted
+    fn retract(&self, syndicated_post: &SyndicatedPost) -> Result<(), StorageError>;
 }
 
 pub struct SqliteSyndycatedPostStorage {
@@ -68,14 +143,86 @@ impl SqliteSyndycatedPostStorage {
                 "CREATE TABLE IF NOT EXISTS post (
               id VARCHAR(64) NOT NULL,
               social_network VARCHAR(20) NOT NULL,
+              account VARCHAR(64) NOT NULL,
               original_guid TEXT NOT NULL,
               original_uri TEXT NOT NULL,
+              thread TEXT NOT NULL DEFAULT '[]',
+              content_hash TEXT,
+              retracted_at TEXT,
             
-              PRIMARY KEY (id, social_network)
+              PRIMARY KEY (id, account)
             )",
                 (),
             )
-            .map(|_| ())
+            .map_err(|err| StorageError::PersistenceError(format!("{err:?}")))?;
+
+        if !db::has_column(&self.conn, "post", "account")? {
+            self.migrate_to_accounts()?;
+        }
+
+        if !db::has_column(&self.conn, "post", "thread")? {
+            self.conn.execute(
+                "ALTER TABLE post ADD COLUMN thread TEXT NOT NULL DEFAULT '[]'",
+                (),
+            )?;
+        }
+
+        if !db::has_column(&self.conn, "post", "content_hash")? {
+            self.conn
+                .execute("ALTER TABLE post ADD COLUMN content_hash TEXT", ())?;
+        }
+
+        if !db::has_column(&self.conn, "post", "retracted_at")? {
+            self.conn
+                .execute("ALTER TABLE post ADD COLUMN retracted_at TEXT", ())?;
+        }
+
+        Ok(())
+    }
+
+    /// Every syndicated post, including the retracted ones
+    pub fn all(&self) -> Result<Vec<SyndicatedPost>, StorageError> {
+        let mut statement = self.conn.prepare(
+            "SELECT id, social_network, account, original_guid, original_uri, thread, content_hash,
+               retracted_at
+             FROM post",
+        )?;
+
+        let posts = statement
+            .query_map((), SyndicatedPost::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(posts)
+    }
+
+    /// Posts used to be stored per social network, they belong to the default accounts which are
+    /// named after the network
+    fn migrate_to_accounts(&self) -> Result<(), StorageError> {
+        // The connection is shared, the transaction is rolled back if it's dropped on an error. A
+        // dry run migrates in its own transaction.
+        let transaction = if self.conn.is_autocommit() {
+            Some(self.conn.unchecked_transaction()?)
+        } else {
+            None
+        };
+        self.conn
+            .execute_batch(
+                "ALTER TABLE post RENAME TO post_before_accounts;
+                 CREATE TABLE post (
+                   id VARCHAR(64) NOT NULL,
+                   social_network VARCHAR(20) NOT NULL,
+                   account VARCHAR(64) NOT NULL,
+                   original_guid TEXT NOT NULL,
+                   original_uri TEXT NOT NULL,
+
+                   PRIMARY KEY (id, account)
+                 );
+                 INSERT INTO post (id, social_network, account, original_guid, original_uri)
+                   SELECT id, social_network, social_network, original_guid, original_uri
+                   FROM post_before_accounts;
+                 DROP TABLE post_before_accounts;",
+            )
+            .and_then(|()| transaction.map_or(Ok(()), |transaction| transaction.commit()))
             .map_err(|err| StorageError::PersistenceError(format!("{err:?}")))
     }
 }
@@ -84,17 +231,17 @@ impl Storage for SqliteSyndycatedPostStorage {
     fn store(&self, syndicated_post: SyndicatedPost) -> Result<(), StorageError> {
         self.conn
             .execute(
-                "INSERT INTO post (id, social_network, original_guid, original_uri) 
-                 VALUES (:id, :social_network, :original_guid, :original_url)",
-                &[
-                    (":id", &syndicated_post.id),
-                    (
-                        ":social_network",
-                        &syndicated_post.social_network.to_string(),
-                    ),
-                    (":original_guid", &syndicated_post.original_guid),
-                    (":original_url", &syndicated_post.original_uri),
-                ],
+                "INSERT INTO post (id, social_network, account, original_guid, original_uri, thread, content_hash)
+                 VALUES (:id, :social_network, :account, :original_guid, :original_url, :thread, :content_hash)",
+                named_params! {
+                    ":id": &syndicated_post.id,
+                    ":social_network": &syndicated_post.social_network.to_string(),
+                    ":account": &syndicated_post.account,
+                    ":original_guid": &syndicated_post.original_guid,
+                    ":original_url": &syndicated_post.original_uri,
+                    ":thread": &serde_json::to_string(&syndicated_post.thread).unwrap(),
+                    ":content_hash": &syndicated_post.content_hash,
+                },
             )
             .map(|_| ())
             .map_err(|err| StorageError::PersistenceError(format!("{err:?}")))
@@ -103,36 +250,63 @@ impl Storage for SqliteSyndycatedPostStorage {
     fn find(
         &self,
         original_guid: &str,
-        social_network: &Network,
+        account: &Account,
     ) -> Result<Option<SyndicatedPost>, StorageError> {
         let mut statement = self.conn.prepare(
-            "SELECT id, social_network, original_guid, original_uri FROM post
-            WHERE original_guid = :original_guid AND social_network = :social_network",
+            "SELECT id, social_network, account, original_guid, original_uri, thread, content_hash,
+              retracted_at
+            FROM post WHERE original_guid = :original_guid AND account = :account",
         )?;
 
         statement
-            .query_map(
+            .query_row(
                 &[
                     (":original_guid", original_guid),
-                    (":social_network", social_network.to_string().as_str()),
+                    (":account", account.name.as_str()),
                 ],
-                |row| {
-                    Ok(SyndicatedPost {
-                        id: row.get(0).unwrap(),
-                        social_network: row.get(1).unwrap(),
-                        original_guid: row.get(2).unwrap(),
-                        original_uri: row.get(3).unwrap(),
-                    })
-                },
+                SyndicatedPost::from_row,
             )
-            .map(|iter| {
-                // TODO: this needs some clean up
-                iter.map(Result::unwrap)
-                    .collect::<Vec<_>>()
-                    .first()
-                    .map(|r| (*r).clone())
-            })
-            .map_err(|_| StorageError::PersistenceError(String::from("foo"))) // TODO: this needs some clean up
+            .optional()
+            .map_err(StorageError::SqlError)
+    }
+
+    fn find_all(&self, original_guid: &str) -> Result<Vec<SyndicatedPost>, StorageError> {
+        let mut statement = self.conn.prepare(
+            "SELECT id, social_network, account, original_guid, original_uri, thread, content_hash,
+              retracted_at
+            FROM post WHERE original_guid = ?1",
+        )?;
+
+        let posts = statement
+            .query_map([original_guid], SyndicatedPost::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(posts)
+    }
+
+    fn update(&self, syndicated_post: &SyndicatedPost) -> Result<(), StorageError> {
+        self.conn
+            .execute(
+                "UPDATE post SET thread = ?1, content_hash = ?2 WHERE id = ?3 AND account = ?4",
+                (
+                    serde_json::to_string(&syndicated_post.thread).unwrap(),
+                    &syndicated_post.content_hash,
+                    &syndicated_post.id,
+                    &syndicated_post.account,
+                ),
+            )
+            .map(|_| ())
+            .map_err(|err| StorageError::PersistenceError(format!("{err:?}")))
+    }
+
+    fn retract(&self, syndicated_post: &SyndicatedPost) -> Result<(), StorageError> {
+        self.conn
+            .execute(
+                "UPDATE post SET retracted_at = CURRENT_TIMESTAMP WHERE id = ?1 AND account = ?2",
+                (&syndicated_post.id, &syndicated_post.account),
+            )
+            .map(|_| ())
+            .map_err(|err| StorageError::PersistenceError(format!("{err:?}")))
     }
 }
 
@@ -140,7 +314,7 @@ impl Storage for SqliteSyndycatedPostStorage {
 pub mod stubs {
     use std::sync::Mutex;
 
-    use crate::social::Network;
+    use crate::social::Account;
 
     use super::{Storage, SyndicatedPost};
 
@@ -160,14 +334,213 @@ pub mod stubs {
         fn find(
             &self,
             original_guid: &str,
-            social_network: &Network,
+            account: &Account,
         ) -> Result<Option<SyndicatedPost>, super::StorageError> {
             let posts = self.posts.lock().unwrap();
 
             Ok(posts
                 .iter()
-                .find(|p| p.original_guid == *original_guid && p.social_network == *social_network)
+                .find(|p| p.original_guid == *original_guid && p.account == account.name)
                 .map(|p| (*p).clone()))
         }
+
+        fn find_all(
+            &self,
+            original_guid: &str,
+        ) -> Result<Vec<SyndicatedPost>, super::StorageError> {
+            let posts = self.posts.lock().unwrap();
+
+            Ok(posts
+                .iter()
+                .filter(|p| p.original_guid == *original_guid)
+                .cloned()
+                .collect())
+        }
+
+        fn update(&self, syndicated_post: &SyndicatedPost) -> Result<(), super::StorageError> {
+            let mut posts = self.posts.lock().unwrap();
+            if let Some(post) = posts
+                .iter_mut()
+                .find(|p| p.id == syndicated_post.id && p.account == syndicated_post.account)
+            {
+                *post = syndicated_post.clone();
+            }
+
+            Ok(())
+        }
+
+        fn retract(&self, syndicated_post: &SyndicatedPost) -> Result<(), super::StorageError> {
+            let mut posts = self.posts.lock().unwrap();
+            if let Some(post) = posts
+                .iter_mut()
+                .find(|p| p.id == syndicated_post.id && p.account == syndicated_post.account)
+            {
+                post.retracted = true;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use rss::{GuidBuilder, Item};
+    use rusqlite::Connection;
+
+    use super::{content_hash, SqliteSyndycatedPostStorage, Storage, SyndicatedPost};
+    use crate::cross_publisher::rss_item_ext::stubs::{create_iwt_extension_map, with_text};
+    use crate::social::{Account, Network};
+
+    fn item() -> Item {
+        Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_posts_are_found_per_account() {
+        let storage =
+            SqliteSyndycatedPostStorage::new(Rc::new(Connection::open_in_memory().unwrap()));
+        storage.init_table().unwrap();
+
+        let personal = Account::new(Network::Mastodon, "personal");
+        let project = Account::new(Network::Mastodon, "project");
+
+        storage
+            .store(SyndicatedPost::new(&personal, "1", &item()))
+            .unwrap();
+
+        assert_eq!(
+            storage.find("post-1", &personal).unwrap(),
+            Some(SyndicatedPost::new(&personal, "1", &item()))
+        );
+        assert_eq!(storage.find("post-1", &project).unwrap(), None);
+    }
+
+    #[test]
+    fn test_every_part_of_a_thread_is_stored() {
+        let storage =
+            SqliteSyndycatedPostStorage::new(Rc::new(Connection::open_in_memory().unwrap()));
+        storage.init_table().unwrap();
+
+        let account = Account::from(Network::Mastodon);
+        let thread = SyndicatedPost::new(&account, "1", &item())
+            .with_thread(vec![String::from("2"), String::from("3")]);
+
+        storage.store(thread.clone()).unwrap();
+
+        assert_eq!(storage.find("post-1", &account).unwrap(), Some(thread));
+    }
+
+    #[test]
+    fn test_retracted_posts_are_kept() {
+        let storage =
+            SqliteSyndycatedPostStorage::new(Rc::new(Connection::open_in_memory().unwrap()));
+        storage.init_table().unwrap();
+
+        let account = Account::from(Network::Mastodon);
+        let post = SyndicatedPost::new(&account, "1", &item());
+        storage.store(post.clone()).unwrap();
+
+        storage.retract(&post).unwrap();
+
+        let retracted = SyndicatedPost {
+            retracted: true,
+            ..post
+        };
+        assert_eq!(
+            storage.find("post-1", &account).unwrap(),
+            Some(retracted.clone())
+        );
+        assert_eq!(storage.all().unwrap(), vec![retracted]);
+    }
+
+    #[test]
+    fn test_the_thread_and_the_content_hash_are_updated() {
+        let storage =
+            SqliteSyndycatedPostStorage::new(Rc::new(Connection::open_in_memory().unwrap()));
+        storage.init_table().unwrap();
+
+        let account = Account::from(Network::Mastodon);
+        let post = SyndicatedPost {
+            content_hash: None,
+            ..SyndicatedPost::new(&account, "1", &item())
+        };
+        storage.store(post.clone()).unwrap();
+
+        let updated = SyndicatedPost {
+            content_hash: Some(String::from("edited")),
+            ..post.with_thread(vec![String::from("2")])
+        };
+        storage.update(&updated).unwrap();
+
+        assert_eq!(storage.find("post-1", &account).unwrap(), Some(updated));
+    }
+
+    #[test]
+    fn test_content_hash_changes_only_on_the_accounts_of_an_edited_override() {
+        let post = |override_text: &str, tags: &[&str]| Item {
+            description: Some(String::from("Post")),
+            extensions: with_text(
+                create_iwt_extension_map(&[Network::Mastodon, Network::Twitter], None, tags),
+                Some("mastodon"),
+                Some(override_text),
+                None,
+            ),
+            ..item()
+        };
+        let mastodon = Account::from(Network::Mastodon);
+        let twitter = Account::from(Network::Twitter);
+
+        let original = post("On Mastodon", &["rust"]);
+        let edited_override = post("Edited on Mastodon", &["rust"]);
+        let edited_tags = post("On Mastodon", &["rust", "indieweb"]);
+
+        assert_ne!(
+            content_hash(&original, &mastodon),
+            content_hash(&edited_override, &mastodon)
+        );
+        assert_eq!(
+            content_hash(&original, &twitter),
+            content_hash(&edited_override, &twitter)
+        );
+        assert_ne!(
+            content_hash(&original, &twitter),
+            content_hash(&edited_tags, &twitter)
+        );
+    }
+
+    #[test]
+    fn test_posts_stored_per_network_belong_to_the_default_account() {
+        let conn = Rc::new(Connection::open_in_memory().unwrap());
+        conn.execute_batch(
+            "CREATE TABLE post (
+              id VARCHAR(64) NOT NULL,
+              social_network VARCHAR(20) NOT NULL,
+              original_guid TEXT NOT NULL,
+              original_uri TEXT NOT NULL,
+              PRIMARY KEY (id, social_network)
+            );
+            INSERT INTO post VALUES ('1', 'mastodon', 'post-1', 'http://example.com/post-1');",
+        )
+        .unwrap();
+
+        let storage = SqliteSyndycatedPostStorage::new(conn);
+        storage.init_table().unwrap();
+
+        assert_eq!(
+            storage
+                .find("post-1", &Account::from(Network::Mastodon))
+                .unwrap(),
+            Some(SyndicatedPost {
+                content_hash: None,
+                ..SyndicatedPost::new(&Account::from(Network::Mastodon), "1", &item())
+            })
+        );
     }
 }
@@ -1,10 +1,109 @@
 
-use crate::social::Network;
+use std::fmt::Display;
+
+use crate::commons::permashort_link::PermashortCitation;
+use crate::social::Account;
 use async_trait::async_trait;
 use rss::Item;
+use serde_derive::Serialize;
 
+use super::media::MediaReference;
 use super::{rss_item_ext::IwtRssExtension, syndicated_post::SyndicatedPost};
 
+/// What the target would publish, it's shown by `--dry-run` instead of publishing
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Preview {
+    /// The text of the post, or of every part of the thread
+    pub texts: Vec<String>,
+    pub spoiler_text: Option<String>,
+    pub tags: Vec<String>,
+    /// The images which would be attached, before they are downloaded
+    pub attachments: Vec<MediaReference>,
+}
+
+impl Display for Preview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, text) in self.texts.iter().enumerate() {
+            writeln!(
+                f,
+                "    text {}/{} ({} characters):",
+                i + 1,
+                self.texts.len(),
+                text.chars().count()
+            )?;
+            for line in text.lines() {
+                writeln!(f, "      {line}")?;
+            }
+        }
+        if let Some(spoiler_text) = &self.spoiler_text {
+            writeln!(f, "    spoiler text: {spoiler_text}")?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f, "    tags: {}", self.tags.join(", "))?;
+        }
+        for attachment in &self.attachments {
+            match &attachment.alt {
+                Some(alt) => writeln!(f, "    attachment: {} ({alt})", attachment.uri)?,
+                None => writeln!(f, "    attachment: {}", attachment.uri)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A thread which failed after some of its parts were published. The IDs of the published parts
+/// are kept, and the next attempt resumes after them.
+#[derive(Debug)]
+pub struct PartiallyPublished {
+    pub ids: Vec<String>,
+    /// The text left to publish, for the targets whose parts depend on the answers of the network
+    /// (i.e. Twitter shortens them until they are accepted), splitting the whole text again may
+    /// not give back the published parts
+    pub rest: Option<String>,
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl Display for PartiallyPublished {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Thread was published partially, published parts: {:?}, error: {}",
+            self.ids, self.source
+        )
+    }
+}
+
+impl std::error::Error for PartiallyPublished {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// The network has answered the publish with an error, so nothing has been published
+#[derive(Debug)]
+pub struct Rejected {
+    pub status: u16,
+    pub message: String,
+}
+
+impl Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Rejected with status {}: {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+/// Stands for the permashort citation in the previews, they don't call the URL shortener
+#[must_use]
+pub fn placeholder_citation() -> PermashortCitation {
+    PermashortCitation::new(
+        String::from("https"),
+        String::from("example.com"),
+        String::from("s/preview"),
+    )
+}
+
 #[async_trait(?Send)]
 pub trait Target {
     async fn publish<'a>(
@@ -13,7 +112,54 @@ pub trait Target {
         extension: &IwtRssExtension,
     ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>>;
 
-    fn network(&self) -> Network;
+    /// Publishes the rest of a thread after its `published` parts, see `PartiallyPublished`. The
+    /// targets which don't publish threads publish the post again.
+    async fn resume<'a>(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+        _published: &[String],
+        _rest: Option<&str>,
+    ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
+        self.publish(post, extension).await
+    }
+
+    /// Finds the copy of the post published by an interrupted run, so that it isn't published
+    /// twice. The targets which can't look it up return `None`.
+    async fn reconcile<'a>(
+        &self,
+        _post: &Item,
+        _extension: &IwtRssExtension,
+    ) -> Result<Option<SyndicatedPost>, Box<dyn std::error::Error + 'a>> {
+        Ok(None)
+    }
+
+    /// Edits the syndicated copy after the original post has changed, returns `None` if it can't
+    /// be edited
+    async fn update<'a>(
+        &self,
+        _syndicated_post: &SyndicatedPost,
+        _post: &Item,
+        _extension: &IwtRssExtension,
+    ) -> Result<Option<SyndicatedPost>, Box<dyn std::error::Error + 'a>> {
+        Ok(None)
+    }
+
+    /// Deletes the syndicated copy, every part of its thread
+    async fn delete<'a>(
+        &self,
+        syndicated_post: &SyndicatedPost,
+    ) -> Result<(), Box<dyn std::error::Error + 'a>>;
+
+    /// What would be published, the same text as publishing with a placeholder citation
+    fn preview(&self, post: &Item, extension: &IwtRssExtension) -> Preview;
+
+    /// Whether the edits of the original posts are propagated, it's configured per target
+    fn propagates_edits(&self) -> bool {
+        false
+    }
+
+    fn account(&self) -> &Account;
 }
 
 #[cfg(test)]
@@ -22,24 +168,36 @@ pub mod stubs {
     use std::{fmt::Display, sync::Arc};
 
     use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
     use rss::Item;
 
+    use crate::cross_publisher::rate_limit::RateLimited;
     use crate::cross_publisher::rss_item_ext::IwtRssExtension;
     use crate::cross_publisher::syndicated_post::SyndicatedPost;
-    use crate::social::Network;
+    use crate::social::{Account, Network};
 
-    use super::Target;
+    use super::{PartiallyPublished, Preview, Rejected, Target};
 
     pub struct StubTarget {
-        pub social_network: Network,
+        pub account: Account,
         pub calls: Arc<Mutex<Vec<Item>>>,
+        pub edits: bool,
+        pub updates: Arc<Mutex<Vec<Item>>>,
+        pub deletions: Arc<Mutex<Vec<String>>>,
     }
 
     impl StubTarget {
         pub fn new(social_network: Network) -> Self {
+            Self::with_account(Account::from(social_network))
+        }
+
+        pub fn with_account(account: Account) -> Self {
             Self {
-                social_network,
+                account,
                 calls: Arc::default(),
+                edits: false,
+                updates: Arc::default(),
+                deletions: Arc::default(),
             }
         }
     }
@@ -54,15 +212,60 @@ pub mod stubs {
             let mut calls = self.calls.lock().await;
             let id = calls.len();
             calls.push(post.clone());
-            Ok(SyndicatedPost::new(
-                self.social_network.clone(),
-                &id.to_string(),
-                post,
+            Ok(SyndicatedPost::new(&self.account, &id.to_string(), post))
+        }
+
+        /// The published posts are found by their guid
+        async fn reconcile<'a>(
+            &self,
+            post: &Item,
+            _extension: &IwtRssExtension,
+        ) -> Result<Option<SyndicatedPost>, Box<dyn std::error::Error + 'a>> {
+            Ok(self
+                .calls
+                .lock()
+                .await
+                .iter()
+                .position(|call| call.guid() == post.guid())
+                .map(|id| SyndicatedPost::new(&self.account, &id.to_string(), post)))
+        }
+
+        async fn update<'a>(
+            &self,
+            syndicated_post: &SyndicatedPost,
+            post: &Item,
+            _extension: &IwtRssExtension,
+        ) -> Result<Option<SyndicatedPost>, Box<dyn std::error::Error + 'a>> {
+            self.updates.lock().await.push(post.clone());
+            Ok(Some(
+                SyndicatedPost::new(&self.account, &syndicated_post.id, post)
+                    .with_thread(syndicated_post.thread.clone()),
             ))
         }
 
-        fn network(&self) -> Network {
-            self.social_network.clone()
+        async fn delete<'a>(
+            &self,
+            syndicated_post: &SyndicatedPost,
+        ) -> Result<(), Box<dyn std::error::Error + 'a>> {
+            self.deletions.lock().await.push(syndicated_post.id.clone());
+            Ok(())
+        }
+
+        fn preview(&self, post: &Item, extension: &IwtRssExtension) -> Preview {
+            Preview {
+                texts: vec![String::from(extension.text(post, &self.account, false))],
+                spoiler_text: extension.content_warning.clone(),
+                tags: extension.tags.clone(),
+                attachments: vec![],
+            }
+        }
+
+        fn propagates_edits(&self) -> bool {
+            self.edits
+        }
+
+        fn account(&self) -> &Account {
+            &self.account
         }
     }
 
@@ -82,8 +285,39 @@ pub mod stubs {
 
     impl std::error::Error for TargetError {}
 
-    #[derive(Default)]
-    pub struct FailingStubTarget;
+    pub struct FailingStubTarget {
+        account: Account,
+        rate_limited_until: Option<DateTime<Utc>>,
+        rejected: bool,
+    }
+
+    impl FailingStubTarget {
+        /// Fails as if the rate limit of the network was reached
+        pub fn rate_limited(until: DateTime<Utc>) -> Self {
+            Self {
+                rate_limited_until: Some(until),
+                ..Self::default()
+            }
+        }
+
+        /// Fails as if the network had answered with an error
+        pub fn rejected() -> Self {
+            Self {
+                rejected: true,
+                ..Self::default()
+            }
+        }
+    }
+
+    impl Default for FailingStubTarget {
+        fn default() -> Self {
+            Self {
+                account: Account::from(Network::Twitter),
+                rate_limited_until: None,
+                rejected: false,
+            }
+        }
+    }
 
     #[async_trait(?Send)]
     impl Target for FailingStubTarget {
@@ -92,11 +326,34 @@ pub mod stubs {
             _post: &Item,
             _extension: &IwtRssExtension,
         ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
+            match self.rate_limited_until {
+                Some(until) => Err(Box::new(RateLimited { until })),
+                None if self.rejected => Err(Box::new(Rejected {
+                    status: 422,
+                    message: String::from("Validation failed"),
+                })),
+                None => Err(Box::new(TargetError)),
+            }
+        }
+
+        async fn delete<'a>(
+            &self,
+            _syndicated_post: &SyndicatedPost,
+        ) -> Result<(), Box<dyn std::error::Error + 'a>> {
             Err(Box::new(TargetError))
         }
 
-        fn network(&self) -> Network {
-            Network::Twitter
+        fn preview(&self, _post: &Item, _extension: &IwtRssExtension) -> Preview {
+            Preview {
+                texts: vec![],
+                spoiler_text: None,
+                tags: vec![],
+                attachments: vec![],
+            }
+        }
+
+        fn account(&self) -> &Account {
+            &self.account
         }
     }
 
@@ -105,4 +362,71 @@ pub mod stubs {
             Box::new(stub_target)
         }
     }
+    /// Publishes only the first part of the thread, the rest is published when it's resumed
+    pub struct PartialStubTarget {
+        account: Account,
+        pub resumed: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl PartialStubTarget {
+        pub fn new(social_network: Network) -> Self {
+            Self {
+                account: Account::from(social_network),
+                resumed: Arc::default(),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Target for PartialStubTarget {
+        async fn publish<'a>(
+            &self,
+            _post: &Item,
+            _extension: &IwtRssExtension,
+        ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
+            Err(Box::new(PartiallyPublished {
+                ids: vec![String::from("1")],
+                rest: None,
+                source: Box::new(TargetError),
+            }))
+        }
+
+        async fn resume<'a>(
+            &self,
+            post: &Item,
+            _extension: &IwtRssExtension,
+            published: &[String],
+            _rest: Option<&str>,
+        ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
+            self.resumed.lock().await.push(published.to_vec());
+            Ok(SyndicatedPost::new(&self.account, &published[0], post)
+                .with_thread(vec![String::from("2")]))
+        }
+
+        async fn delete<'a>(
+            &self,
+            _syndicated_post: &SyndicatedPost,
+        ) -> Result<(), Box<dyn std::error::Error + 'a>> {
+            Ok(())
+        }
+
+        fn preview(&self, _post: &Item, _extension: &IwtRssExtension) -> Preview {
+            Preview {
+                texts: vec![],
+                spoiler_text: None,
+                tags: vec![],
+                attachments: vec![],
+            }
+        }
+
+        fn account(&self) -> &Account {
+            &self.account
+        }
+    }
+
+    impl From<PartialStubTarget> for Box<dyn Target> {
+        fn from(stub_target: PartialStubTarget) -> Self {
+            Box::new(stub_target)
+        }
+    }
 }
\ No newline at end of file
@@ -6,51 +6,124 @@ use crate::commons::text;
 use crate::IwtError;
 use async_trait::async_trait;
 
-use futures::TryFutureExt;
 use oauth2::{basic::BasicClient, AuthUrl, ClientId, TokenUrl};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use rss::Item;
+use serde_json::json;
 
+use super::media::{self, Media};
+use super::rate_limit::RateLimiter;
 use super::rss_item_ext::IwtRssExtension;
 use super::syndicated_post::SyndicatedPost;
-use super::target::Target;
+use super::target::{self, PartiallyPublished, Preview, Target};
 use crate::commons::auth::oauth::AuthedClient;
 use crate::commons::auth::token_db::TokenDB;
 use crate::commons::url_shortener;
-use crate::social::Network;
+use crate::error::Error;
+use crate::social::Account;
+
+const API_URI: &str = "https://api.twitter.com";
+const MAX_ATTACHMENTS: usize = 4;
+const MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+const MAX_ALT_TEXT_LENGTH: usize = 1000;
+
+/// Rate limits, Twitter limits every endpoint separately
+const TWEETS: &str = "tweets";
+const DELETE: &str = "delete";
+const MEDIA: &str = "media";
 
 pub struct Twitter<DB: TokenDB, USClient: url_shortener::Client> {
+    account: Account,
+    thread: bool,
+    edits: bool,
     authed_client: AuthedClient<DB>,
     http_client: Client,
     url_shortener_client: Rc<USClient>,
+    rate_limiter: RateLimiter,
+    base_uri: String,
 }
 
 impl<DB: TokenDB, USClient: url_shortener::Client> Twitter<DB, USClient> {
-    pub fn new(client_id: ClientId, db: Rc<DB>, url_shortener_client: Rc<USClient>) -> Self {
-        Self {
-            authed_client: AuthedClient::new(
-                Network::Twitter,
-                BasicClient::new(
-                    client_id,
-                    None,
-                    AuthUrl::new("https://twitter.com/i/oauth2/authorize".to_string())
-                        .expect("Twitter auth url is invalid."),
-                    Some(
-                        TokenUrl::new("https://api.twitter.com/2/oauth2/token".to_string())
-                            .expect("Twitter token url is invalid"),
-                    ),
-                ),
-                db,
-            ),
+    pub fn new(
+        account: Account,
+        client_id: ClientId,
+        thread: bool,
+        edits: bool,
+        db: Rc<DB>,
+        url_shortener_client: Rc<USClient>,
+    ) -> Result<Self, Error> {
+        let authed_client = AuthedClient::new(account.clone(), oauth_client(client_id), db)
+            .map_err(|source| Error::Auth {
+                account: account.clone(),
+                source,
+            })?;
+
+        Ok(Self {
+            account,
+            thread,
+            edits,
+            authed_client,
             http_client: Client::new(),
             url_shortener_client,
-        }
+            rate_limiter: RateLimiter::default(),
+            base_uri: String::from(API_URI),
+        })
     }
 }
 
+/// The text carried by the tweets after the first `sent` ones
+fn rest_after(tweets: &[(String, String)], sent: usize) -> String {
+    tweets[sent..]
+        .iter()
+        .map(|(part, _)| part.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The thread has failed, the published tweets are kept along with the text left to publish
+fn partially_published(
+    ids: Vec<String>,
+    rest: Option<String>,
+    source: Box<dyn std::error::Error>,
+) -> Box<dyn std::error::Error> {
+    if ids.is_empty() {
+        source
+    } else {
+        Box::new(PartiallyPublished { ids, rest, source })
+    }
+}
+
+/// OAuth client of the Twitter API, the tokens are refreshed with it
+pub fn oauth_client(client_id: ClientId) -> BasicClient {
+    BasicClient::new(
+        client_id,
+        None,
+        AuthUrl::new("https://twitter.com/i/oauth2/authorize".to_string())
+            .expect("Twitter auth url is invalid."),
+        Some(
+            TokenUrl::new("https://api.twitter.com/2/oauth2/token".to_string())
+                .expect("Twitter token url is invalid"),
+        ),
+    )
+}
+
 #[derive(serde::Serialize)]
 struct TweetsRequest {
     text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply: Option<TweetReply>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media: Option<TweetMedia>,
+}
+
+#[derive(serde::Serialize)]
+struct TweetReply {
+    in_reply_to_tweet_id: String,
+}
+
+#[derive(serde::Serialize)]
+struct TweetMedia {
+    media_ids: Vec<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -73,89 +146,289 @@ struct TwitterError {
     message: String,
 }
 
+enum TweetError {
+    TooLong,
+    Other(Box<dyn std::error::Error>),
+}
+
 impl<DB: TokenDB, USClient: url_shortener::Client> Twitter<DB, USClient> {
+    /// Publishes the thread after its `published` parts. Twitter counts some characters
+    /// differently, so the `rest` of the text, which hasn't been published yet, is split again
+    /// with shorter parts until they are accepted. The published parts are never split again.
     async fn try_publish<'a>(
         &self,
         post: &Item,
+        extension: &IwtRssExtension,
         permashort_citation: &PermashortCitation,
-        tags: &[String],
+        media_ids: &[String],
+        published: &[String],
+        rest: Option<&str>,
     ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
+        let mut ids = published.to_vec();
         let mut length = 280;
-        let mut success_or_gave_up = false;
-        let mut result = Err(Box::new(IwtError::new(
-            "You shouldn't see this, we're trying to publish now...",
-        )) as Box<dyn std::error::Error>);
-
-        while !success_or_gave_up {
-            let text = text::shorten_with_permashort_citation(
-                post.description().unwrap(),
+        let mut rest = match rest {
+            Some(rest) => Some(String::from(rest)),
+            // The parts were published without keeping the rest, they were split at the full length
+            None if !ids.is_empty() => Some(rest_after(
+                &self.tweets(post, extension, permashort_citation, length, None, 0),
+                ids.len(),
+            )),
+            None => None,
+        };
+
+        loop {
+            let tweets = self.tweets(
+                post,
+                extension,
+                permashort_citation,
+                length,
+                rest.as_deref(),
+                ids.len(),
+            );
+
+            match self.send_thread(&tweets, media_ids, &ids).await {
+                Ok(mut ids) => {
+                    let id = ids.remove(0);
+                    return Ok(SyndicatedPost::new(&self.account, &id, post).with_thread(ids));
+                }
+                Err((sent, err)) => {
+                    if sent.len() > ids.len() {
+                        rest = Some(rest_after(&tweets, sent.len() - ids.len()));
+                        ids = sent;
+                    }
+
+                    match err {
+                        TweetError::TooLong if length > 210 => {
+                            log::info!("Length {} was too long, trying to reduce it...", length);
+                            length -= 5;
+                        }
+                        TweetError::TooLong => {
+                            return Err(partially_published(
+                                ids,
+                                rest,
+                                Box::new(IwtError::new(
+                                    "Couldn't reliably reduce the length of the post, gave up",
+                                )),
+                            ))
+                        }
+                        TweetError::Other(err) => return Err(partially_published(ids, rest, err)),
+                    }
+                }
+            }
+        }
+    }
+
+    /// The tweets along with the text they carry, a single one or the parts of the thread after
+    /// the `published` ones
+    fn tweets(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+        permashort_citation: &PermashortCitation,
+        length: usize,
+        rest: Option<&str>,
+        published: usize,
+    ) -> Vec<(String, String)> {
+        let status = extension.text(post, &self.account, self.thread);
+        if self.thread {
+            text::thread_rest_with_permashort_citation(
+                status,
+                rest,
+                published,
                 length,
                 permashort_citation,
-                tags,
+                &extension.tags,
+            )
+        } else {
+            let tweet = text::shorten_with_permashort_citation(
+                status,
+                length,
+                permashort_citation,
+                &extension.tags,
             );
+            vec![(tweet.clone(), tweet)]
+        }
+    }
+
+    /// Publishes the tweets as replies to each other, after the `published` ones. The images are
+    /// attached to the first tweet. On failure the IDs of the published tweets are returned along
+    /// with the error.
+    async fn send_thread(
+        &self,
+        tweets: &[(String, String)],
+        media_ids: &[String],
+        published: &[String],
+    ) -> Result<Vec<String>, (Vec<String>, TweetError)> {
+        let mut ids = published.to_vec();
+        if let Err(err) = self
+            .rate_limiter
+            .ensure(TWEETS, u32::try_from(tweets.len()).unwrap_or(u32::MAX))
+            .await
+        {
+            return Err((ids, TweetError::Other(Box::new(err))));
+        }
+
+        for (_, text) in tweets {
+            let media_ids = if ids.is_empty() {
+                media_ids.to_vec()
+            } else {
+                vec![]
+            };
+
+            match self
+                .send_tweet(text.clone(), ids.last().cloned(), media_ids)
+                .await
+            {
+                Ok(id) => ids.push(id),
+                Err(err) => return Err((ids, err)),
+            }
+        }
+
+        Ok(ids)
+    }
+
+    async fn send_tweet(
+        &self,
+        text: String,
+        in_reply_to: Option<String>,
+        media_ids: Vec<String>,
+    ) -> Result<String, TweetError> {
+        let request = self
+            .http_client
+            .post(format!("{}/2/tweets", self.base_uri))
+            .json(&TweetsRequest {
+                text,
+                reply: in_reply_to.map(|id| TweetReply {
+                    in_reply_to_tweet_id: id,
+                }),
+                media: (!media_ids.is_empty()).then_some(TweetMedia { media_ids }),
+            });
+
+        self.rate_limiter
+            .acquire(TWEETS)
+            .await
+            .map_err(|err| TweetError::Other(Box::new(err)))?;
+        let response = self
+            .authed_client
+            .authed_request(
+                request
+                    .build()
+                    .map_err(|err| TweetError::Other(Box::new(err)))?,
+            )
+            .await
+            .map_err(TweetError::Other)?;
+        self.rate_limiter
+            .check(TWEETS, &response)
+            .map_err(|err| TweetError::Other(Box::new(err)))?;
+
+        log::info!("Twitter response: {:?}", &response);
+
+        let status = response.status();
+
+        let body = response
+            .text()
+            .await
+            .map_err(|err| TweetError::Other(Box::new(err)))?;
+
+        if status.is_success() {
+            return serde_json::from_str::<TweetResponse>(&body)
+                .map(|response| response.data.id)
+                .map_err(|err| TweetError::Other(Box::new(err)));
+        }
+
+        match serde_json::from_str::<TwitterErrorResponse>(&body) {
+            Ok(error)
+                if error
+                    .errors
+                    .iter()
+                    .any(|e| e.message.starts_with("Your Tweet text is too long.")) =>
+            {
+                Err(TweetError::TooLong)
+            }
+            Ok(error) => Err(TweetError::Other(Box::new(IwtError::new(&format!(
+                "Couldn't publish tweet: {}",
+                error
+                    .errors
+                    .iter()
+                    .map(|e| e.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))))),
+            Err(err) => Err(TweetError::Other(Box::new(err))),
+        }
+    }
+
+    /// A tweet which doesn't exist anymore is considered to be deleted
+    async fn delete_tweet(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
+            .http_client
+            .delete(format!("{}/2/tweets/{id}", self.base_uri));
+
+        self.rate_limiter.acquire(DELETE).await?;
+        let response = self.authed_client.authed_request(request.build()?).await?;
+        self.rate_limiter.check(DELETE, &response)?;
+
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            let body = response.text().await?;
+            return Err(Box::new(IwtError::new(&format!(
+                "Couldn't delete tweet {id}, status: {status}, body: {body}"
+            ))));
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the image with its alt text, the upload is base64 encoded JSON, so the request
+    /// can be retried after a token refresh
+    async fn upload_media(&self, media: Media) -> Result<String, Box<dyn std::error::Error>> {
+        let request = self
+            .http_client
+            .post(format!("{}/2/media/upload", self.base_uri))
+            .json(&json!({
+                "media": base64::encode(&media.bytes),
+                "media_category": "tweet_image",
+                "media_type": media.mime_type,
+            }));
+
+        self.rate_limiter.acquire(MEDIA).await?;
+        let response = self.authed_client.authed_request(request.build()?).await?;
+        self.rate_limiter.check(MEDIA, &response)?;
 
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(Box::new(IwtError::new(&format!(
+                "Couldn't upload media, status: {status}, body: {body}"
+            ))));
+        }
+
+        let id = serde_json::from_str::<TweetResponse>(&body)?.data.id;
+
+        if let Some(alt) = media.alt {
             let request = self
                 .http_client
-                .post("https://api.twitter.com/2/tweets")
-                .json(&TweetsRequest { text });
-
-            result =
-                self.authed_client
-                    .authed_request(request.build().unwrap())
-                    .and_then(|response| async {
-                        log::info!("Twitter response: {:?}", &response);
-
-                        let status = response.status();
-
-                        let body = response.text().await.expect("Body should be available");
-
-                        if status.is_success() {
-                            success_or_gave_up = true;
-                            serde_json::from_str::<TweetResponse>(&body)
-                                .map(|response| {
-                                    SyndicatedPost::new(Network::Twitter, &response.data.id, post)
-                                })
-                                .map_err(|err| Box::new(err) as Box<dyn std::error::Error>)
-                        } else {
-                            match serde_json::from_str::<TwitterErrorResponse>(&body) {
-                                Ok(error) => {
-                                    let too_long_error = error.errors.iter().any(|e| {
-                                        e.message.starts_with("Your Tweet text is too long.")
-                                    });
-
-                                    if too_long_error && length > 210 {
-                                        log::info!(
-                                            "Length {} was too long, trying to reduce it...",
-                                            length
-                                        );
-
-                                        Err(Box::new(IwtError::new(
-                                            "You shouldn't see this, we're trying it again",
-                                        ))
-                                            as Box<dyn std::error::Error>)
-                                    } else {
-                                        success_or_gave_up = true;
-                                        Err(Box::new(IwtError::new(
-                                        "Couldn't reliably reduce the length of the post, gave up",
-                                    ))
-                                        as Box<dyn std::error::Error>)
-                                    }
-                                }
-                                Err(err) => {
-                                    success_or_gave_up = true;
-                                    Err(Box::new(err) as Box<dyn std::error::Error>)
-                                }
-                            }
-                        }
+                .post(format!("{}/2/media/metadata", self.base_uri))
+                .json(&json!({
+                    "id": id,
+                    "metadata": {
+                        "alt_text": { "text": text::shorten(&alt, MAX_ALT_TEXT_LENGTH) },
+                    },
+                }));
 
-                        // log::info!("Twitter response body: {}", &body);
-                    })
-                    .await;
+            let response = self.authed_client.authed_request(request.build()?).await?;
 
-            length -= 5;
+            if !response.status().is_success() {
+                log::warn!(
+                    "Couldn't set the alt text of media {}, status: {}",
+                    id,
+                    response.status()
+                );
+            }
         }
 
-        result
+        Ok(id)
     }
 }
 
@@ -173,11 +446,274 @@ impl<DB: TokenDB, WHClient: url_shortener::Client> Target for Twitter<DB, WHClie
             .put_uri(post.link.as_ref().unwrap())
             .await?;
 
-        self.try_publish(post, &permashort_citation, &extension.tags)
+        let mut media_ids = vec![];
+        for media in
+            media::download_all(&self.http_client, post, MAX_ATTACHMENTS, MAX_IMAGE_SIZE).await
+        {
+            media_ids.push(self.upload_media(media).await?);
+        }
+
+        self.try_publish(post, extension, &permashort_citation, &media_ids, &[], None)
+            .await
+    }
+
+    /// The images were attached to the first tweet, they aren't uploaded again
+    async fn resume<'a>(
+        &self,
+        post: &Item,
+        extension: &IwtRssExtension,
+        published: &[String],
+        rest: Option<&str>,
+    ) -> Result<SyndicatedPost, Box<dyn std::error::Error + 'a>> {
+        let permashort_citation = self
+            .url_shortener_client
+            .put_uri(post.link.as_ref().unwrap())
+            .await?;
+
+        self.try_publish(post, extension, &permashort_citation, &[], published, rest)
+            .await
+    }
+
+    /// Deletes the replies first, so that no part of the thread is left without its parent
+    async fn delete<'a>(
+        &self,
+        syndicated_post: &SyndicatedPost,
+    ) -> Result<(), Box<dyn std::error::Error + 'a>> {
+        for id in syndicated_post
+            .thread
+            .iter()
+            .rev()
+            .chain(std::iter::once(&syndicated_post.id))
+        {
+            self.delete_tweet(id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Twitter counts some characters differently, the published tweets may be shorter
+    fn preview(&self, post: &Item, extension: &IwtRssExtension) -> Preview {
+        Preview {
+            texts: self
+                .tweets(
+                    post,
+                    extension,
+                    &target::placeholder_citation(),
+                    280,
+                    None,
+                    0,
+                )
+                .into_iter()
+                .map(|(_, tweet)| tweet)
+                .collect(),
+            spoiler_text: None,
+            tags: extension.tags.clone(),
+            attachments: media::media_references(post)
+                .into_iter()
+                .take(MAX_ATTACHMENTS)
+                .collect(),
+        }
+    }
+
+    fn propagates_edits(&self) -> bool {
+        self.edits
+    }
+
+    fn account(&self) -> &Account {
+        &self.account
+    }
+}
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use oauth2::ClientId;
+    use rss::{GuidBuilder, Item};
+    use serde_json::{json, Value};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    use super::Twitter;
+    use crate::commons::url_shortener::stubs::StubUrlShortenerClient;
+    use crate::cross_publisher::rss_item_ext::IwtRssExtension;
+    use crate::cross_publisher::target::{PartiallyPublished, Target};
+    use crate::social::{Account, Network};
+    use crate::stubs::auth::token_db::stubs::StubTokenDB;
+
+    fn tweet(request: &Request) -> (String, Option<String>) {
+        let body = serde_json::from_slice::<Value>(&request.body).unwrap();
+        (
+            String::from(body["text"].as_str().unwrap()),
+            body["reply"]["in_reply_to_tweet_id"]
+                .as_str()
+                .map(String::from),
+        )
+    }
+
+    /// Twitter accepts `first_limit` characters in the first tweet and `reply_limit` in the
+    /// replies, the replies to tweet `n` get the ID `n + 1`
+    async fn mount_tweets(server: &MockServer, first_limit: usize, reply_limit: usize) {
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .and(move |request: &Request| {
+                let (text, in_reply_to) = tweet(request);
+                let limit = in_reply_to.map_or(first_limit, |_| reply_limit);
+                text.chars().count() > limit
+            })
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "errors": [{ "message": "Your Tweet text is too long. For more information on how Twitter determines text length, see https://github.com/twitter/twitter-text." }],
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .and(|request: &Request| tweet(request).1.is_none())
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "data": { "id": "1" },
+            })))
+            .mount(server)
+            .await;
+        for id in 1..10 {
+            Mock::given(method("POST"))
+                .and(path("/2/tweets"))
+                .and(move |request: &Request| tweet(request).1 == Some(id.to_string()))
+                .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                    "data": { "id": (id + 1).to_string() },
+                })))
+                .mount(server)
+                .await;
+        }
+    }
+
+    /// The accepted tweets of the thread, the last one sent in reply to each tweet
+    async fn thread(server: &MockServer) -> Vec<String> {
+        let tweets = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(tweet)
+            .collect::<Vec<_>>();
+
+        let mut thread = vec![];
+        let mut in_reply_to = None;
+        while let Some((text, _)) = tweets.iter().rev().find(|(_, to)| *to == in_reply_to) {
+            thread.push(text.clone());
+            in_reply_to = Some((thread.len()).to_string());
+        }
+        thread
+    }
+
+    fn words(thread: &[String]) -> Vec<String> {
+        thread
+            .iter()
+            .flat_map(|text| text.split_whitespace())
+            .filter(|word| word.starts_with("word"))
+            .map(String::from)
+            .collect()
+    }
+
+    fn twitter(server: &MockServer) -> Twitter<StubTokenDB, StubUrlShortenerClient> {
+        let mut twitter = Twitter::new(
+            Account::from(Network::Twitter),
+            ClientId::new(String::from("client_id")),
+            true,
+            false,
+            Rc::new(StubTokenDB::new()),
+            Rc::new(StubUrlShortenerClient),
+        )
+        .unwrap();
+        twitter.base_uri = server.uri();
+        twitter
+    }
+
+    fn item() -> Item {
+        Item {
+            link: Some(String::from("http://example.com/post-1")),
+            guid: Some(GuidBuilder::default().value("post-1").build()),
+            description: Some(format!(
+                "<p>{}</p>",
+                (0..120)
+                    .map(|i| format!("word{i}"))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )),
+            ..Default::default()
+        }
+    }
+
+    fn extension() -> IwtRssExtension {
+        IwtRssExtension {
+            target_networks: vec![],
+            content_warning: None,
+            tags: vec![],
+            deleted: false,
+            publish_at: None,
+            publish_offsets: vec![],
+            text: None,
+            summary: None,
+            overrides: vec![],
+            visibility: None,
+            language: None,
+            sensitive: None,
+        }
+    }
+
+    fn expected_words() -> Vec<String> {
+        (0..120).map(|i| format!("word{i}")).collect()
+    }
+
+    #[tokio::test]
+    async fn test_resume_publishes_the_rest_of_the_text_after_the_shortened_parts() {
+        let server = MockServer::start().await;
+        // The second part fails once, after the first one was shortened to 270 characters
+        Mock::given(method("POST"))
+            .and(path("/2/tweets"))
+            .and(|request: &Request| {
+                let (text, in_reply_to) = tweet(request);
+                in_reply_to.as_deref() == Some("1") && text.chars().count() <= 270
+            })
+            .respond_with(ResponseTemplate::new(503).set_body_json(json!({
+                "errors": [{ "message": "Service Unavailable" }],
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        mount_tweets(&server, 270, 270).await;
+        let twitter = twitter(&server);
+
+        let err = twitter.publish(&item(), &extension()).await.unwrap_err();
+        let partially_published = err.downcast_ref::<PartiallyPublished>().unwrap();
+        assert_eq!(partially_published.ids, vec![String::from("1")]);
+        let syndicated = twitter
+            .resume(
+                &item(),
+                &extension(),
+                &partially_published.ids,
+                partially_published.rest.as_deref(),
+            )
             .await
+            .unwrap();
+
+        let thread = thread(&server).await;
+        assert_eq!(syndicated.id, "1");
+        assert_eq!(syndicated.thread.len() + 1, thread.len());
+        assert!(thread[0].chars().count() > 265);
+        assert_eq!(words(&thread), expected_words());
     }
 
-    fn network(&self) -> Network {
-        Network::Twitter
+    #[tokio::test]
+    async fn test_publish_shortens_the_rest_of_the_text_if_a_later_part_is_too_long() {
+        let server = MockServer::start().await;
+        mount_tweets(&server, 270, 250).await;
+        let twitter = twitter(&server);
+
+        let syndicated = twitter.publish(&item(), &extension()).await.unwrap();
+
+        let thread = thread(&server).await;
+        assert_eq!(syndicated.thread.len() + 1, thread.len());
+        assert!(thread[0].chars().count() > 265);
+        assert!(thread[1..].iter().all(|tweet| tweet.chars().count() <= 250));
+        assert_eq!(words(&thread), expected_words());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,482 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use reqwest::header::{CONTENT_TYPE, LINK};
+use rss::Item;
+use rusqlite::{Connection, OptionalExtension};
+use scraper::{Html, Selector};
+use url::Url;
+
+use crate::commons::{db, link_header};
+use crate::error::Error;
+
+/// Attempts of a failing Webmention before it's given up
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay of the first retry, it's doubled on every failed attempt
+const BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+/// Sends Webmentions from the syndicated posts to the pages they link, see
+/// https://www.w3.org/TR/webmention/
+pub struct Sender {
+    http_client: reqwest::Client,
+    storage: SqliteSentWebmentionStorage,
+}
+
+impl Sender {
+    /// Fails if the HTTP client can't be created, there is no URI yet
+    pub fn new(storage: SqliteSentWebmentionStorage, timeout: Duration) -> Result<Self, Error> {
+        let http_client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|err| Error::Webmention {
+                uri: String::new(),
+                source: Box::new(err),
+            })?;
+
+        Ok(Self {
+            http_client,
+            storage,
+        })
+    }
+
+    /// Sends Webmentions to the links of the post which haven't been handled yet. Failing links
+    /// are only logged, they are retried with a backoff the next times the post is syndicated, up
+    /// to `MAX_ATTEMPTS` times.
+    pub async fn send_all(&self, post: &Item) -> Result<(), Error> {
+        let source = match post.link() {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let now = chrono::Utc::now().timestamp();
+
+        for target in links(post) {
+            if !self.storage.is_due(source, &target, now)? {
+                continue;
+            }
+
+            match send(&self.http_client, source, &target).await {
+                Ok(endpoint) => {
+                    match &endpoint {
+                        Some(endpoint) => {
+                            log::info!(
+                                "{} |> Webmention sent to {} via {}",
+                                source,
+                                target,
+                                endpoint
+                            );
+                        }
+                        None => log::debug!("{} |> {} has no Webmention endpoint", source, target),
+                    }
+                    self.storage.store(source, &target, endpoint.as_deref())?;
+                }
+                Err(err) => {
+                    let attempts =
+                        self.storage
+                            .record_failure(source, &target, &err.to_string(), now)?;
+                    if attempts >= MAX_ATTEMPTS {
+                        log::warn!(
+                            "{} |> Webmention to {} was given up after {} attempts: {}",
+                            source,
+                            target,
+                            attempts,
+                            err
+                        );
+                    } else {
+                        log::warn!("{} |> Webmention to {} failed: {}", source, target, err);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Discovers the Webmention endpoint of the target and sends the Webmention, returns the endpoint
+/// or `None` if the target doesn't accept Webmentions
+pub async fn send(
+    http_client: &reqwest::Client,
+    source: &str,
+    target: &str,
+) -> Result<Option<String>, Error> {
+    let webmention_error = |source| Error::Webmention {
+        uri: String::from(target),
+        source,
+    };
+    let endpoint = match discover_endpoint(http_client, target)
+        .await
+        .map_err(webmention_error)?
+    {
+        Some(endpoint) => endpoint,
+        None => return Ok(None),
+    };
+
+    http_client
+        .post(endpoint.clone())
+        .form(&[("source", source), ("target", target)])
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|err| webmention_error(Box::new(err)))?;
+
+    Ok(Some(endpoint.to_string()))
+}
+
+/// The first endpoint of the `Link` headers, or of the `<link>` and `<a>` elements of an HTML
+/// page, relative URLs are resolved against the final URL of the target
+async fn discover_endpoint(
+    http_client: &reqwest::Client,
+    target: &str,
+) -> Result<Option<Url>, Box<dyn std::error::Error>> {
+    let response = http_client.get(target).send().await?.error_for_status()?;
+    let base_url = response.url().clone();
+
+    let from_headers = response
+        .headers()
+        .get_all(LINK)
+        .iter()
+        .filter_map(|link| link.to_str().ok())
+        .find_map(|link| link_header::find_rel(link, "webmention"));
+    if let Some(endpoint) = from_headers {
+        return Ok(base_url.join(&endpoint).ok());
+    }
+
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    if !is_html {
+        return Ok(None);
+    }
+
+    let html = response.text().await?;
+    let endpoint = Html::parse_document(&html)
+        .select(&Selector::parse("link[href], a[href]").unwrap())
+        .find(|element| has_webmention_rel(element.value().attr("rel")))
+        .and_then(|element| element.value().attr("href"))
+        .and_then(|href| base_url.join(href).ok());
+
+    Ok(endpoint)
+}
+
+fn has_webmention_rel(rel: Option<&str>) -> bool {
+    rel.is_some_and(|rel| {
+        rel.split_whitespace()
+            .any(|rel| rel.eq_ignore_ascii_case("webmention"))
+    })
+}
+
+/// The HTTP(S) links of the post's content, resolved against its URL
+fn links(post: &Item) -> Vec<String> {
+    let (source, content) = match (post.link().map(Url::parse), post.description()) {
+        (Some(Ok(source)), Some(content)) => (source, content),
+        _ => return vec![],
+    };
+
+    let mut links = Vec::<String>::new();
+    for href in Html::parse_fragment(content)
+        .select(&Selector::parse("a[href]").unwrap())
+        .filter_map(|anchor| anchor.value().attr("href"))
+    {
+        if let Ok(mut link) = source.join(href) {
+            link.set_fragment(None);
+            let is_http = link.scheme() == "http" || link.scheme() == "https";
+            if is_http && link != source && !links.contains(&link.to_string()) {
+                links.push(link.to_string());
+            }
+        }
+    }
+
+    links
+}
+
+/// The Webmentions which have been handled, so that nothing is sent twice, and the failed ones
+/// with their attempts
+pub struct SqliteSentWebmentionStorage {
+    conn: Rc<Connection>,
+}
+
+impl SqliteSentWebmentionStorage {
+    pub fn new(conn: Rc<Connection>) -> Self {
+        Self { conn }
+    }
+
+    pub fn init_table(&self) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sent_webmention (
+                    source          TEXT NOT NULL,
+                    target          TEXT NOT NULL,
+                    endpoint        TEXT,
+                    sent_at         TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    error           TEXT,
+                    attempts        INTEGER NOT NULL DEFAULT 0,
+                    next_attempt_at INTEGER,
+                    PRIMARY KEY (source, target)
+                )",
+            (),
+        )?;
+
+        for (column, definition) in [
+            ("error", "TEXT"),
+            ("attempts", "INTEGER NOT NULL DEFAULT 0"),
+            ("next_attempt_at", "INTEGER"),
+        ] {
+            if !db::has_column(&self.conn, "sent_webmention", column)? {
+                self.conn.execute(
+                    &format!("ALTER TABLE sent_webmention ADD COLUMN {column} {definition}"),
+                    (),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The endpoint the Webmention was sent to, `Some(None)` if the target has no endpoint. The
+    /// failed Webmentions aren't found.
+    #[cfg(test)]
+    pub fn find(&self, source: &str, target: &str) -> rusqlite::Result<Option<Option<String>>> {
+        self.conn
+            .query_row(
+                "SELECT endpoint FROM sent_webmention
+                 WHERE source = ?1 AND target = ?2 AND error IS NULL",
+                (source, target),
+                |row| row.get("endpoint"),
+            )
+            .optional()
+    }
+
+    /// Whether the Webmention should be sent at `now`: it hasn't been handled yet, or it has
+    /// failed and its next attempt is due
+    pub fn is_due(&self, source: &str, target: &str, now: i64) -> rusqlite::Result<bool> {
+        let failure = self
+            .conn
+            .query_row(
+                "SELECT error IS NOT NULL, attempts, next_attempt_at FROM sent_webmention
+                 WHERE source = ?1 AND target = ?2",
+                (source, target),
+                |row| {
+                    Ok((
+                        row.get::<_, bool>(0)?,
+                        row.get::<_, u32>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        Ok(match failure {
+            None => true,
+            Some((false, _, _)) => false,
+            Some((true, attempts, next_attempt_at)) => {
+                attempts < MAX_ATTEMPTS && next_attempt_at.unwrap_or_default() <= now
+            }
+        })
+    }
+
+    pub fn store(
+        &self,
+        source: &str,
+        target: &str,
+        endpoint: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO sent_webmention (source, target, endpoint) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (source, target) DO UPDATE SET
+                   endpoint = excluded.endpoint,
+                   sent_at = CURRENT_TIMESTAMP,
+                   error = NULL,
+                   next_attempt_at = NULL",
+                (source, target, endpoint),
+            )
+            .map(|_| ())
+    }
+
+    /// Records the failed attempt and schedules the next one, returns the number of attempts
+    pub fn record_failure(
+        &self,
+        source: &str,
+        target: &str,
+        error: &str,
+        now: i64,
+    ) -> rusqlite::Result<u32> {
+        let attempts = self
+            .conn
+            .query_row(
+                "SELECT attempts FROM sent_webmention WHERE source = ?1 AND target = ?2",
+                (source, target),
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()?
+            .unwrap_or_default()
+            + 1;
+        let backoff = BACKOFF.saturating_mul(2_u32.saturating_pow(attempts - 1));
+        let next_attempt_at = now + i64::try_from(backoff.as_secs()).unwrap_or(i64::MAX - now);
+
+        self.conn.execute(
+            "INSERT INTO sent_webmention (source, target, error, attempts, next_attempt_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (source, target) DO UPDATE SET
+               error = excluded.error,
+               attempts = excluded.attempts,
+               next_attempt_at = excluded.next_attempt_at",
+            (source, target, error, attempts, next_attempt_at),
+        )?;
+
+        Ok(attempts)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use rss::Item;
+    use rusqlite::Connection;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::{links, Sender, SqliteSentWebmentionStorage, MAX_ATTEMPTS};
+
+    fn post(description: &str) -> Item {
+        Item {
+            link: Some(String::from("http://example.com/post-1")),
+            description: Some(String::from(description)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_links_are_resolved_and_deduplicated() {
+        let post = post(
+            r##"<p>See <a href="/post-0#comments">this</a>, <a href="http://example.com/post-0">this</a>,
+            <a href="https://other.example.com/">that</a>, <a href="mailto:someone@example.com">me</a>
+            and <a href="#top">here</a></p>"##,
+        );
+
+        assert_eq!(
+            links(&post),
+            vec![
+                String::from("http://example.com/post-0"),
+                String::from("https://other.example.com/")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webmentions_are_sent_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/header"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Link", "</endpoint-1>; rel=\"webmention\""),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/html"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"<html><head><link rel="webmention" href="/endpoint-2"></head></html>"#,
+                "text/html; charset=utf-8",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/none"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("<html></html>", "text/html"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        for (endpoint, target) in [("/endpoint-1", "header"), ("/endpoint-2", "html")] {
+            Mock::given(method("POST"))
+                .and(path(endpoint))
+                .and(body_string_contains(
+                    "source=http%3A%2F%2Fexample.com%2Fpost-1",
+                ))
+                .and(body_string_contains(format!("%2F{target}")))
+                .respond_with(ResponseTemplate::new(202))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let storage =
+            SqliteSentWebmentionStorage::new(Rc::new(Connection::open_in_memory().unwrap()));
+        storage.init_table().unwrap();
+        let sender = Sender::new(storage, Duration::from_secs(1)).unwrap();
+        let post = post(&format!(
+            r#"<a href="{0}/header">1</a> <a href="{0}/html">2</a> <a href="{0}/none">3</a>"#,
+            server.uri()
+        ));
+
+        sender.send_all(&post).await.unwrap();
+        sender.send_all(&post).await.unwrap();
+
+        assert_eq!(
+            sender
+                .storage
+                .find(
+                    "http://example.com/post-1",
+                    &format!("{}/none", server.uri())
+                )
+                .unwrap(),
+            Some(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_webmentions_are_retried_with_backoff_until_given_up() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/target"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("Link", "</endpoint>; rel=\"webmention\""),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/endpoint"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let storage =
+            SqliteSentWebmentionStorage::new(Rc::new(Connection::open_in_memory().unwrap()));
+        storage.init_table().unwrap();
+        let sender = Sender::new(storage, Duration::from_secs(1)).unwrap();
+        let source = "http://example.com/post-1";
+        let target = format!("{}/target", server.uri());
+        let post = post(&format!(r#"<a href="{target}">1</a>"#));
+
+        // The second run is before the next attempt
+        sender.send_all(&post).await.unwrap();
+        sender.send_all(&post).await.unwrap();
+
+        let storage = &sender.storage;
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(storage.find(source, &target).unwrap(), None);
+        assert!(!storage.is_due(source, &target, now).unwrap());
+        assert!(storage.is_due(source, &target, now + 15 * 60).unwrap());
+
+        for attempt in 2..=MAX_ATTEMPTS {
+            assert_eq!(
+                storage
+                    .record_failure(source, &target, "Internal Server Error", now)
+                    .unwrap(),
+                attempt
+            );
+        }
+        assert!(!storage.is_due(source, &target, i64::MAX).unwrap());
+
+        storage.store(source, &target, Some("/endpoint")).unwrap();
+        assert_eq!(
+            storage.find(source, &target).unwrap(),
+            Some(Some(String::from("/endpoint")))
+        );
+    }
+}
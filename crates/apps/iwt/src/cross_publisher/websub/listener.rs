@@ -0,0 +1,213 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    body::Bytes,
+    extract::Query,
+    http::{header::LINK, HeaderMap, StatusCode},
+    routing::get,
+    Extension, Router,
+};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use rss::Channel;
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use tokio::sync::mpsc::Sender;
+
+use super::Subscriptions;
+use crate::commons::link_header;
+use crate::cross_publisher::rss::read_channel;
+
+pub struct State {
+    secret: String,
+    subscriptions: Subscriptions,
+    /// The channels pushed by the hubs are sent to the daemon with their topic
+    pushed: Sender<(String, Channel)>,
+}
+
+impl State {
+    pub fn new(
+        secret: String,
+        subscriptions: Subscriptions,
+        pushed: Sender<(String, Channel)>,
+    ) -> Self {
+        Self {
+            secret,
+            subscriptions,
+            pushed,
+        }
+    }
+}
+
+/// Starts the callback endpoint in the background, returns the address it's bound to
+pub fn start(addr: &SocketAddr, state: State) -> Result<SocketAddr, axum::Error> {
+    let app = Router::new()
+        .route("/", get(verify_intent).post(receive_content))
+        // share the state with the request handlers
+        .layer(Extension(Arc::new(state)));
+
+    let server = axum::Server::try_bind(addr)
+        .map_err(axum::Error::new)?
+        .serve(app.into_make_service());
+    let local_addr = server.local_addr();
+
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            log::error!("WebSub listener stopped: {}", err);
+        }
+    });
+
+    log::info!("WebSub listener started on {}", local_addr);
+    Ok(local_addr)
+}
+
+/// Echoes the challenge of the hub, if the subscription to the topic was requested. The lease
+/// granted by the hub is kept, and the topic is forgotten when the hub denies the subscription.
+async fn verify_intent(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(state): Extension<Arc<State>>,
+) -> Result<String, StatusCode> {
+    let mode = params.get("hub.mode").map(String::as_str);
+    let topic = params.get("hub.topic").ok_or(StatusCode::BAD_REQUEST)?;
+    let mut subscriptions = state.subscriptions.lock().unwrap();
+
+    if mode == Some("denied") {
+        log::warn!(
+            "{} |> Subscription denied by the hub: {}",
+            topic,
+            params.get("hub.reason").map_or("no reason", String::as_str)
+        );
+        subscriptions.remove(topic);
+        return Ok(String::new());
+    }
+
+    let lease = match subscriptions.get_mut(topic) {
+        Some(lease) if mode == Some("subscribe") => lease,
+        _ => {
+            log::warn!("{} |> Unexpected {:?} verification", topic, mode);
+            return Err(StatusCode::NOT_FOUND);
+        }
+    };
+
+    let challenge = params.get("hub.challenge").ok_or(StatusCode::BAD_REQUEST)?;
+    *lease = params
+        .get("hub.lease_seconds")
+        .and_then(|lease_seconds| lease_seconds.parse().ok())
+        .map(Duration::from_secs);
+    log::info!("{} |> Subscription verified, lease: {:?}", topic, lease);
+
+    Ok(challenge.clone())
+}
+
+/// Forwards the pushed channel to the daemon with its topic. Content with a missing or invalid
+/// signature is acknowledged all the same, as the hub must not be able to tell it apart. Content
+/// of a topic which isn't subscribed is dropped.
+async fn receive_content(
+    headers: HeaderMap,
+    Extension(state): Extension<Arc<State>>,
+    body: Bytes,
+) -> StatusCode {
+    let signature = headers
+        .get("X-Hub-Signature")
+        .and_then(|signature| signature.to_str().ok());
+
+    if !signature.is_some_and(|signature| verify_signature(&state.secret, &body, signature)) {
+        log::warn!("Ignoring pushed content with an invalid signature");
+        return StatusCode::ACCEPTED;
+    }
+
+    let channel = match read_channel(&body) {
+        Ok(channel) => channel,
+        Err(err) => {
+            log::warn!("Couldn't parse the pushed content: {}", err);
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+
+    let topic = match topic(&headers, &channel) {
+        Some(topic) => topic,
+        None => {
+            log::warn!("Ignoring pushed content without a topic");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+
+    if !state.subscriptions.lock().unwrap().contains_key(&topic) {
+        log::warn!("{} |> Ignoring pushed content, not subscribed", topic);
+        return StatusCode::NOT_FOUND;
+    }
+
+    log::info!(
+        "{} |> Received {} pushed items",
+        topic,
+        channel.items().len()
+    );
+    if state.pushed.send((topic, channel)).await.is_err() {
+        log::error!("Pushed content dropped, the daemon has stopped");
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    StatusCode::ACCEPTED
+}
+
+/// The topic of the `Link` header with `rel="self"`, which the hubs send with the content, or the
+/// self link of the channel
+fn topic(headers: &HeaderMap, channel: &Channel) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|link| link.to_str().ok())
+        .find_map(|link| link_header::find_rel(link, "self"))
+        .or_else(|| {
+            channel
+                .atom_ext()?
+                .links()
+                .iter()
+                .find(|link| link.rel() == "self")
+                .map(|link| link.href().to_string())
+        })
+}
+
+/// Verifies the `X-Hub-Signature` header, i.e. `sha256=<hex encoded HMAC of the body>`
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let (method, signature) = match signature.split_once('=') {
+        Some((method, signature)) => (method, signature),
+        None => return false,
+    };
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    match method {
+        "sha1" => verify_hmac::<Hmac<Sha1>>(secret, body, &signature),
+        "sha256" => verify_hmac::<Hmac<Sha256>>(secret, body, &signature),
+        "sha384" => verify_hmac::<Hmac<Sha384>>(secret, body, &signature),
+        "sha512" => verify_hmac::<Hmac<Sha512>>(secret, body, &signature),
+        _ => false,
+    }
+}
+
+fn verify_hmac<M: Mac + KeyInit>(secret: &str, body: &[u8], signature: &[u8]) -> bool {
+    let mut mac = <M as KeyInit>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    mac.verify_slice(signature).is_ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::verify_signature;
+
+    #[test]
+    fn test_verify_signature_supports_the_websub_algorithms() {
+        // HMACs of "body" with the key "secret"
+        let sha1 = "sha1=a18991ff7e4513a1c2d2ee51e3a8e99ca891d9cd";
+        let sha512 = "sha512=de4a671e8233987c46a9048b8041bda6296901960022836c15c28023d3f66c3720449af241bdfe2e592c5b2273730cd1df763073e018e60d7313077c5342fde9";
+
+        assert!(verify_signature("secret", b"body", sha1));
+        assert!(verify_signature("secret", b"body", sha512));
+        assert!(!verify_signature("other", b"body", sha1));
+        assert!(!verify_signature("secret", b"other body", sha512));
+        assert!(!verify_signature("secret", b"body", "md5=00"));
+        assert!(!verify_signature("secret", b"body", "sha256"));
+        assert!(!verify_signature("secret", b"body", "sha256=not-hex"));
+    }
+}
@@ -0,0 +1,435 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rss::Channel;
+use url::{Host, Url};
+
+use super::rss::{read_channel, Client};
+use crate::config::WebSub;
+use crate::error::Error;
+use crate::IwtError;
+
+pub mod listener;
+
+/// The topics with a requested subscription and the lease granted by the hub once it verified the
+/// subscription, the listener only verifies these topics
+pub type Subscriptions = Arc<Mutex<HashMap<String, Option<Duration>>>>;
+
+/// The hub of a feed and the topic to subscribe to
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hub {
+    pub uri: String,
+    pub topic: String,
+}
+
+/// Subscribes to the WebSub hubs named by the feeds, see https://www.w3.org/TR/websub/
+pub struct Subscriber {
+    callback_uri: String,
+    secret: String,
+    lease_seconds: u64,
+    http_client: reqwest::Client,
+    subscriptions: Subscriptions,
+}
+
+impl Subscriber {
+    pub fn new(config: &WebSub) -> Self {
+        Self {
+            callback_uri: config.callback_uri.clone(),
+            secret: config.secret.clone(),
+            lease_seconds: config.lease_seconds,
+            http_client: reqwest::Client::new(),
+            subscriptions: Arc::default(),
+        }
+    }
+
+    pub fn subscriptions(&self) -> Subscriptions {
+        self.subscriptions.clone()
+    }
+
+    /// The lease of the topic, `None` until the hub has verified the subscription
+    pub fn lease(&self, topic: &str) -> Option<Duration> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .get(topic)
+            .copied()
+            .flatten()
+    }
+
+    /// Loads the feed to find its hub, only for the feeds which haven't been loaded by a poll
+    pub async fn discover(&self, feed_url: &str) -> Result<Option<Hub>, Error> {
+        let feed = self
+            .http_client
+            .get(feed_url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| Error::websub(feed_url, err))?
+            .bytes()
+            .await
+            .map_err(|err| Error::websub(feed_url, err))?;
+        let channel = read_channel(&feed).map_err(|err| Error::websub(feed_url, err))?;
+
+        Ok(hub(&channel, feed_url))
+    }
+
+    /// Requests the subscription from the hub, it's only active once the hub verified it with
+    /// the listener. Hubs without https are skipped, the secret of the signatures would be sent in
+    /// clear text.
+    pub async fn subscribe(&self, hub: &Hub) -> Result<(), Error> {
+        if !is_secure(&hub.uri) {
+            log::warn!(
+                "{} |> {} doesn't use https, the feed is polled instead",
+                hub.topic,
+                hub.uri
+            );
+            self.subscriptions.lock().unwrap().remove(&hub.topic);
+            return Ok(());
+        }
+
+        // A renewal keeps the current lease until the hub verifies it again
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .entry(hub.topic.clone())
+            .or_insert(None);
+
+        let lease_seconds = self.lease_seconds.to_string();
+        let params = [
+            ("hub.callback", self.callback_uri.as_str()),
+            ("hub.mode", "subscribe"),
+            ("hub.topic", hub.topic.as_str()),
+            ("hub.secret", self.secret.as_str()),
+            ("hub.lease_seconds", lease_seconds.as_str()),
+        ];
+
+        let response = self
+            .http_client
+            .post(&hub.uri)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|err| Error::websub(&hub.topic, err))?;
+        if !response.status().is_success() {
+            self.subscriptions.lock().unwrap().remove(&hub.topic);
+            return Err(Error::websub(
+                &hub.topic,
+                IwtError::new(&format!(
+                    "Subscription was refused by {}: {}",
+                    hub.uri,
+                    response.status()
+                )),
+            ));
+        }
+
+        log::info!("{} |> Subscription requested from {}", hub.topic, hub.uri);
+        Ok(())
+    }
+}
+
+/// https, or a hub on the same host
+fn is_secure(uri: &str) -> bool {
+    match Url::parse(uri) {
+        Ok(url) => {
+            url.scheme() == "https"
+                || match url.host() {
+                    Some(Host::Domain(domain)) => domain == "localhost",
+                    Some(Host::Ipv4(ip)) => ip.is_loopback(),
+                    Some(Host::Ipv6(ip)) => ip.is_loopback(),
+                    None => false,
+                }
+        }
+        Err(_) => false,
+    }
+}
+
+/// The hub and the topic named by the `<atom:link>`s of the channel, the topic falls back to the
+/// URL of the feed without a `self` link
+pub fn hub(channel: &Channel, feed_url: &str) -> Option<Hub> {
+    let links = channel.atom_ext()?.links();
+    let hub = links.iter().find(|link| link.rel() == "hub")?;
+    let topic = links
+        .iter()
+        .find(|link| link.rel() == "self")
+        .map_or(feed_url, |link| link.href());
+
+    Some(Hub {
+        uri: hub.href().to_string(),
+        topic: topic.to_string(),
+    })
+}
+
+/// Keeps the hubs of the channels loaded by the client, so that the polled feeds aren't loaded
+/// again to subscribe
+pub struct HubReader<'a, R: Client> {
+    client: &'a R,
+    hubs: RefCell<HashMap<String, Option<Hub>>>,
+}
+
+impl<'a, R: Client> HubReader<'a, R> {
+    pub fn new(client: &'a R) -> Self {
+        Self {
+            client,
+            hubs: RefCell::default(),
+        }
+    }
+
+    /// The hub of the loaded feed, `None` if the feed hasn't been loaded, i.e. it hasn't changed
+    pub fn hub(&self, url: &str) -> Option<Option<Hub>> {
+        self.hubs.borrow().get(url).cloned()
+    }
+}
+
+#[async_trait(?Send)]
+impl<'a, R: Client> Client for HubReader<'a, R> {
+    async fn get_channel(
+        &self,
+        url: &str,
+    ) -> Result<Option<Channel>, Box<dyn std::error::Error + 'static>> {
+        let channel = self.client.get_channel(url).await?;
+        if let Some(channel) = &channel {
+            self.hubs
+                .borrow_mut()
+                .insert(url.to_string(), hub(channel, url));
+        }
+        Ok(channel)
+    }
+
+    fn feed_syndicated(&self, url: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.client.feed_syndicated(url)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use tokio::sync::mpsc;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::listener::{self, State};
+    use super::{Hub, Subscriber};
+    use crate::config::WebSub;
+
+    fn feed(hub_uri: &str, self_uri: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+              <channel>
+                <title>Some blog</title>
+                <link>http://example.com/</link>
+                <atom:link rel="hub" href="{hub_uri}"/>
+                <atom:link rel="self" href="{self_uri}"/>
+                <item>
+                  <guid>post-1</guid>
+                  <link>http://example.com/post-1</link>
+                  <description>Some post</description>
+                </item>
+              </channel>
+            </rss>"#
+        )
+    }
+
+    fn sign(secret: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[tokio::test]
+    async fn test_pushed_content_is_received_from_the_stand_in_hub() {
+        let hub = MockServer::start().await;
+        let hub_uri = format!("{}/hub", hub.uri());
+        let topic = format!("{}/rss.xml", hub.uri());
+        let feed = feed(&hub_uri, &topic);
+
+        Mock::given(method("GET"))
+            .and(path("/rss.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(feed.clone()))
+            .mount(&hub)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hub"))
+            .and(body_string_contains("hub.mode=subscribe"))
+            .and(body_string_contains("hub.secret=s3cret"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&hub)
+            .await;
+
+        let (pushed, mut received) = mpsc::channel(10);
+        let mut subscriber = Subscriber::new(&WebSub {
+            callback_uri: String::new(),
+            listen: String::from("127.0.0.1:0"),
+            secret: String::from("s3cret"),
+            lease_seconds: 3600,
+        });
+        let addr = listener::start(
+            &"127.0.0.1:0".parse::<SocketAddr>().unwrap(),
+            State::new(String::from("s3cret"), subscriber.subscriptions(), pushed),
+        )
+        .unwrap();
+        let callback_uri = format!("http://{addr}/");
+        subscriber.callback_uri = callback_uri.clone();
+
+        let hub = subscriber.discover(&topic).await.unwrap().unwrap();
+        subscriber.subscribe(&hub).await.unwrap();
+        assert_eq!(subscriber.lease(&topic), None);
+
+        // The hub verifies the intent of the subscriber
+        let client = reqwest::Client::new();
+        let verification = client
+            .get(&callback_uri)
+            .query(&[
+                ("hub.mode", "subscribe"),
+                ("hub.topic", topic.as_str()),
+                ("hub.challenge", "some-challenge"),
+                ("hub.lease_seconds", "3600"),
+            ])
+            .send()
+            .await
+            .unwrap();
+        assert!(verification.status().is_success());
+        assert_eq!(verification.text().await.unwrap(), "some-challenge");
+        assert_eq!(subscriber.lease(&topic), Some(Duration::from_secs(3600)));
+
+        let unknown_topic = client
+            .get(&callback_uri)
+            .query(&[
+                ("hub.mode", "subscribe"),
+                ("hub.topic", "http://example.com/other.xml"),
+                ("hub.challenge", "some-challenge"),
+            ])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unknown_topic.status(), 404);
+
+        // Content with an invalid signature is acknowledged but ignored
+        let forged = client
+            .post(&callback_uri)
+            .header("X-Hub-Signature", sign("other", &feed))
+            .body(feed.clone())
+            .send()
+            .await
+            .unwrap();
+        assert!(forged.status().is_success());
+        assert!(received.try_recv().is_err());
+
+        // Content of a topic which isn't subscribed is dropped
+        let unsubscribed = client
+            .post(&callback_uri)
+            .header("X-Hub-Signature", sign("s3cret", &feed))
+            .header("Link", r#"<http://example.com/other.xml>; rel="self""#)
+            .body(feed.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unsubscribed.status(), 404);
+        assert!(received.try_recv().is_err());
+
+        let distribution = client
+            .post(&callback_uri)
+            .header("X-Hub-Signature", sign("s3cret", &feed))
+            .header(
+                "Link",
+                format!(r#"<{hub_uri}>; rel="hub", <{topic}>; rel="self""#),
+            )
+            .body(feed.clone())
+            .send()
+            .await
+            .unwrap();
+        assert!(distribution.status().is_success());
+
+        let (pushed_topic, channel) = received.recv().await.unwrap();
+        assert_eq!(pushed_topic, topic);
+        assert_eq!(channel.items().len(), 1);
+        assert_eq!(channel.items()[0].guid().unwrap().value(), "post-1");
+
+        // The topic is forgotten once the hub denies the subscription
+        let denial = client
+            .get(&callback_uri)
+            .query(&[("hub.mode", "denied"), ("hub.topic", topic.as_str())])
+            .send()
+            .await
+            .unwrap();
+        assert!(denial.status().is_success());
+        assert_eq!(subscriber.lease(&topic), None);
+    }
+
+    #[tokio::test]
+    async fn test_hubs_without_https_are_not_subscribed() {
+        let subscriber = Subscriber::new(&WebSub {
+            callback_uri: String::from("https://iwt.example.com/websub"),
+            listen: String::from("127.0.0.1:0"),
+            secret: String::from("s3cret"),
+            lease_seconds: 3600,
+        });
+
+        // The hub isn't requested, it doesn't exist
+        subscriber
+            .subscribe(&Hub {
+                uri: String::from("http://hub.example.invalid/"),
+                topic: String::from("http://example.com/rss.xml"),
+            })
+            .await
+            .unwrap();
+
+        assert!(subscriber.subscriptions().lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refused_subscriptions_are_websub_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&mock_server)
+            .await;
+        let subscriber = Subscriber::new(&WebSub {
+            callback_uri: String::from("https://iwt.example.com/websub"),
+            listen: String::from("127.0.0.1:0"),
+            secret: String::from("s3cret"),
+            lease_seconds: 3600,
+        });
+
+        let err = subscriber
+            .subscribe(&Hub {
+                uri: mock_server.uri(),
+                topic: String::from("http://example.com/rss.xml"),
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), "websub");
+        assert!(err.to_string().starts_with(
+            "WebSub error (http://example.com/rss.xml): IwtError: Subscription was refused"
+        ));
+        assert!(subscriber.subscriptions().lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_hub_and_topic_are_read_from_the_atom_links() {
+        let channel = super::read_channel(
+            feed("https://hub.example.com/", "http://example.com/rss.xml").as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            super::hub(&channel, "http://example.com/feed"),
+            Some(Hub {
+                uri: String::from("https://hub.example.com/"),
+                topic: String::from("http://example.com/rss.xml")
+            })
+        );
+
+        let without_hub = rss::Channel::default();
+        assert_eq!(super::hub(&without_hub, "http://example.com/feed"), None);
+    }
+}
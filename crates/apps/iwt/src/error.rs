@@ -0,0 +1,238 @@
+use std::fmt::Display;
+
+use crate::commons::url_shortener::ClientError;
+use crate::social::Account;
+
+/// Failures of the cross-publish path, they carry the feed, the post or the account they concern
+#[derive(Debug)]
+pub enum Error {
+    /// The config can't be read, parsed or it's invalid
+    Config {
+        message: String,
+        source: Option<Box<dyn std::error::Error>>,
+    },
+    /// The feed can't be loaded or parsed
+    Feed {
+        url: String,
+        source: Box<dyn std::error::Error>,
+    },
+    /// The item of the feed is malformed, i.e. it has no guid or no IWT extension
+    Item {
+        guid: Option<String>,
+        message: String,
+    },
+    /// The network failed to publish, edit or delete the post
+    Target {
+        account: Account,
+        guid: String,
+        source: Box<dyn std::error::Error>,
+    },
+    /// The tokens of the account are missing or can't be refreshed
+    Auth {
+        account: Account,
+        source: Box<dyn std::error::Error>,
+    },
+    /// The syndicated posts, the outbox or the tokens can't be read or written
+    Storage { source: Box<dyn std::error::Error> },
+    /// The permashortlink of the post can't be created
+    Shortener {
+        account: Account,
+        guid: String,
+        source: ClientError,
+    },
+    /// The Webmentions of the post can't be sent
+    Webmention {
+        uri: String,
+        source: Box<dyn std::error::Error>,
+    },
+    /// The hub of the feed can't be discovered, or it refused the subscription
+    Websub {
+        topic: String,
+        source: Box<dyn std::error::Error>,
+    },
+    /// The interactions of some syndicated copies can't be fetched or written, they are logged
+    Backfeed { failures: u32 },
+    /// The files, the sockets or the signals of the host can't be used
+    Io {
+        message: String,
+        source: Box<dyn std::error::Error>,
+    },
+}
+
+impl Error {
+    #[must_use]
+    pub fn config(message: &str) -> Self {
+        Self::Config {
+            message: String::from(message),
+            source: None,
+        }
+    }
+
+    #[must_use]
+    pub fn websub(topic: &str, source: impl Into<Box<dyn std::error::Error>>) -> Self {
+        Self::Websub {
+            topic: String::from(topic),
+            source: source.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn io(message: &str, source: impl Into<Box<dyn std::error::Error>>) -> Self {
+        Self::Io {
+            message: String::from(message),
+            source: source.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn item(guid: Option<&str>, message: &str) -> Self {
+        Self::Item {
+            guid: guid.map(String::from),
+            message: String::from(message),
+        }
+    }
+
+    /// The failure of the target, the errors of the URL shortener are told apart
+    #[must_use]
+    pub fn target(account: &Account, guid: &str, source: Box<dyn std::error::Error>) -> Self {
+        let source = match source.downcast::<Self>() {
+            Ok(error) => return *error,
+            Err(source) => source,
+        };
+
+        match source.downcast::<ClientError>() {
+            Ok(source) => Self::Shortener {
+                account: account.clone(),
+                guid: String::from(guid),
+                source: *source,
+            },
+            Err(source) => Self::Target {
+                account: account.clone(),
+                guid: String::from(guid),
+                source,
+            },
+        }
+    }
+
+    /// Name of the variant, i.e. to tell the failures apart in reports
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Config { .. } => "config",
+            Self::Feed { .. } => "feed",
+            Self::Item { .. } => "item",
+            Self::Target { .. } => "target",
+            Self::Auth { .. } => "auth",
+            Self::Storage { .. } => "storage",
+            Self::Shortener { .. } => "shortener",
+            Self::Webmention { .. } => "webmention",
+            Self::Websub { .. } => "websub",
+            Self::Backfeed { .. } => "backfeed",
+            Self::Io { .. } => "io",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Config {
+                message,
+                source: Some(source),
+            } => write!(f, "Config error: {message}: {source}"),
+            Self::Config {
+                message,
+                source: None,
+            } => write!(f, "Config error: {message}"),
+            Self::Feed { url, source } => write!(f, "Feed error ({url}): {source}"),
+            Self::Item {
+                guid: Some(guid),
+                message,
+            } => write!(f, "Item error ({guid}): {message}"),
+            Self::Item {
+                guid: None,
+                message,
+            } => write!(f, "Item error: {message}"),
+            Self::Target {
+                account,
+                guid,
+                source,
+            } => write!(f, "Target error ({guid} on {account}): {source}"),
+            Self::Auth { account, source } => write!(f, "Auth error ({account}): {source}"),
+            Self::Storage { source } => write!(f, "Storage error: {source}"),
+            Self::Shortener {
+                account,
+                guid,
+                source,
+            } => write!(f, "Shortener error ({guid} on {account}): {source}"),
+            Self::Webmention { uri, source } => write!(f, "Webmention error ({uri}): {source}"),
+            Self::Websub { topic, source } => write!(f, "WebSub error ({topic}): {source}"),
+            Self::Backfeed { failures } => {
+                write!(f, "Backfeed error: {failures} failures, see the logs")
+            }
+            Self::Io { message, source } => write!(f, "IO error: {message}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Config { source, .. } => source.as_deref(),
+            Self::Item { .. } | Self::Backfeed { .. } => None,
+            Self::Feed { source, .. }
+            | Self::Target { source, .. }
+            | Self::Auth { source, .. }
+            | Self::Storage { source }
+            | Self::Webmention { source, .. }
+            | Self::Websub { source, .. }
+            | Self::Io { source, .. } => Some(source.as_ref()),
+            Self::Shortener { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(source: rusqlite::Error) -> Self {
+        Self::Storage {
+            source: Box::new(source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::commons::url_shortener::ClientError;
+    use crate::social::{Account, Network};
+    use crate::IwtError;
+
+    use super::Error;
+
+    #[test]
+    fn test_target_errors_are_told_apart_by_their_source() {
+        let account = Account::from(Network::Mastodon);
+
+        let target = Error::target(&account, "post-1", Box::new(IwtError::new("Bad Gateway")));
+        let shortener = Error::target(
+            &account,
+            "post-1",
+            Box::new(ClientError {
+                message: String::from("Connection refused"),
+            }),
+        );
+        let item = Error::target(
+            &account,
+            "post-1",
+            Box::new(Error::item(Some("post-1"), "The item has no link")),
+        );
+
+        assert_eq!(
+            (target.kind(), shortener.kind(), item.kind()),
+            ("target", "shortener", "item")
+        );
+        assert_eq!(
+            shortener.to_string(),
+            "Shortener error (post-1 on mastodon): UrlShortener Client Error: Connection refused"
+        );
+    }
+}
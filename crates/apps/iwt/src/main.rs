@@ -10,6 +10,7 @@ mod app_auth;
 pub mod commons;
 pub mod config;
 mod cross_publisher;
+pub mod error;
 pub mod social;
 
 use config::Config;
@@ -57,10 +58,38 @@ enum Command {
         #[clap(subcommand)]
         sub_command: app_auth::AuthSubcommand,
     },
-    /// Cross publish posts
+    /// Cross publish posts, exits with 1 if any of them has failed
     CrossPublish {
         #[clap(long, action)]
         dry_run: bool,
+        /// Print the outcome of every post and target as a table or as JSON
+        #[clap(long, value_enum, default_value_t = cross_publisher::report::Format::Table)]
+        format: cross_publisher::report::Format,
+    },
+    /// Cross publish posts periodically, until SIGTERM is received
+    Daemon,
+    /// Delete the syndicated copies of a post, i.e. of a post which has been removed from the feeds
+    Unpublish {
+        /// Guid of the original post
+        #[clap(value_parser)]
+        guid: String,
+        #[clap(long, action)]
+        dry_run: bool,
+    },
+    /// Forward the replies, likes and reposts of the syndicated posts to the original posts
+    Backfeed {
+        /// Write the interactions as JF2 files into the directory
+        #[clap(long, value_parser)]
+        output: String,
+    },
+    /// Export the permalinks of the syndicated posts as JSON, i.e. for u-syndication links
+    ExportSyndication {
+        /// Key the permalinks by the guid or by the URI of the original post
+        #[clap(long, value_enum, default_value_t = cross_publisher::export::Key::Uri)]
+        key: cross_publisher::export::Key,
+        /// Write the JSON into the file instead of the standard output
+        #[clap(long, value_parser)]
+        output: Option<String>,
     },
 }
 
@@ -75,7 +104,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     match cli.command {
         Command::AppAuth { sub_command } => app_auth::execute(sub_command, &config).await,
-        Command::CrossPublish { dry_run } => cross_publisher::execute(&config, dry_run).await,
+        Command::CrossPublish { dry_run, format } => {
+            let report = cross_publisher::execute(&config, dry_run).await?;
+            report.write(format, &mut std::io::stdout())?;
+            if !report.is_success() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Daemon => Ok(cross_publisher::daemon::execute(&config).await?),
+        Command::Unpublish { guid, dry_run } => {
+            Ok(cross_publisher::unpublish(&config, &guid, dry_run).await?)
+        }
+        Command::Backfeed { output } => {
+            Ok(cross_publisher::backfeed::execute(&config, &output).await?)
+        }
+        Command::ExportSyndication { key, output } => {
+            Ok(cross_publisher::export::execute(&config, key, output.as_deref()).await?)
+        }
     }
 }
 
@@ -5,10 +5,11 @@ use rusqlite::types::{FromSql, FromSqlError};
 
 use crate::commons::SqlConversionError;
 
-#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Hash)]
 pub enum Network {
     Twitter,
     Mastodon,
+    Bluesky,
 }
 
 impl Display for Network {
@@ -16,6 +17,42 @@ impl Display for Network {
         match self {
             Network::Twitter => write!(f, "twitter"),
             Network::Mastodon => write!(f, "mastodon"),
+            Network::Bluesky => write!(f, "bluesky"),
+        }
+    }
+}
+
+/// A named account on a social network, the name is unique across the configured targets
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Hash)]
+pub struct Account {
+    pub network: Network,
+    pub name: String,
+}
+
+impl Account {
+    #[must_use]
+    pub fn new(network: Network, name: &str) -> Self {
+        Self {
+            network,
+            name: name.to_string(),
+        }
+    }
+}
+
+impl From<Network> for Account {
+    /// The default account of the network, named after the network
+    fn from(network: Network) -> Self {
+        let name = network.to_string();
+        Self { network, name }
+    }
+}
+
+impl Display for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name == self.network.to_string() {
+            write!(f, "{}", self.network)
+        } else {
+            write!(f, "{}:{}", self.network, self.name)
         }
     }
 }
@@ -25,9 +62,28 @@ impl FromSql for Network {
         value.as_str().and_then(|n| match n {
             "twitter" => Ok(Network::Twitter),
             "mastodon" => Ok(Network::Mastodon),
+            "bluesky" => Ok(Network::Bluesky),
             n => Err(FromSqlError::Other(Box::new(SqlConversionError {
                 message: format!("Unknown social network: {n}"),
             }))),
         })
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Account, Network};
+
+    #[test]
+    fn test_default_account_is_displayed_as_the_network() {
+        assert_eq!(Account::from(Network::Mastodon).to_string(), "mastodon");
+    }
+
+    #[test]
+    fn test_named_account_is_displayed_with_the_network() {
+        assert_eq!(
+            Account::new(Network::Mastodon, "fosstodon").to_string(),
+            "mastodon:fosstodon"
+        );
+    }
 }
\ No newline at end of file
@@ -5,7 +5,7 @@ pub mod stubs {
     use oauth2::{AccessToken, RefreshToken};
 
     use crate::commons::auth::token_db::TokenDB;
-    use crate::social::Network;
+    use crate::social::Account;
 
     pub struct StubTokenDB {
         access_token: Mutex<AccessToken>,
@@ -31,7 +31,7 @@ pub mod stubs {
     impl TokenDB for StubTokenDB {
         fn get_access_token(
             &self,
-            _social_network: &Network,
+            _account: &Account,
         ) -> Result<oauth2::AccessToken, Box<dyn std::error::Error>> {
             let guard = self.access_token.lock().unwrap();
             Ok((*guard).clone())
@@ -39,7 +39,7 @@ pub mod stubs {
 
         fn get_refresh_token(
             &self,
-            _social_network: &Network,
+            _account: &Account,
         ) -> Result<oauth2::RefreshToken, Box<dyn std::error::Error>> {
             let guard = self.refresh_token.lock().unwrap();
             Ok((*guard).clone())
@@ -47,7 +47,7 @@ pub mod stubs {
 
         fn store(
             &self,
-            _social_network: &Network,
+            _account: &Account,
             access_token: &AccessToken,
             refresh_tokem: &RefreshToken,
         ) -> Result<(), Box<dyn std::error::Error>> {
@@ -59,5 +59,16 @@ pub mod stubs {
 
             Ok(())
         }
+
+        fn store_access_token(
+            &self,
+            _account: &Account,
+            access_token: &AccessToken,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            let mut guard = self.access_token.lock().unwrap();
+            *guard = access_token.clone();
+
+            Ok(())
+        }
     }
 }
\ No newline at end of file